@@ -11,14 +11,14 @@ nodes(name, type, geom)
 VALUES ('C1','charging_station', ST_MakePoint(1,0,0));
 
 INSERT INTO 
-nodes(name, type, geom)
-VALUES ('S1','item_stocker', ST_MakePoint(1,3,0));
+nodes(name, type, geom, side)
+VALUES ('S1','item_stocker', ST_MakePoint(1,3,0), 'negy');
 INSERT INTO 
-nodes(name, type, geom)
-VALUES ('S2','item_stocker', ST_MakePoint(-1,1,0));
+nodes(name, type, geom, side, slot_first, slot_last)
+VALUES ('S2','item_stocker', ST_MakePoint(-1,1,0), 'negy', 1, 8);
 INSERT INTO 
-nodes(name, type, geom)
-VALUES ('S3','shipping_dock', ST_MakePoint(-1,2,0));
+nodes(name, type, geom, side, port)
+VALUES ('S3','shipping_dock', ST_MakePoint(-1,2,0), 'negy', 'dock');
 
 INSERT INTO 
 nodes(name, type, geom)
@@ -12,9 +12,8 @@ JOIN track.nodes src ON e.begin_node_id = src.id
 JOIN track.nodes tgt ON e.end_node_id = tgt.id
 ORDER BY src.name, tgt.name;
 
-UPDATE edges
-SET is_lock = false
-WHERE begin_node_id = 11 OR end_node_id = 11;
+DELETE FROM node_locks
+WHERE node_id = 11;
 
 -- should error
 -- INSERT INTO 
@@ -43,7 +42,11 @@ SELECT * FROM
     FROM edges AS e
     JOIN nodes AS n_src ON e.begin_node_id = n_src.id
     JOIN nodes AS n_tgt ON e.end_node_id = n_tgt.id
-	WHERE is_lock=false',
+	WHERE e.end_node_id NOT IN (
+		SELECT l.node_id FROM node_locks AS l JOIN nodes AS n ON n.id = l.node_id
+		WHERE l.expires_at > now()
+		GROUP BY l.node_id, n.capacity
+		HAVING count(*) >= n.capacity)',
 		(SELECT id FROM nodes WHERE name = 'S2'),
 		(SELECT id FROM nodes WHERE name = 'S1'),
 		true)
@@ -81,7 +84,11 @@ SELECT * FROM
     FROM edges AS e
     JOIN nodes AS n_src ON e.begin_node_id = n_src.id
     JOIN nodes AS n_tgt ON e.end_node_id = n_tgt.id
-	WHERE is_lock=false',
+	WHERE e.end_node_id NOT IN (
+		SELECT l.node_id FROM node_locks AS l JOIN nodes AS n ON n.id = l.node_id
+		WHERE l.expires_at > now()
+		GROUP BY l.node_id, n.capacity
+		HAVING count(*) >= n.capacity)',
         (SELECT id FROM current_node),
         (SELECT id FROM shortest_node),
 		true
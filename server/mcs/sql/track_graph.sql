@@ -1,5 +1,7 @@
 SET search_path TO track;
 
+DROP TABLE node_locks;
+DROP TABLE maintenance;
 DROP TABLE edges;
 DROP TABLE nodes;
 DROP TYPE NodeType;
@@ -12,7 +14,18 @@ CREATE TYPE NodeType AS ENUM(
 	'parking_station',
 	'shipping_dock',
 	'item_stocker',
-	'fluid_stocker'
+	'fluid_stocker',
+	'lift'
+);
+
+-- Named like the vehicle's sides table.
+CREATE TYPE Side AS ENUM(
+	'negy',
+	'posy',
+	'negz',
+	'posz',
+	'negx',
+	'posx'
 );
 
 CREATE TYPE Direction AS ENUM(
@@ -25,7 +38,14 @@ CREATE TABLE nodes(
 	name VARCHAR(50) NOT NULL UNIQUE,
 	type NodeType NOT NULL,
 	geom geometry(PointZ) NOT NULL,
-	comment TEXT
+	capacity INT NOT NULL DEFAULT 1 CHECK (capacity > 0),
+	-- How vehicles work with the inventory at the node, unset is left to the vehicle.
+	side Side,
+	slot_first INT CHECK (slot_first > 0),
+	slot_last INT CHECK (slot_last >= slot_first),
+	port VARCHAR(50),
+	comment TEXT,
+	CHECK ((slot_first IS NULL) = (slot_last IS NULL))
 );
 
 CREATE TABLE IF NOT EXISTS edges(
@@ -34,8 +54,24 @@ CREATE TABLE IF NOT EXISTS edges(
 	end_node_id INT REFERENCES nodes(id),
 	direction VARCHAR(15),
 	cost FLOAT,
-	reverse_cost FLOAT,
-	is_lock BOOL DEFAULT false
+	reverse_cost FLOAT
+);
+
+CREATE TABLE IF NOT EXISTS node_locks(
+	node_id INT REFERENCES nodes(id) ON DELETE CASCADE,
+	vehicle_id INT NOT NULL,
+	expires_at TIMESTAMPTZ NOT NULL,
+	PRIMARY KEY (node_id, vehicle_id)
+);
+
+-- Nodes and edges taken out of service, routing avoids them until enabled again.
+CREATE TABLE IF NOT EXISTS maintenance(
+	resource_type VARCHAR(4) NOT NULL CHECK (resource_type IN ('node', 'edge')),
+	resource_id INT NOT NULL,
+	reason TEXT NOT NULL,
+	operator VARCHAR(50) NOT NULL,
+	disabled_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+	PRIMARY KEY (resource_type, resource_id)
 );
 
 CREATE OR REPLACE FUNCTION calculate_manhatten_distance(
@@ -82,6 +118,10 @@ RETURNS TRIGGER
 LANGUAGE plpgsql
 AS $$
 BEGIN
+	-- imported layouts carry their own costs
+	IF TG_OP = 'INSERT' AND NEW.cost IS NOT NULL THEN
+		RETURN NEW;
+	END IF;
 	NEW.cost := calculate_manhatten_distance(NEW.begin_node_id, NEW.end_node_id);
 	NEW.reverse_cost := CASE 
 		WHEN NEW.direction='bidirectional' THEN NEW.cost
@@ -120,3 +160,28 @@ CREATE TRIGGER update_edge_weight_trigger
 BEFORE UPDATE OF geom ON nodes
 FOR EACH ROW
 EXECUTE FUNCTION update_edge_weight_on_node_change();
+
+CREATE OR REPLACE FUNCTION notify_track_changed()
+RETURNS TRIGGER
+LANGUAGE plpgsql
+AS $$
+BEGIN
+	PERFORM pg_notify('track_changed', TG_TABLE_NAME);
+	RETURN NULL;
+END;
+$$;
+
+CREATE TRIGGER notify_track_changed_trigger
+AFTER INSERT OR DELETE OR UPDATE OF name, type, geom, capacity, side, slot_first, slot_last, port ON nodes
+FOR EACH STATEMENT
+EXECUTE FUNCTION notify_track_changed();
+
+CREATE TRIGGER notify_track_changed_trigger
+AFTER INSERT OR DELETE OR UPDATE OF begin_node_id, end_node_id, direction, cost, reverse_cost ON edges
+FOR EACH STATEMENT
+EXECUTE FUNCTION notify_track_changed();
+
+CREATE TRIGGER notify_track_changed_trigger
+AFTER INSERT OR DELETE OR UPDATE ON maintenance
+FOR EACH STATEMENT
+EXECUTE FUNCTION notify_track_changed();
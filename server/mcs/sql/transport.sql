@@ -3,7 +3,8 @@ SET search_path to transport;
 CREATE TYPE transport.STATE AS ENUM (
 	'pending',
 	'processing',
-	'completed'
+	'completed',
+	'failed'
 );
 
 CREATE table transport.item (
@@ -12,7 +13,17 @@ CREATE table transport.item (
 	date_updated TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
 	begin_node_name CHAR(50) NOT NULL REF,
 	end_node_name CHAR(50) NOT NULL,
-	state transport.STATE DEFAULT 'pending'
+	state transport.STATE DEFAULT 'pending',
+	vehicle_id INT,
+	fail_reason TEXT,
+	cargo_name TEXT,
+	quantity INT CHECK (quantity > 0),
+	from_slot_first INT CHECK (from_slot_first > 0),
+	from_slot_last INT CHECK (from_slot_last >= from_slot_first),
+	to_slot_first INT CHECK (to_slot_first > 0),
+	to_slot_last INT CHECK (to_slot_last >= to_slot_first),
+	CHECK ((from_slot_first IS NULL) = (from_slot_last IS NULL)),
+	CHECK ((to_slot_first IS NULL) = (to_slot_last IS NULL))
 );
 
 CREATE table transport.fluid(
@@ -21,7 +32,17 @@ CREATE table transport.fluid(
 	date_updated TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
 	begin_node_name CHAR(50) NOT NULL,
 	end_node_name CHAR(50) NOT NULL,
-	state transport.STATE DEFAULT 'pending'
+	state transport.STATE DEFAULT 'pending',
+	vehicle_id INT,
+	fail_reason TEXT,
+	cargo_name TEXT,
+	quantity INT CHECK (quantity > 0),
+	from_slot_first INT CHECK (from_slot_first > 0),
+	from_slot_last INT CHECK (from_slot_last >= from_slot_first),
+	to_slot_first INT CHECK (to_slot_first > 0),
+	to_slot_last INT CHECK (to_slot_last >= to_slot_first),
+	CHECK ((from_slot_first IS NULL) = (from_slot_last IS NULL)),
+	CHECK ((to_slot_first IS NULL) = (to_slot_last IS NULL))
 );
 
 CREATE TYPE transport.ToolType as ENUM(
@@ -39,7 +60,9 @@ CREATE table transport.use_tool(
 	date_updated TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
 	end_node_name CHAR(50) NOT NULL,
 	tool_type transport.ToolType NOT NULL, 
-	state transport.STATE DEFAULT 'pending'
+	state transport.STATE DEFAULT 'pending',
+	vehicle_id INT,
+	fail_reason TEXT
 );
 
 CREATE OR REPLACE FUNCTION transport.update_modified_date()
@@ -66,3 +89,27 @@ CREATE TRIGGER update_date_trigger
 	BEFORE UPDATE ON transport.use_tool
 	FOR EACH ROW
 	EXECUTE FUNCTION transport.update_modified_date();
+
+CREATE TYPE transport.SKILL AS ENUM(
+	'item',
+	'fluid',
+	'use_tool'
+);
+
+CREATE table transport.vehicle(
+	id INT PRIMARY KEY,
+	date_created TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+	date_updated TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+	name TEXT NOT NULL UNIQUE,
+	skill transport.SKILL NOT NULL,
+	tool_type transport.ToolType,
+	battery JSONB,
+	enabled BOOLEAN NOT NULL DEFAULT TRUE,
+	home_node_name CHAR(50),
+	CHECK ((skill = 'use_tool') = (tool_type IS NOT NULL))
+);
+
+CREATE TRIGGER update_date_trigger
+	BEFORE UPDATE ON transport.vehicle
+	FOR EACH ROW
+	EXECUTE FUNCTION transport.update_modified_date();
@@ -1,15 +1,25 @@
-use std::ops::Range;
-
-pub const ITEM_VEHICLE_ID_RANGE: Range<i32> = 2000..4000;
-pub const FLUID_VEHICLE_ID_RANGE: Range<i32> = 4000..6000;
-pub const USE_TOOL_WRENCH_VEHICLE_ID_RANGE: Range<i32> = 100..200;
-pub const USE_TOOL_SOLDER_VEHICLE_ID_RANGE: Range<i32> = 200..300;
-pub const USE_TOOL_CROWBAR_VEHICLE_ID_RANGE: Range<i32> = 300..400;
-pub const USE_TOOL_SCREWDRIVER_VEHICLE_ID_RANGE: Range<i32> = 400..500;
-pub const USE_TOOL_WIRENIPPER_VEHICLE_ID_RANGE: Range<i32> = 500..600;
-pub const USE_TOOL_SOFT_HAMMER_VEHICLE_ID_RANGE: Range<i32> = 600..700;
-
 pub const VEHICLE_ONLINE_UPDATE_TIMEOUT: i64 = 5;
 pub const VEHICLE_SCHEDULE_TIME: u64 = 2;
 
 pub const VEHICLE_TOOL_WARN_LEVEL: f32 = 0.3;
+pub const VEHICLE_CHARGE_LEVEL: f32 = 0.3;
+pub const VEHICLE_CHARGED_LEVEL: f32 = 0.95;
+pub const VEHICLE_CRITICAL_LEVEL: f32 = 0.1;
+pub const VEHICLE_OPPORTUNISTIC_LEVEL: f32 = 0.6;
+pub const VEHICLE_INTERRUPTIBLE_LEVEL: f32 = 0.5;
+
+pub const TRACK_LOCK_LEASE_TIME: i64 = 30;
+pub const TRACK_LOCK_REAP_TIME: u64 = 5;
+pub const TRACK_RESERVATION_MARGIN: i64 = 2;
+
+pub const VEHICLE_SPEED: f64 = 1.0;
+pub const LIFT_SPEED: f64 = 0.5;
+pub const VEHICLE_ACTION_TIME: i64 = 5;
+pub const VEHICLE_ACTION_RETRIES: u32 = 2;
+
+pub const DEADLOCK_DETECT_TIME: u64 = 3;
+
+pub const EVENT_FEED_CAPACITY: usize = 256;
+
+pub const TASK_QUERY_DEFAULT_LIMIT: i64 = 50;
+pub const TASK_QUERY_MAX_LIMIT: i64 = 500;
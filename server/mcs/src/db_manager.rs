@@ -1,6 +1,6 @@
 use std::sync::Arc;
 
-use sqlx::{PgPool, Postgres, pool::PoolConnection, query};
+use sqlx::{PgPool, Postgres, pool::PoolConnection, postgres::PgListener, query};
 
 #[derive(Debug)]
 pub struct DbManager {
@@ -29,4 +29,10 @@ impl DbManager {
             .unwrap();
         Ok(conn)
     }
+
+    pub async fn listen(&self, channel: &str) -> Result<PgListener, sqlx::Error> {
+        let mut listener = PgListener::connect_with(&self.pool).await?;
+        listener.listen(channel).await?;
+        Ok(listener)
+    }
 }
@@ -1,3 +1,6 @@
 pub mod constant;
 pub mod db_manager;
+mod mcs;
 mod transport;
+
+pub use mcs::{Error, Mcs, McsConfig};
@@ -0,0 +1,59 @@
+use std::path::Path;
+
+use mcs::{Mcs, McsConfig};
+use tokio::signal::{self, unix::SignalKind};
+use tracing::{info, level_filters::LevelFilter, warn};
+use tracing_subscriber::{fmt, prelude::*};
+
+fn init_tracing() -> tracing_appender::non_blocking::WorkerGuard {
+    let file_appender = tracing_appender::rolling::daily("logs", "mcs");
+    let (non_blocking, guard) = tracing_appender::non_blocking(file_appender);
+
+    let file_layer = fmt::layer()
+        .with_ansi(false)
+        .with_writer(non_blocking)
+        .with_filter(LevelFilter::INFO);
+    let stdout_layer = fmt::layer()
+        .with_level(true)
+        .with_writer(std::io::stdout)
+        .with_filter(LevelFilter::DEBUG);
+
+    let collector = tracing_subscriber::registry()
+        .with(file_layer)
+        .with(stdout_layer);
+    tracing::subscriber::set_global_default(collector).expect("Tracing collect error");
+    guard
+}
+
+async fn shutdown_signal() {
+    let mut terminate =
+        signal::unix::signal(SignalKind::terminate()).expect("Install SIGTERM handler error");
+    tokio::select! {
+        _ = signal::ctrl_c() => {},
+        _ = terminate.recv() => {},
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    dotenvy::dotenv().ok();
+    let _guard = init_tracing();
+
+    // mcs [config.json], a missing default config runs on defaults.
+    let config = match std::env::args().nth(1) {
+        Some(path) => McsConfig::load(&path).expect("Load config error"),
+        None if Path::new("mcs.json").exists() => {
+            McsConfig::load("mcs.json").expect("Load config error")
+        }
+        None => {
+            warn!("no mcs.json, running on default config.");
+            McsConfig::default()
+        }
+    };
+    info!("Starting mcs...");
+
+    let mcs = Mcs::start(config).await.expect("Start mcs error");
+    shutdown_signal().await;
+    info!("Shutting down, draining in-flight requests...");
+    mcs.stop().await;
+}
@@ -1,73 +1,136 @@
-mod db;
-pub mod prelude;
-mod transport;
+use std::{fs, path::Path, sync::Arc};
 
-use std::sync::Arc;
+use jsonrpsee::server::ServerHandle;
+use serde::Deserialize;
+use sqlx::postgres::PgPoolOptions;
+use tracing::info;
 
-use prelude::*;
-use tokio::sync::mpsc;
-
-use crate::mcs::{
-    db::DbClient,
-    transport::{DispatchExec, PingPong, TrackGraph},
+use crate::{
+    db_manager::DbManager,
+    transport::{
+        schedule::{self, PgTaskStore, ScheduleExec, Stopper},
+        server::Server,
+        track::{self, Graph, PgStore},
+    },
 };
 
+#[derive(Debug)]
+pub enum Error {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    /// Neither the config nor `DATABASE_URL` name a database.
+    NoDatabase,
+    /// Settings the server can not run with.
+    Config(String),
+    Db(sqlx::Error),
+    Track(track::Error),
+    Server(std::io::Error),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
 pub struct McsConfig {
-    pub sql: Arc<sqlx::PgPool>,
-    pub transport_server_addr: String,
-    pub timeout: i64,
-    pub tool_warn_level: f32,
-    pub track_graph: TrackGraph,
+    /// Falls back to `DATABASE_URL`.
+    pub database_url: Option<String>,
+    pub max_connections: u32,
+    pub rpc_addr: String,
+    pub schedule: schedule::Config,
+}
+
+impl Default for McsConfig {
+    fn default() -> Self {
+        Self {
+            database_url: None,
+            max_connections: 5,
+            rpc_addr: "0.0.0.0:5000".to_string(),
+            schedule: schedule::Config::default(),
+        }
+    }
+}
+
+impl McsConfig {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let json = fs::read_to_string(path).map_err(Error::Io)?;
+        let config: Self = serde_json::from_str(&json).map_err(Error::Json)?;
+        config.schedule.check().map_err(Error::Config)?;
+        Ok(config)
+    }
+
+    fn database_url(&self) -> Result<String> {
+        match &self.database_url {
+            Some(url) => Ok(url.clone()),
+            None => std::env::var("DATABASE_URL").map_err(|_| Error::NoDatabase),
+        }
+    }
 }
 
 pub struct Mcs {
-    transport_server_addr: String,
-    vehicle_pingpong: PingPong,
-    transport_server_builder: transport::ServerBuilder,
-    db_client: DbClient,
+    handle: ServerHandle,
+    stopper: Option<Stopper>,
 }
 
 impl Mcs {
-    pub fn new(config: McsConfig) -> Self {
-        let (sender, receiver) = mpsc::channel(200);
-        let vehicle_pingpong = PingPong::new(sender.clone(), config.timeout);
-        let dispatch_exec = DispatchExec::new(receiver, config.tool_warn_level, config.track_graph);
-        let transport_server_addr = config.transport_server_addr;
-        let mut transport_server_builder = transport::ServerBuilder::new(dispatch_exec);
-        let db_client = DbClient::new(config.sql);
-
-        let register = transport_server_builder.register();
-        register.register_async_method("trans_item", async |params, dispatch_exec, _| {});
+    /// Connects the database, loads the track and serves RPC until stopped.
+    pub async fn start(config: McsConfig) -> Result<Self> {
+        let pool = PgPoolOptions::new()
+            .max_connections(config.max_connections)
+            .connect(&config.database_url()?)
+            .await
+            .map_err(Error::Db)?;
+        let db = DbManager::new(pool);
+        let track_graph = Graph::new(PgStore::new(db.clone()))
+            .await
+            .map_err(Error::Track)?;
+        let task_store = Arc::new(PgTaskStore::new(db.clone()));
+        let mut schedule_exec =
+            ScheduleExec::new(track_graph, db, task_store, config.schedule).await;
+        let stopper = schedule_exec.take_stopper();
+        let handle = Server::run(&config.rpc_addr, schedule_exec)
+            .await
+            .map_err(Error::Server)?;
+        info!("mcs serving on {}.", config.rpc_addr);
+        Ok(Self { handle, stopper })
+    }
 
-        Self {
-            vehicle_pingpong,
-            transport_server_addr,
-            transport_server_builder,
-            db_client,
+    /// Stops taking connections, waits for calls in flight to be answered, then stops planning
+    /// and writes the task events still queued.
+    pub async fn stop(self) {
+        if self.handle.stop().is_ok() {
+            self.handle.stopped().await;
         }
+        if let Some(stopper) = self.stopper {
+            stopper.stop().await;
+        }
+        info!("mcs stopped.");
     }
-    // pub fn new(config: MCSConfig) -> Self {
-    //     let (vehicle_pingpong, vehicle_request, vehicle_exec) =
-    //         vehicle::dispatch(config.timeout, config.tool_warn_level, config.track_graph);
-    //     Self {
-    //         sql: config.sql,
-    //         vehicle_pingpong,
-    //         vehicle_request,
-    //         vehicle_exec,
-    //     }
-    // }
+}
 
-    pub async fn run(self) {
-        let transport_server = self.transport_server_builder.build(self.transport_server_addr).await.expect("Start transport server error!");
-        tokio::spawn(async move {
-            self.vehicle_pingpong.task().await;
-        })
-        .await
-        .unwrap();
-        tokio::spawn(async move {
-            transport_server.task().await;
-        })
-        .await
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config() {
+        let config: McsConfig = serde_json::from_str(
+            r#"{"rpc_addr": "127.0.0.1:5001", "schedule": {"vehicle": {"battery": {"fluid": {"charge": 0.4}}}}}"#,
+        )
         .unwrap();
+        assert_eq!(config.rpc_addr, "127.0.0.1:5001");
+        assert_eq!(config.max_connections, 5);
+        assert_eq!(config.schedule.planner_interval, 2);
+        let battery = &config.schedule.vehicle.battery;
+        assert_eq!(battery.fluid.charge, Some(0.4));
+        assert_eq!(battery.default.resume, 0.95);
+        assert!(serde_json::from_str::<McsConfig>(r#"{"max_connections": "5"}"#).is_err());
+        config.schedule.check().unwrap();
+
+        let config: McsConfig =
+            serde_json::from_str(r#"{"schedule": {"planner_interval": 0}}"#).unwrap();
+        assert!(config.schedule.check().is_err());
+        let config: McsConfig =
+            serde_json::from_str(r#"{"schedule": {"vehicle": {"online_timeout": -1}}}"#).unwrap();
+        assert!(config.schedule.check().is_err());
     }
 }
@@ -1,5 +1,5 @@
 pub mod prelude;
-mod schedule;
-mod server;
-mod track;
+pub mod schedule;
+pub mod server;
+pub mod track;
 mod vehicle;
@@ -1,62 +1,58 @@
 use std::{collections::HashMap, sync::Arc};
 
-use sqlx::{PgConnection, prelude::FromRow};
+use chrono::{TimeDelta, Utc};
 use tokio::{
-    sync::RwLock,
+    sync::{RwLock, watch},
+    task::JoinHandle,
     time::{self},
 };
-use tracing::error;
+use tracing::{error, info, warn};
 
 use crate::{
     constant,
-    db_manager::DbManager,
     transport::{
-        schedule::{Error, Result},
+        schedule::{
+            Error, Load, Result, TaskKind, TaskStore,
+            store::{ItemFluidRow, UseToolRow},
+        },
         track::{self, Graph},
         vehicle::{ActionSequence, ActionSequenceBuilder, Skill, ToolType, Vehicle},
     },
 };
 
-#[derive(Debug, FromRow)]
-struct ItemFluidRow {
-    id: i32,
-    begin_node_name: String,
-    end_node_name: String,
-}
-
-#[derive(Debug, FromRow)]
-struct UseToolRow {
-    id: i32,
-    end_node_name: String,
-    tool_type: ToolType,
-}
-
 #[derive(Debug)]
 pub struct ActionPlanner {
     vehicles: Arc<RwLock<HashMap<i32, Vehicle>>>,
     track_graph: Arc<Graph>,
-    db: Arc<DbManager>,
+    tasks: Arc<dyn TaskStore>,
+    interval: u64,
 }
 
 impl ActionPlanner {
     pub fn run(
         vehicles: Arc<RwLock<HashMap<i32, Vehicle>>>,
         track_graph: Arc<Graph>,
-        db: Arc<DbManager>,
-    ) {
+        tasks: Arc<dyn TaskStore>,
+        interval: u64,
+        stop: watch::Receiver<bool>,
+    ) -> JoinHandle<()> {
         let planner = Self {
             vehicles,
             track_graph,
-            db,
+            tasks,
+            interval,
         };
-        tokio::spawn(async move { planner.task().await });
+        tokio::spawn(async move { planner.task(stop).await })
     }
 
-    async fn task(mut self) {
-        let mut interval =
-            time::interval(time::Duration::from_secs(constant::VEHICLE_SCHEDULE_TIME));
+    /// A round in progress is finished before stopping.
+    async fn task(mut self, mut stop: watch::Receiver<bool>) {
+        let mut interval = time::interval(time::Duration::from_secs(self.interval));
         loop {
-            interval.tick().await;
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = stop.changed() => break,
+            }
             if let Err(e) = self.plan().await {
                 if let Error::VehicleBusy = e {
                 } else {
@@ -66,48 +62,83 @@ impl ActionPlanner {
         }
     }
 
+    /// An unknown target fails right away, a target no idle vehicle can reach fails once every
+    /// idle vehicle was tried.
     async fn find_idle_vehicle_shortest_path_by_skill(
         &self,
         to: &str,
         skill: Skill,
-    ) -> Option<(i32, track::Path)> {
-        let mut result: Vec<(i32, track::Path)> = Vec::new();
+    ) -> Result<(i32, track::Path)> {
+        let mut result: Vec<(bool, i32, track::Path)> = Vec::new();
+        let mut no_route = None;
         for (id, vehicle) in self.vehicles.read().await.iter() {
             if skill != *vehicle.skill() || !vehicle.idle().await {
                 continue;
             }
+            let charging = vehicle.is_charging().await;
 
-            if let Ok(path) = self.track_graph.find_path(&vehicle.node().map_err(|e|{
+            let Ok(node) = vehicle.node().map_err(|e|{
                 error!("vehicle({}): current node not find in idle. may be not in trackgraph or dont init. error type is {:?}.", {id}, {e});
-            }).ok()?.name, to).await {
-                result.push((*id, path));
+            }) else {
+                continue;
+            };
+            match self
+                .track_graph
+                .find_timed_path(*id, &node.name, to, Utc::now())
+                .await
+            {
+                Ok(path) => result.push((charging, *id, path)),
+                Err(e @ track::Error::NoRoute { .. }) => no_route = Some(e),
+                Err(e) => return Err(Error::PathFind(e)),
             }
         }
-        result.sort_by(|a, b| a.1.len().cmp(&b.1.len()));
-        result.first().cloned()
+        // Vehicles on a charger only go when nobody else can.
+        result.sort_by_key(|(charging, _, path)| (*charging, path.len()));
+        match (result.into_iter().next(), no_route) {
+            (Some((_, id, path)), _) => Ok((id, path)),
+            (None, Some(e)) => Err(Error::PathFind(e)),
+            (None, None) => Err(Error::VehicleBusy),
+        }
+    }
+
+    /// The loaded leg starts once the vehicle reached `begin_node_name` and picked up.
+    async fn begin_to_end_path(
+        &self,
+        vehicle_id: i32,
+        to_begin_path: &track::Path,
+        begin_node_name: &str,
+        end_node_name: &str,
+    ) -> Result<track::Path> {
+        let depart = Utc::now()
+            + self.track_graph.travel_time(to_begin_path).await
+            + TimeDelta::seconds(constant::VEHICLE_ACTION_TIME);
+        self.track_graph
+            .find_timed_path(vehicle_id, begin_node_name, end_node_name, depart)
+            .await
+            .map_err(Error::PathFind)
     }
 
     async fn trans_item_actions(
         &self,
         begin_node_name: &str,
         end_node_name: &str,
+        load: &Load,
     ) -> Result<(i32, ActionSequence)> {
         let (id, to_begin_path) = self
             .find_idle_vehicle_shortest_path_by_skill(begin_node_name, Skill::Item)
-            .await
-            .ok_or(Error::VehicleBusy)?;
+            .await?;
         let begin_to_end_path = self
-            .track_graph
-            .find_path(begin_node_name, end_node_name)
-            .await
-            .map_err(Error::Db)?;
+            .begin_to_end_path(id, &to_begin_path, begin_node_name, end_node_name)
+            .await?;
         Ok((
             id,
             ActionSequenceBuilder::new()
                 .move_path(&to_begin_path)
                 .suck()
+                .cargo(&load.cargo, load.from_slots)
                 .move_path(&begin_to_end_path)
                 .drop()
+                .cargo(&load.cargo, load.to_slots)
                 .build(),
         ))
     }
@@ -116,31 +147,31 @@ impl ActionPlanner {
         &self,
         begin_node_name: &str,
         end_node_name: &str,
+        load: &Load,
     ) -> Result<(i32, ActionSequence)> {
         let (id, to_begin_path) = self
             .find_idle_vehicle_shortest_path_by_skill(begin_node_name, Skill::Fluid)
-            .await
-            .ok_or(Error::VehicleBusy)?;
+            .await?;
 
         let begin_to_end_path = self
-            .track_graph
-            .find_path(begin_node_name, end_node_name)
-            .await
-            .map_err(Error::Db)?;
+            .begin_to_end_path(id, &to_begin_path, begin_node_name, end_node_name)
+            .await?;
 
         let to_shipping_dock_path = self
             .track_graph
             .find_shipping_dock_path(end_node_name)
             .await
-            .map_err(Error::Db)?;
+            .map_err(Error::PathFind)?;
 
         Ok((
             id,
             ActionSequenceBuilder::new()
                 .move_path(&to_begin_path)
                 .suck()
+                .cargo(&load.cargo, load.from_slots)
                 .move_path(&begin_to_end_path)
                 .fill()
+                .cargo(&load.cargo, load.to_slots)
                 .move_path(&to_shipping_dock_path)
                 .drop()
                 .build(),
@@ -154,8 +185,7 @@ impl ActionPlanner {
     ) -> Result<(i32, ActionSequence)> {
         let (id, to_end_path) = self
             .find_idle_vehicle_shortest_path_by_skill(node_name, Skill::UseTool(tool_type))
-            .await
-            .ok_or(Error::VehicleBusy)?;
+            .await?;
 
         Ok((
             id,
@@ -166,108 +196,116 @@ impl ActionPlanner {
         ))
     }
 
-    async fn get_item_rows(&self, conn: &mut PgConnection) -> Result<Vec<ItemFluidRow>> {
-        sqlx::query_as::<_, ItemFluidRow>(
-            "
-            SELECT id,begin_node_name,end_node_name
-            FROM item
-            WHERE state = 'pending'
-            ORDER BY date_created
-            LIMIT 20;
-        ",
-        )
-        .fetch_all(&mut *conn)
-        .await
-        .map_err(Error::Db)
-    }
+    /// Hands the planned sequence to its vehicle. A task visiting a node the track lacks, a first
+    /// stop no vehicle can get to, or nodes no track joins, is failed instead of waiting forever,
+    /// one blocked by maintenance, full nodes or where the vehicles are stays pending for the next
+    /// round.
+    async fn dispatch(
+        &self,
+        kind: TaskKind,
+        task_id: i32,
+        route: &[&str],
+        planned: Result<(i32, ActionSequence)>,
+    ) -> Result<()> {
+        let (vehicle_id, actions) = match planned {
+            Ok(planned) => planned,
+            Err(Error::PathFind(e @ track::Error::UnknownNode(_))) => {
+                return self.fail_task(kind, task_id, &e).await;
+            }
+            Err(Error::PathFind(e @ track::Error::NoRoute { .. })) => {
+                if let Err(e) = self.track_graph.check_reachable(route[0]).await {
+                    return self.fail_task(kind, task_id, &e).await;
+                }
+                for leg in route.windows(2) {
+                    if let Err(e) = self.track_graph.check_connected(leg[0], leg[1]).await {
+                        return self.fail_task(kind, task_id, &e).await;
+                    }
+                }
+                info!("{}({}): {}, left pending.", kind.name(), task_id, e);
+                return Ok(());
+            }
+            Err(e) => return Err(e),
+        };
 
-    async fn get_fluid_rows(&self, conn: &mut PgConnection) -> Result<Vec<ItemFluidRow>> {
-        sqlx::query_as::<_, ItemFluidRow>(
-            "
-            SELECT id, begin_node_name, end_node_name
-            FROM fluid
-            WHERE state = 'pending'
-            ORDER BY date_created
-            LIMIT 20;
-        ",
-        )
-        .fetch_all(&mut *conn)
-        .await
-        .map_err(Error::Db)
+        self.vehicles
+            .write()
+            .await
+            .get_mut(&vehicle_id)
+            .ok_or(Error::VehicleBusy)?
+            .processing(task_id, actions)
+            .await
+            .map_err(|e| {
+                warn!(
+                    "{}({}): vehicle({}) can not take it, {}.",
+                    kind.name(),
+                    task_id,
+                    vehicle_id,
+                    e
+                );
+                Error::VehicleBusy
+            })
     }
 
-    async fn get_use_tool_rows(&self, conn: &mut PgConnection) -> Result<Vec<UseToolRow>> {
-        sqlx::query_as::<_, UseToolRow>(
-            "
-            SELECT id, end_node_name, tool_type
-            FROM use_tool
-            WHERE state = 'pending'
-            ORDER BY date_created
-            LIMIT 20;
-        ",
-        )
-        .fetch_all(&mut *conn)
-        .await
-        .map_err(Error::Db)
+    async fn fail_task(&self, kind: TaskKind, task_id: i32, reason: &track::Error) -> Result<()> {
+        warn!("{}({}): task failed, {}.", kind.name(), task_id, reason);
+        self.tasks
+            .fail(kind, task_id, &reason.to_string())
+            .await
+            .map_err(Error::Db)
     }
 
     async fn plan_tran_item(&self, item_rows: Vec<ItemFluidRow>) -> Result<()> {
         for row in item_rows {
-            let (vehicle_id, actions) = self
-                .trans_item_actions(&row.begin_node_name.trim(), &row.end_node_name.trim())
+            let route = [row.begin_node_name.trim(), row.end_node_name.trim()];
+            let planned = self
+                .trans_item_actions(route[0], route[1], &row.load())
+                .await;
+            self.dispatch(TaskKind::Item, row.id, &route, planned)
                 .await?;
-
-            self.vehicles
-                .write()
-                .await
-                .get_mut(&vehicle_id)
-                .ok_or(Error::VehicleBusy)?
-                .processing(row.id, actions)
-                .await
-                .map_err(|_| Error::VehicleBusy)?;
         }
         Ok(())
     }
 
     async fn plan_tran_fluid(&self, item_rows: Vec<ItemFluidRow>) -> Result<()> {
         for row in item_rows {
-            let (vehicle_id, actions) = self
-                .trans_fluid_actions(&row.begin_node_name.trim(), &row.end_node_name.trim())
+            let route = [row.begin_node_name.trim(), row.end_node_name.trim()];
+            let planned = self
+                .trans_fluid_actions(route[0], route[1], &row.load())
+                .await;
+            self.dispatch(TaskKind::Fluid, row.id, &route, planned)
                 .await?;
-
-            self.vehicles
-                .write()
-                .await
-                .get_mut(&vehicle_id)
-                .ok_or(Error::VehicleBusy)?
-                .processing(row.id, actions)
-                .await
-                .map_err(|_| Error::VehicleBusy)?;
         }
         Ok(())
     }
 
     async fn plan_use_tool(&self, item_rows: Vec<UseToolRow>) -> Result<()> {
         for row in item_rows {
-            let (vehicle_id, actions) = self
-                .use_tool_actions(&row.end_node_name.trim(), row.tool_type)
+            let route = [row.end_node_name.trim()];
+            let planned = self.use_tool_actions(route[0], row.tool_type).await;
+            self.dispatch(TaskKind::UseTool, row.id, &route, planned)
                 .await?;
-
-            self.vehicles
-                .write()
-                .await
-                .get_mut(&vehicle_id)
-                .ok_or(Error::VehicleBusy)?
-                .processing(row.id, actions)
-                .await
-                .map_err(|_| Error::VehicleBusy)?;
         }
         Ok(())
     }
 
+    /// Parked vehicles only top up their battery while nothing waits for their skill.
+    async fn update_pending_work(&self, pending: &[Skill]) {
+        for vehicle in self.vehicles.write().await.values_mut() {
+            let pending_work = pending.contains(vehicle.skill());
+            vehicle.set_pending_work(pending_work);
+        }
+    }
+
     async fn plan(&mut self) -> Result<()> {
-        let mut conn = self.db.transport().await.map_err(Error::Db)?;
-        let item_rows = self.get_item_rows(&mut *conn).await?;
+        let mut pending = Vec::new();
+        let item_rows = self
+            .tasks
+            .pending_trans(TaskKind::Item)
+            .await
+            .map_err(Error::Db)?;
+        if !item_rows.is_empty() {
+            pending.push(Skill::Item);
+        }
         if let Err(e) = self.plan_tran_item(item_rows).await {
             if let Error::VehicleBusy = e {
             } else {
@@ -275,7 +313,14 @@ impl ActionPlanner {
             }
         }
 
-        let fluid_rows = self.get_fluid_rows(&mut *conn).await?;
+        let fluid_rows = self
+            .tasks
+            .pending_trans(TaskKind::Fluid)
+            .await
+            .map_err(Error::Db)?;
+        if !fluid_rows.is_empty() {
+            pending.push(Skill::Fluid);
+        }
         if let Err(e) = self.plan_tran_fluid(fluid_rows).await {
             if let Error::VehicleBusy = e {
             } else {
@@ -283,7 +328,12 @@ impl ActionPlanner {
             }
         }
 
-        let use_tool_rows = self.get_use_tool_rows(&mut *conn).await?;
+        let use_tool_rows = self.tasks.pending_use_tool().await.map_err(Error::Db)?;
+        pending.extend(
+            use_tool_rows
+                .iter()
+                .map(|row| Skill::UseTool(row.tool_type.clone())),
+        );
         if let Err(e) = self.plan_use_tool(use_tool_rows).await {
             if let Error::VehicleBusy = e {
             } else {
@@ -291,6 +341,7 @@ impl ActionPlanner {
             }
         }
 
+        self.update_pending_work(&pending).await;
         Ok(())
     }
 }
@@ -299,48 +350,127 @@ impl ActionPlanner {
 mod tests {
     use super::*;
 
-    use dotenvy::dotenv;
-    use sqlx::postgres::PgPoolOptions;
+    use crate::transport::{
+        schedule::{MemoryTaskStore, query::TaskState},
+        track::{Side, Slots, offline_sample},
+        vehicle::{Action, Cargo},
+    };
 
     #[tokio::test]
-    async fn get_rows() {
-        dotenv().ok();
-        let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
-        println!("Connecting to database: {}", database_url);
-        let pool = PgPoolOptions::new()
-            .max_connections(5)
-            .connect(&database_url)
-            .await
-            .expect("Failed to create pool");
-
-        let db = DbManager::new(pool);
-        let vehicles = Arc::new(RwLock::new(HashMap::new()));
-        let track_graph = Arc::new(Graph::new(db.clone()).await);
-        {
-            vehicles
-                .write()
-                .await
-                .insert(2500, Vehicle::new(2500, track_graph.clone()).await);
+    async fn plan() {
+        let (_, track_graph) = offline_sample().await;
+        let track_graph = Arc::new(track_graph);
+        let tasks = Arc::new(MemoryTaskStore::default());
+
+        let mut vehicle = Vehicle::new(2500, Skill::Item, track_graph.clone()).await;
+        for _ in 0..3 {
+            vehicle.get_action(&(-1.0, 2.0, 0.0).into(), 1.0).await;
         }
+        assert!(vehicle.idle().await);
+        let vehicles = Arc::new(RwLock::new(HashMap::from([(2500, vehicle)])));
 
-        // let _a = sqlx::query_as::<_, ItemFluidRow>(
-        //     "
-        //     SELECT id,begin_node_name,end_node_name
-        //     FROM item
-        //     WHERE state = 'pending'
-        //     LIMIT 20;
-        // ",
-        // )
-        // .fetch_all(&mut *db.transport().await.unwrap())
-        // .await
-        // .unwrap();
+        let load = Load::default();
+        tasks
+            .add_trans(TaskKind::Item, "S2", "S9", &load)
+            .await
+            .unwrap();
+        tasks
+            .add_trans(TaskKind::Item, "S2", "S1", &load)
+            .await
+            .unwrap();
+        tasks.add_use_tool("S1", ToolType::Wrench).await.unwrap();
 
         let mut action_planner = ActionPlanner {
-            db,
+            vehicles: vehicles.clone(),
+            track_graph,
+            tasks: tasks.clone(),
+            interval: constant::VEHICLE_SCHEDULE_TIME,
+        };
+        action_planner.plan().await.unwrap();
+
+        // A node the track lacks fails the task, the next one goes to the vehicle.
+        let failed = tasks.task(TaskKind::Item, 1).await.unwrap();
+        assert_eq!(failed.state, TaskState::Failed);
+        assert_eq!(failed.fail_reason.as_deref(), Some("unknown node S9"));
+        assert_eq!(vehicles.read().await[&2500].task_id(), Some(2));
+        // Nobody carries a wrench, so it waits.
+        let waiting = tasks.task(TaskKind::UseTool, 1).await.unwrap();
+        assert_eq!(waiting.state, TaskState::Pending);
+        assert_eq!(waiting.fail_reason, None);
+    }
+
+    #[tokio::test]
+    async fn trans_item_actions() {
+        let (_, track_graph) = offline_sample().await;
+        let track_graph = Arc::new(track_graph);
+
+        let mut vehicle = Vehicle::new(2500, Skill::Item, track_graph.clone()).await;
+        vehicle.get_action(&(-1.0, 2.0, 0.0).into(), 1.0).await;
+        vehicle.get_action(&(-1.0, 2.0, 0.0).into(), 1.0).await;
+        vehicle.get_action(&(-1.0, 2.0, 0.0).into(), 1.0).await;
+        assert!(vehicle.idle().await);
+
+        let vehicles = Arc::new(RwLock::new(HashMap::from([(2500, vehicle)])));
+        let action_planner = ActionPlanner {
+            tasks: Arc::new(MemoryTaskStore::default()),
             vehicles,
             track_graph,
+            interval: constant::VEHICLE_SCHEDULE_TIME,
         };
 
-        action_planner.plan().await.unwrap();
+        let load = Load {
+            cargo: Cargo {
+                name: Some("copper_plate".to_string()),
+                quantity: Some(32),
+            },
+            from_slots: None,
+            to_slots: Some(Slots { first: 2, last: 3 }),
+        };
+        let (id, mut actions) = action_planner
+            .trans_item_actions("S2", "S1", &load)
+            .await
+            .unwrap();
+        assert_eq!(id, 2500);
+
+        let mut steps = Vec::new();
+        let mut interactions = Vec::new();
+        while let Some(action) = actions.pop_next_action() {
+            steps.push(match action {
+                Action::Move(node) => node.name.clone(),
+                Action::Suck(station) => {
+                    assert_eq!(station.cargo, load.cargo);
+                    interactions.push(station.interaction);
+                    "Suck".to_string()
+                }
+                Action::Drop(station) => {
+                    assert_eq!(station.cargo, load.cargo);
+                    interactions.push(station.interaction);
+                    "Drop".to_string()
+                }
+                other => format!("{:?}", other),
+            });
+        }
+        assert_eq!(
+            steps,
+            [
+                "A5", "A6", "S2", "Suck", "A6", "A2", "A1", "A4", "A3", "S1", "Drop"
+            ]
+        );
+        // Each station action carries the interaction of the node it happens at, the task's
+        // slots win over the node's.
+        assert_eq!(interactions[0].slots, Some(Slots { first: 1, last: 8 }));
+        assert_eq!(interactions[1].side, Some(Side::NegY));
+        assert_eq!(interactions[1].slots, Some(Slots { first: 2, last: 3 }));
+
+        let e = action_planner
+            .trans_item_actions("S2", "S9", &load)
+            .await
+            .unwrap_err();
+        assert!(matches!(e, Error::PathFind(track::Error::UnknownNode(ref name)) if name == "S9"));
+        let e = action_planner
+            .trans_item_actions("S9", "S1", &load)
+            .await
+            .unwrap_err();
+        assert!(matches!(e, Error::PathFind(track::Error::UnknownNode(_))));
     }
 }
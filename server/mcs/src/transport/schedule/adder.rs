@@ -1,51 +1,124 @@
 use std::sync::Arc;
 
-use sqlx::query;
+use serde::Deserialize;
 
-use crate::db_manager::DbManager;
-use crate::transport::schedule::{Error, Result};
-use crate::transport::vehicle::ToolType;
+use crate::transport::schedule::{Error, Result, TaskKind, TaskStore};
+use crate::transport::track::{Graph, Slots};
+use crate::transport::vehicle::{Cargo, ToolType};
+
+/// What a transport task moves, unset fields take whatever is at the stations.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct Load {
+    #[serde(flatten)]
+    pub cargo: Cargo,
+    /// Stand in for the slots of the station picked up from.
+    pub from_slots: Option<Slots>,
+    /// Stand in for the slots of the station delivered to.
+    pub to_slots: Option<Slots>,
+}
+
+impl Load {
+    fn check(&self) -> Result<()> {
+        if self.cargo.quantity.is_some_and(|quantity| quantity <= 0) {
+            return Err(Error::InvalidLoad("quantity must be positive".to_string()));
+        }
+        for slots in [self.from_slots, self.to_slots].into_iter().flatten() {
+            if slots.first <= 0 || slots.last < slots.first {
+                return Err(Error::InvalidLoad(format!(
+                    "no slots in {}..{}",
+                    slots.first, slots.last
+                )));
+            }
+        }
+        Ok(())
+    }
+}
 
 #[derive(Debug)]
 pub struct ScheduleAdder {
-    db: Arc<DbManager>,
+    tasks: Arc<dyn TaskStore>,
+    track_graph: Arc<Graph>,
 }
 
 impl ScheduleAdder {
-    pub fn new(db: Arc<DbManager>) -> Self {
-        Self { db }
+    pub fn new(tasks: Arc<dyn TaskStore>, track_graph: Arc<Graph>) -> Self {
+        Self { tasks, track_graph }
     }
 
-    pub async fn trans_items(&mut self, from: &str, to: &str) -> Result<()> {
-        let mut conn = self.db.transport().await.map_err(Error::Db)?;
-        query("INSERT INTO item(begin_node_name, end_node_name) VALUES($1,$2)")
-            .bind(from)
-            .bind(to)
-            .execute(&mut *conn)
-            .await
-            .map_err(Error::Db)?;
+    async fn check_nodes(&self, from: &str, to: &str) -> Result<()> {
+        self.track_graph.node(from).await.map_err(Error::PathFind)?;
+        self.track_graph.node(to).await.map_err(Error::PathFind)?;
+        if from == to {
+            return Err(Error::SameNode(from.to_string()));
+        }
         Ok(())
     }
 
-    pub async fn trans_fluid(&mut self, from: &str, to: &str) -> Result<()> {
-        let mut conn = self.db.transport().await.map_err(Error::Db)?;
-        query("INSERT INTO fluid(begin_node_name, end_node_name) VALUES($1,$2)")
-            .bind(from)
-            .bind(to)
-            .execute(&mut *conn)
+    async fn trans(&self, kind: TaskKind, from: &str, to: &str, load: &Load) -> Result<i32> {
+        self.check_nodes(from, to).await?;
+        load.check()?;
+        self.tasks
+            .add_trans(kind, from, to, load)
             .await
-            .map_err(Error::Db)?;
-        Ok(())
+            .map_err(Error::Db)
+    }
+
+    /// Returns the id of the new task.
+    pub async fn trans_items(&self, from: &str, to: &str, load: &Load) -> Result<i32> {
+        self.trans(TaskKind::Item, from, to, load).await
     }
 
-    pub async fn use_tool(&mut self, pos: &str, tool_type: ToolType) -> Result<()> {
-        let mut conn = self.db.transport().await.map_err(Error::Db)?;
-        query("INSERT INTO use_tool(end_node_name, tool_type) VALUES($1,$2)")
-            .bind(pos)
-            .bind(tool_type)
-            .execute(&mut *conn)
+    pub async fn trans_fluid(&self, from: &str, to: &str, load: &Load) -> Result<i32> {
+        self.trans(TaskKind::Fluid, from, to, load).await
+    }
+
+    pub async fn use_tool(&self, pos: &str, tool_type: ToolType) -> Result<i32> {
+        self.track_graph.node(pos).await.map_err(Error::PathFind)?;
+        self.tasks
+            .add_use_tool(pos, tool_type)
             .await
-            .map_err(Error::Db)?;
-        Ok(())
+            .map_err(Error::Db)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::{
+        schedule::MemoryTaskStore,
+        track::{self, offline_sample},
+    };
+
+    #[tokio::test]
+    async fn reject_bad_nodes() {
+        // Validation fails before anything is stored.
+        let (_, track_graph) = offline_sample().await;
+        let tasks = Arc::new(MemoryTaskStore::default());
+        let adder = ScheduleAdder::new(tasks, Arc::new(track_graph));
+
+        let load = Load::default();
+        let e = adder.trans_items("S2", "S9", &load).await.unwrap_err();
+        assert!(matches!(e, Error::PathFind(track::Error::UnknownNode(ref name)) if name == "S9"));
+        let e = adder.trans_fluid("S1", "S1", &load).await.unwrap_err();
+        assert!(matches!(e, Error::SameNode(ref name) if name == "S1"));
+        let e = adder.use_tool("X1", ToolType::Wrench).await.unwrap_err();
+        assert!(matches!(e, Error::PathFind(track::Error::UnknownNode(_))));
+
+        let load: Load = serde_json::from_value(serde_json::json!({
+            "name": "copper_plate",
+            "quantity": 0,
+            "to_slots": {"first": 1, "last": 4}
+        }))
+        .unwrap();
+        assert_eq!(load.to_slots, Some(Slots { first: 1, last: 4 }));
+        let e = adder.trans_items("S2", "S1", &load).await.unwrap_err();
+        assert!(matches!(e, Error::InvalidLoad(_)));
+        let load = Load {
+            from_slots: Some(Slots { first: 3, last: 2 }),
+            ..Load::default()
+        };
+        let e = adder.trans_fluid("S2", "S1", &load).await.unwrap_err();
+        assert!(matches!(e, Error::InvalidLoad(_)));
     }
 }
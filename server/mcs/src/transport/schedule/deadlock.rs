@@ -0,0 +1,136 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use tokio::{
+    sync::{RwLock, watch},
+    task::JoinHandle,
+    time,
+};
+use tracing::warn;
+
+use crate::{
+    constant,
+    transport::vehicle::{self, EventSender, Vehicle},
+};
+
+/// Which vehicle each held vehicle is waiting for. A vehicle waits for at most one other, so
+/// every cycle can be found by walking forward.
+#[derive(Debug, Default)]
+pub struct WaitForGraph(HashMap<i32, i32>);
+
+impl WaitForGraph {
+    pub fn new(waits: HashMap<i32, i32>) -> Self {
+        Self(waits)
+    }
+
+    pub fn cycles(&self) -> Vec<Vec<i32>> {
+        let mut ids: Vec<i32> = self.0.keys().copied().collect();
+        ids.sort();
+        let mut visited = HashSet::new();
+        let mut cycles = Vec::new();
+        for id in ids {
+            let mut walk = Vec::new();
+            let mut current = Some(id);
+            while let Some(vehicle_id) = current {
+                if let Some(start) = walk.iter().position(|walked| *walked == vehicle_id) {
+                    cycles.push(walk.split_off(start));
+                    break;
+                }
+                if !visited.insert(vehicle_id) {
+                    break;
+                }
+                walk.push(vehicle_id);
+                current = self.0.get(&vehicle_id).copied();
+            }
+        }
+        cycles
+    }
+}
+
+#[derive(Debug)]
+pub struct DeadlockDetector {
+    vehicles: Arc<RwLock<HashMap<i32, Vehicle>>>,
+    vehicle_event_sender: EventSender,
+}
+
+impl DeadlockDetector {
+    pub fn run(
+        vehicles: Arc<RwLock<HashMap<i32, Vehicle>>>,
+        vehicle_event_sender: EventSender,
+        stop: watch::Receiver<bool>,
+    ) -> JoinHandle<()> {
+        let detector = Self {
+            vehicles,
+            vehicle_event_sender,
+        };
+        tokio::spawn(async move { detector.task(stop).await })
+    }
+
+    async fn task(self, mut stop: watch::Receiver<bool>) {
+        let mut interval =
+            time::interval(time::Duration::from_secs(constant::DEADLOCK_DETECT_TIME));
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = stop.changed() => break,
+            }
+            self.detect().await;
+        }
+    }
+
+    async fn detect(&self) {
+        let mut vehicles = self.vehicles.write().await;
+        let waits = vehicles
+            .iter()
+            .filter_map(|(id, vehicle)| Some((*id, vehicle.waiting_for()?)))
+            .collect();
+        for cycle in WaitForGraph::new(waits).cycles() {
+            let mut victim = (u8::MAX, i32::MIN);
+            for id in &cycle {
+                // Ties go against the higher id, the same order reservations use.
+                let priority = (vehicles[id].priority().await, -id);
+                victim = victim.min(priority);
+            }
+            let victim = -victim.1;
+            let resolution = vehicles.get_mut(&victim).unwrap().give_way().await;
+            warn!(
+                "deadlock between vehicles {:?}, vehicle {} {:?}.",
+                cycle, victim, resolution
+            );
+            let _ = self
+                .vehicle_event_sender
+                .send(vehicle::Event::Deadlock {
+                    vehicle_ids: cycle,
+                    victim,
+                    resolution,
+                })
+                .await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_cycles() {
+        let graph = WaitForGraph::new(HashMap::from([
+            (1, 2),
+            (2, 3),
+            (3, 1),
+            (4, 1),
+            (5, 6),
+            (7, 8),
+            (8, 7),
+        ]));
+        assert_eq!(graph.cycles(), [vec![1, 2, 3], vec![7, 8]]);
+        assert!(
+            WaitForGraph::new(HashMap::from([(1, 2), (2, 3)]))
+                .cycles()
+                .is_empty()
+        );
+    }
+}
@@ -1,39 +1,283 @@
 use std::{collections::HashMap, sync::Arc};
 
-use tokio::sync::{RwLock, mpsc};
-use tracing::warn;
+use serde::Serialize;
+use tokio::{
+    sync::{RwLock, broadcast, mpsc, watch},
+    task::JoinHandle,
+};
+use tracing::{error, info, warn};
 
 use crate::{
     constant,
     db_manager::DbManager,
     transport::{
         prelude::Position,
-        schedule::{action_planner::ActionPlanner, state_update::StateUpdate},
-        track::Graph,
-        vehicle::{self, Action, Vehicle},
+        schedule::{
+            Config, Error, Result, ScheduleAdder, TaskQuery, TaskStore, VehicleInfo,
+            VehicleRegistry, action_planner::ActionPlanner, deadlock::DeadlockDetector,
+            state_update::StateUpdate,
+        },
+        track::{self, Format, Graph, Layout, Maintenance, Resource, layout},
+        vehicle::{self, Action, EventSender, Outcome, Vehicle},
     },
 };
 
+#[derive(Debug, Clone, Serialize)]
+pub struct Disabled {
+    pub maintenance: Maintenance,
+    pub rerouted: Vec<i32>,
+    /// Vehicles whose route could not avoid the disabled track, they keep the old one.
+    pub stranded: Vec<i32>,
+}
+
+/// Stops the planner and the deadlock detector, then writes the task events still queued.
+#[derive(Debug)]
+pub struct Stopper {
+    stop: watch::Sender<bool>,
+    loops: Vec<JoinHandle<()>>,
+    state_update: JoinHandle<()>,
+}
+
+impl Stopper {
+    pub async fn stop(self) {
+        let _ = self.stop.send(true);
+        for task in self.loops {
+            let _ = task.await;
+        }
+        let _ = self.state_update.await;
+    }
+}
+
 #[derive(Debug)]
 pub struct ScheduleExec {
     track_graph: Arc<Graph>,
     vehicles: Arc<RwLock<HashMap<i32, Vehicle>>>,
-    vehicle_event_sender: mpsc::Sender<vehicle::Event>,
+    vehicle_event_sender: EventSender,
+    event_feed: broadcast::Sender<vehicle::Event>,
+    adder: ScheduleAdder,
+    tasks: TaskQuery,
+    registry: VehicleRegistry,
+    config: Config,
+    stopper: Option<Stopper>,
 }
 
 impl ScheduleExec {
-    pub async fn new(track_graph: Graph, db: Arc<DbManager>) -> Self {
+    pub async fn new(
+        track_graph: Graph,
+        db: Arc<DbManager>,
+        task_store: Arc<dyn TaskStore>,
+        config: Config,
+    ) -> Self {
         let vehicles = Arc::new(RwLock::new(HashMap::new()));
         let track_graph = Arc::new(track_graph);
-        let (vehicle_event_sender, vehicle_event_receiver) = mpsc::channel(50);
+        let (persist, vehicle_event_receiver) = mpsc::channel(50);
+        let (event_feed, _) = broadcast::channel(constant::EVENT_FEED_CAPACITY);
+        let vehicle_event_sender = EventSender::new(event_feed.clone(), persist);
 
-        ActionPlanner::run(vehicles.clone(), track_graph.clone(), db.clone());
-        StateUpdate::run(vehicle_event_receiver, db);
+        let (stop, stopped) = watch::channel(false);
+        let loops = vec![
+            ActionPlanner::run(
+                vehicles.clone(),
+                track_graph.clone(),
+                task_store.clone(),
+                config.planner_interval,
+                stopped.clone(),
+            ),
+            DeadlockDetector::run(
+                vehicles.clone(),
+                vehicle_event_sender.clone(),
+                stopped.clone(),
+            ),
+        ];
+        let state_update = StateUpdate::run(vehicle_event_receiver, task_store.clone(), stopped);
+        let adder = ScheduleAdder::new(task_store, track_graph.clone());
+        let registry = VehicleRegistry::new(
+            db.clone(),
+            track_graph.clone(),
+            config.vehicle.battery.clone(),
+        );
+        if let Err(e) = registry.reload().await {
+            error!("load vehicle registry error: {:?}.", e);
+        }
         Self {
             track_graph,
             vehicles,
             vehicle_event_sender,
+            event_feed,
+            adder,
+            tasks: TaskQuery::new(db),
+            registry,
+            config,
+            stopper: Some(Stopper {
+                stop,
+                loops,
+                state_update,
+            }),
+        }
+    }
+
+    /// Taken once, by whoever shuts the server down.
+    pub fn take_stopper(&mut self) -> Option<Stopper> {
+        self.stopper.take()
+    }
+
+    pub fn track_graph(&self) -> &Graph {
+        &self.track_graph
+    }
+
+    pub fn adder(&self) -> &ScheduleAdder {
+        &self.adder
+    }
+
+    pub fn tasks(&self) -> &TaskQuery {
+        &self.tasks
+    }
+
+    /// Every vehicle event from now on, a subscriber that falls behind loses the oldest ones.
+    pub fn subscribe(&self) -> broadcast::Receiver<vehicle::Event> {
+        self.event_feed.subscribe()
+    }
+
+    /// Every vehicle that has asked for an action, by id.
+    pub async fn vehicles(&self) -> Vec<vehicle::Status> {
+        let mut statuses = Vec::new();
+        for vehicle in self.vehicles.read().await.values() {
+            statuses.push(vehicle.status().await);
+        }
+        statuses.sort_by_key(|status| status.id);
+        statuses
+    }
+
+    /// Replaces the track, refused while a vehicle is live or holds a node lock.
+    pub async fn import_layout(&self, layout: &Layout) -> layout::Result<()> {
+        // Held until the new track is in, so no vehicle comes live in between.
+        let vehicles = self.vehicles.read().await;
+        let mut in_use: Vec<i32> = vehicles.keys().copied().collect();
+        in_use.extend(
+            self.track_graph
+                .locks()
+                .await
+                .iter()
+                .map(|lock| lock.vehicle_id),
+        );
+        in_use.sort();
+        in_use.dedup();
+        if !in_use.is_empty() {
+            return Err(layout::Error::InUse(in_use));
+        }
+        self.track_graph.import(layout).await
+    }
+
+    /// Takes the node or edge out of service and routes vehicles that would cross it around.
+    pub async fn disable(
+        &self,
+        resource: Resource,
+        reason: &str,
+        operator: &str,
+    ) -> track::Result<Disabled> {
+        let maintenance = self.track_graph.disable(resource, reason, operator).await?;
+        warn!(
+            "{:?} disabled by {}: {}.",
+            resource, maintenance.operator, maintenance.reason
+        );
+        let mut rerouted = Vec::new();
+        let mut stranded = Vec::new();
+        let mut vehicles = self.vehicles.write().await;
+        for (id, vehicle) in vehicles.iter_mut() {
+            match vehicle.reroute_disabled().await {
+                Ok(true) => rerouted.push(*id),
+                Ok(false) => {}
+                Err(e) => {
+                    warn!("vehicle({}): can not avoid {:?}, {:?}.", id, resource, e);
+                    stranded.push(*id);
+                }
+            }
         }
+        rerouted.sort();
+        stranded.sort();
+        Ok(Disabled {
+            maintenance,
+            rerouted,
+            stranded,
+        })
+    }
+
+    pub async fn render(&self, format: Format, with_vehicles: bool) -> String {
+        let mut overlays = Vec::new();
+        if with_vehicles {
+            for vehicle in self.vehicles.read().await.values() {
+                overlays.push(vehicle.overlay().await);
+            }
+            overlays.sort_by_key(|overlay| overlay.vehicle_id);
+        }
+        self.track_graph.render(format, &overlays).await
+    }
+
+    /// Registered vehicles, enabled or not, by id.
+    pub async fn registered_vehicles(&self) -> Vec<VehicleInfo> {
+        self.registry.list().await
+    }
+
+    /// Adds or updates a vehicle, a skill change only applies while it has no task.
+    pub async fn register_vehicle(&self, info: VehicleInfo) -> Result<()> {
+        self.registry.check(&info).await?;
+        let battery = self.registry.battery_policy(&info)?;
+        let mut vehicles = self.vehicles.write().await;
+        let retiring = vehicles
+            .get(&info.id)
+            .is_some_and(|vehicle| vehicle.skill() != &info.skill || !info.enabled);
+        if retiring {
+            Self::check_idle(&vehicles, info.id)?;
+        }
+        self.registry.upsert(info.clone()).await?;
+        if retiring {
+            Self::retire(&mut vehicles, info.id).await?;
+        } else if let Some(vehicle) = vehicles.get_mut(&info.id) {
+            vehicle.set_battery_policy(battery);
+            vehicle.set_home(info.home.clone());
+        }
+        info!(
+            "vehicle({}): registered as {} {:?}.",
+            info.id, info.name, info.skill
+        );
+        Ok(())
+    }
+
+    /// A disabled vehicle gets an error on every report until it is enabled again.
+    pub async fn set_vehicle_enabled(&self, id: i32, enabled: bool) -> Result<()> {
+        let mut vehicles = self.vehicles.write().await;
+        if !enabled {
+            Self::check_idle(&vehicles, id)?;
+        }
+        self.registry.set_enabled(id, enabled).await?;
+        if !enabled {
+            Self::retire(&mut vehicles, id).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn remove_vehicle(&self, id: i32) -> Result<()> {
+        let mut vehicles = self.vehicles.write().await;
+        Self::check_idle(&vehicles, id)?;
+        self.registry.remove(id).await?;
+        Self::retire(&mut vehicles, id).await
+    }
+
+    /// Checked before the registry changes, so a refused change leaves both sides as they were.
+    fn check_idle(vehicles: &HashMap<i32, Vehicle>, id: i32) -> Result<()> {
+        match vehicles.get(&id) {
+            Some(vehicle) if vehicle.task_id().is_some() => Err(Error::VehicleBusy),
+            _ => Ok(()),
+        }
+    }
+
+    /// Drops the live vehicle so it starts over on its next report, unless it is on a task.
+    async fn retire(vehicles: &mut HashMap<i32, Vehicle>, id: i32) -> Result<()> {
+        Self::check_idle(vehicles, id)?;
+        if let Some(mut vehicle) = vehicles.remove(&id) {
+            vehicle.retire().await;
+        }
+        Ok(())
     }
 
     pub async fn get_action(
@@ -42,22 +286,45 @@ impl ScheduleExec {
         position: impl Into<Position>,
         battery_level: f32,
         tool_level: Option<f32>,
-    ) -> Option<Action> {
+        outcome: Option<Outcome>,
+    ) -> Result<Option<Action>> {
+        let info = match self.registry.get(id).await {
+            Some(info) if info.enabled => info,
+            Some(_) => return Err(Error::VehicleDisabled(id)),
+            None => return Err(Error::UnknownVehicle(id)),
+        };
         if let Some(tool_level) = tool_level
-            && tool_level < constant::VEHICLE_TOOL_WARN_LEVEL
+            && tool_level < self.config.tool_warn_level
         {
             warn!("{} suffer low tool level", id);
         }
         let position = &position.into();
         let mut vehicles = self.vehicles.write().await;
         match vehicles.get_mut(&id) {
-            Some(vehicle) => vehicle.get_action(position, battery_level).await,
+            Some(vehicle) => {
+                vehicle.set_tool_level(tool_level);
+                vehicle.set_outcome(outcome);
+                Ok(vehicle.get_action(position, battery_level).await)
+            }
             None => {
-                let mut vehicle = Vehicle::new(id, self.track_graph.clone()).await;
-                vehicle.set_event_sender(self.vehicle_event_sender.clone());
+                let battery = self.registry.battery_policy(&info)?;
+                let mut vehicle = Vehicle::with_config(
+                    id,
+                    info.skill,
+                    self.track_graph.clone(),
+                    self.config.vehicle.clone(),
+                )
+                .await;
+                vehicle.set_battery_policy(battery);
+                vehicle.set_home(info.home);
+                vehicle
+                    .set_event_sender(self.vehicle_event_sender.clone())
+                    .await;
+                vehicle.set_tool_level(tool_level);
+                vehicle.set_outcome(outcome);
                 let action = vehicle.get_action(position, battery_level).await;
                 vehicles.insert(id, vehicle);
-                action
+                Ok(action)
             }
         }
     }
@@ -66,283 +333,429 @@ impl ScheduleExec {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::transport::schedule::adder::ScheduleAdder;
-    use dotenvy::dotenv;
-    use sqlx::postgres::PgPoolOptions;
-    use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt};
-
-    async fn get_track_graph() -> Graph {
-        dotenv().ok();
-        let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
-        println!("Connecting to database: {}", database_url);
-        let pool = PgPoolOptions::new()
-            .max_connections(5)
-            .connect(&database_url)
-            .await
-            .expect("Failed to create pool");
-        let db_manaer = DbManager::new(pool);
-        Graph::new(db_manaer).await
+    use crate::transport::{
+        schedule::{Load, MemoryTaskStore, TaskKind, query::TaskState},
+        track::offline_sample,
+        vehicle::Skill,
+    };
+
+    #[tokio::test]
+    async fn register_unsaved() {
+        let (db, track_graph) = offline_sample().await;
+        let tasks = Arc::new(MemoryTaskStore::default());
+        let schedule_exec = ScheduleExec::new(track_graph, db, tasks, Config::default()).await;
+        let vehicle = Vehicle::new(2500, Skill::Item, schedule_exec.track_graph.clone()).await;
+        schedule_exec.vehicles.write().await.insert(2500, vehicle);
+
+        // The registry can not be written, so the live vehicle keeps running as it was.
+        let e = schedule_exec
+            .register_vehicle(VehicleInfo {
+                id: 2500,
+                name: "fluid-1".to_string(),
+                skill: Skill::Fluid,
+                battery: None,
+                enabled: true,
+                home: None,
+            })
+            .await
+            .unwrap_err();
+        assert!(matches!(e, Error::Db(_)));
+        let vehicles = schedule_exec.vehicles.read().await;
+        assert_eq!(vehicles[&2500].skill(), &Skill::Item);
+    }
+
+    #[tokio::test]
+    async fn import_in_use() {
+        let (db, track_graph) = offline_sample().await;
+        let tasks = Arc::new(MemoryTaskStore::default());
+        let schedule_exec = ScheduleExec::new(track_graph, db, tasks, Config::default()).await;
+        let layout = schedule_exec.track_graph.export().await.unwrap();
+        let vehicle = Vehicle::new(2500, Skill::Item, schedule_exec.track_graph.clone()).await;
+        schedule_exec.vehicles.write().await.insert(2500, vehicle);
+        assert!(matches!(
+            schedule_exec.import_layout(&layout).await,
+            Err(layout::Error::InUse(vehicles)) if vehicles == [2500]
+        ));
+
+        schedule_exec.vehicles.write().await.clear();
+        schedule_exec.track_graph.lock_node(1, 5500).await.unwrap();
+        assert!(matches!(
+            schedule_exec.import_layout(&layout).await,
+            Err(layout::Error::InUse(vehicles)) if vehicles == [5500]
+        ));
+
+        schedule_exec.track_graph.release_locks(5500).await.unwrap();
+        schedule_exec.import_layout(&layout).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn stop() {
+        let (db, track_graph) = offline_sample().await;
+        let tasks = Arc::new(MemoryTaskStore::default());
+        let mut schedule_exec =
+            ScheduleExec::new(track_graph, db, tasks.clone(), Config::default()).await;
+        let stopper = schedule_exec.take_stopper().unwrap();
+        assert!(schedule_exec.take_stopper().is_none());
+        let task_id = schedule_exec
+            .adder()
+            .trans_items("S2", "S1", &Load::default())
+            .await
+            .unwrap();
+
+        let event = vehicle::Event::ProcessStart {
+            vehicle_id: 2500,
+            vehicle_skill: Skill::Item,
+            task_id,
+        };
+        let sender = schedule_exec.vehicle_event_sender.clone();
+        sender.send(event.clone()).await.unwrap();
+        // The queued event is still written before the stop returns.
+        tokio::time::timeout(tokio::time::Duration::from_secs(10), stopper.stop())
+            .await
+            .unwrap();
+        let task = tasks.task(TaskKind::Item, task_id).await.unwrap();
+        assert_eq!(task.state, TaskState::Processing);
+        assert_eq!(task.vehicle_id, Some(2500));
+        assert!(sender.send(event).await.is_err());
     }
 
     #[tokio::test]
     async fn dispatch() {
-        tracing_subscriber::registry().with(fmt::layer()).init();
-        let track_graph = get_track_graph().await;
-        dotenv().ok();
-        let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
-        println!("Connecting to database: {}", database_url);
-        let pool = PgPoolOptions::new()
-            .max_connections(5)
-            .connect(&database_url)
-            .await
-            .expect("Failed to create pool");
-        let db = DbManager::new(pool);
-        let mut dispatch = ScheduleExec::new(track_graph, db.clone()).await;
-
-        let mut adder = ScheduleAdder::new(db);
-        adder.trans_items("S2", "S1").await.unwrap();
+        let (db, track_graph) = offline_sample().await;
+        let tasks = Arc::new(MemoryTaskStore::default());
+        let dispatch = ScheduleExec::new(track_graph, db, tasks.clone(), Config::default()).await;
+        for (id, name, skill) in [
+            (2500, "item-1", Skill::Item),
+            (5500, "fluid-1", Skill::Fluid),
+        ] {
+            dispatch
+                .registry
+                .insert(VehicleInfo {
+                    id,
+                    name: name.to_string(),
+                    skill,
+                    battery: None,
+                    enabled: true,
+                    home: None,
+                })
+                .await;
+        }
+        assert!(matches!(
+            dispatch
+                .get_action(9999, (0.0, 2.0, 0.0), 1.0, None, None)
+                .await,
+            Err(Error::UnknownVehicle(9999))
+        ));
+
+        dispatch
+            .adder()
+            .trans_items("S2", "S1", &Load::default())
+            .await
+            .unwrap();
         // Item
 
         assert!(
-            matches!(dispatch .get_action(2500, (0.0, 2.0, 0.0), 1.0, Some(1.0))
-            .await.unwrap(), Action::Move(node) if node.name == "S3")
+            matches!(dispatch .get_action(2500, (0.0, 2.0, 0.0), 1.0, Some(1.0), None)
+            .await
+            .unwrap().unwrap(), Action::Move(node) if node.name == "S3")
         );
         assert!(matches!(
             dispatch
-                .get_action(2500, (-1.0, 2.0, 0.0), 1.0, Some(1.0))
+                .get_action(2500, (-1.0, 2.0, 0.0), 1.0, Some(1.0), None)
                 .await
+                .unwrap()
                 .unwrap(),
-            Action::Drop
+            Action::Drop(_)
         ));
 
         assert!(
-            matches!(dispatch .get_action(2500, (-1.0, 2.0, 0.0), 1.0, Some(1.0))
-            .await.unwrap(), Action::Move(node) if node.name == "A5")
+            matches!(dispatch .get_action(2500, (-1.0, 2.0, 0.0), 1.0, Some(1.0), None)
+            .await
+            .unwrap().unwrap(), Action::Move(node) if node.name == "A5")
         );
 
         // Yield to recv next task
         tokio::time::sleep(tokio::time::Duration::from_secs(3)).await;
         println!("{:#?}", dispatch.vehicles.read().await.get(&2500).unwrap());
         assert!(
-            matches!(dispatch.get_action(2500, (-1.0, 2.0, 0.0), 1.0, Some(1.0))
-            .await.unwrap(), Action::Move(node) if node.name == "A5")
+            matches!(dispatch.get_action(2500, (-1.0, 2.0, 0.0), 1.0, Some(1.0), None)
+            .await
+            .unwrap().unwrap(), Action::Move(node) if node.name == "A5")
         );
         assert!(
-            matches!(dispatch.get_action(2500, (0.0, 2.0, 0.0), 1.0, Some(1.0))
-            .await.unwrap(), Action::Move(node) if node.name == "A6")
+            matches!(dispatch.get_action(2500, (0.0, 2.0, 0.0), 1.0, Some(1.0), None)
+            .await
+            .unwrap().unwrap(), Action::Move(node) if node.name == "A6")
         );
         assert!(
-            matches!(dispatch.get_action(2500, (0.0, 1.0, 0.0), 1.0, Some(1.0))
-            .await.unwrap(), Action::Move(node) if node.name == "S2")
+            matches!(dispatch.get_action(2500, (0.0, 1.0, 0.0), 1.0, Some(1.0), None)
+            .await
+            .unwrap().unwrap(), Action::Move(node) if node.name == "S2")
         );
         assert!(matches!(
             dispatch
-                .get_action(2500, (-1.0, 1.0, 0.0), 1.0, Some(1.0))
+                .get_action(2500, (-1.0, 1.0, 0.0), 1.0, Some(1.0), None)
                 .await
+                .unwrap()
                 .unwrap(),
-            Action::Suck
+            Action::Suck(_)
         ));
         assert!(
-            matches!(dispatch.get_action(2500, (-1.0, 1.0, 0.0), 1.0, Some(1.0))
-            .await.unwrap(), Action::Move(node) if node.name == "A6")
+            matches!(dispatch.get_action(2500, (-1.0, 1.0, 0.0), 1.0, Some(1.0), None)
+            .await
+            .unwrap().unwrap(), Action::Move(node) if node.name == "A6")
         );
         assert!(
-            matches!(dispatch.get_action(2500, (0.0, 1.0, 0.0), 1.0, Some(1.0))
-            .await.unwrap(), Action::Move(node) if node.name == "A2")
+            matches!(dispatch.get_action(2500, (0.0, 1.0, 0.0), 1.0, Some(1.0), None)
+            .await
+            .unwrap().unwrap(), Action::Move(node) if node.name == "A2")
         );
         assert!(
-            matches!(dispatch.get_action(2500, (1.0, 1.0, 0.0), 1.0, Some(1.0))
-            .await.unwrap(), Action::Move(node) if node.name == "A1")
+            matches!(dispatch.get_action(2500, (1.0, 1.0, 0.0), 1.0, Some(1.0), None)
+            .await
+            .unwrap().unwrap(), Action::Move(node) if node.name == "A1")
         );
         assert!(
-            matches!(dispatch.get_action(2500, (2.0, 1.0, 0.0), 1.0, Some(1.0))
-            .await.unwrap(), Action::Move(node) if node.name == "A4")
+            matches!(dispatch.get_action(2500, (2.0, 1.0, 0.0), 1.0, Some(1.0), None)
+            .await
+            .unwrap().unwrap(), Action::Move(node) if node.name == "A4")
         );
         assert!(
-            matches!(dispatch.get_action(2500, (2.0, 2.0, 0.0), 1.0, Some(1.0))
-            .await.unwrap(), Action::Move(node) if node.name == "A3")
+            matches!(dispatch.get_action(2500, (2.0, 2.0, 0.0), 1.0, Some(1.0), None)
+            .await
+            .unwrap().unwrap(), Action::Move(node) if node.name == "A3")
         );
         assert!(
-            matches!(dispatch.get_action(2500, (1.0, 2.0, 0.0), 1.0, Some(1.0))
-            .await.unwrap(), Action::Move(node) if node.name == "S1")
+            matches!(dispatch.get_action(2500, (1.0, 2.0, 0.0), 1.0, Some(1.0), None)
+            .await
+            .unwrap().unwrap(), Action::Move(node) if node.name == "S1")
         );
         assert!(matches!(
             dispatch
-                .get_action(2500, (1.0, 3.0, 0.0), 1.0, Some(1.0))
+                .get_action(2500, (1.0, 3.0, 0.0), 1.0, Some(1.0), None)
                 .await
+                .unwrap()
                 .unwrap(),
-            Action::Drop
+            Action::Drop(_)
         ));
         assert!(
-            matches!(dispatch.get_action(2500, (1.0, 3.0, 0.0), 1.0, Some(1.0))
-            .await.unwrap(), Action::Move(node) if node.name == "A3")
+            matches!(dispatch.get_action(2500, (1.0, 3.0, 0.0), 1.0, Some(1.0), None)
+            .await
+            .unwrap().unwrap(), Action::Move(node) if node.name == "A3")
         );
         assert!(
-            matches!(dispatch.get_action(2500, (1.0, 2.0, 0.0), 1.0, Some(1.0))
-            .await.unwrap(), Action::Move(node) if node.name == "A2")
+            matches!(dispatch.get_action(2500, (1.0, 2.0, 0.0), 1.0, Some(1.0), None)
+            .await
+            .unwrap().unwrap(), Action::Move(node) if node.name == "A2")
         );
         assert!(
-            matches!(dispatch.get_action(2500, (1.0, 1.0, 0.0), 1.0, Some(1.0))
-            .await.unwrap(), Action::Move(node) if node.name == "A1")
+            matches!(dispatch.get_action(2500, (1.0, 1.0, 0.0), 1.0, Some(1.0), None)
+            .await
+            .unwrap().unwrap(), Action::Move(node) if node.name == "A1")
         );
         assert!(
-            matches!(dispatch.get_action(2500, (2.0, 1.0, 0.0), 1.0, Some(1.0))
-            .await.unwrap(), Action::Move(node) if node.name == "P1")
+            matches!(dispatch.get_action(2500, (2.0, 1.0, 0.0), 1.0, Some(1.0), None)
+            .await
+            .unwrap().unwrap(), Action::Move(node) if node.name == "P1")
         );
         assert!(
             dispatch
-                .get_action(2500, (2.0, 0.0, 0.0), 1.0, Some(1.0))
+                .get_action(2500, (2.0, 0.0, 0.0), 1.0, Some(1.0), None)
                 .await
+                .unwrap()
                 .is_none()
         );
 
         // Fluid
         assert!(
-            matches!(dispatch.get_action(5500, (2.0, 1.0, 0.0), 1.0, Some(1.0))
-            .await.unwrap(), Action::Move(node) if node.name == "A4")
+            matches!(dispatch.get_action(5500, (2.0, 1.0, 0.0), 1.0, Some(1.0), None)
+            .await
+            .unwrap().unwrap(), Action::Move(node) if node.name == "A4")
         );
         assert!(
-            matches!(dispatch.get_action(5500, (2.0, 2.0, 0.0), 1.0, Some(1.0))
-            .await.unwrap(), Action::Move(node) if node.name == "A3")
+            matches!(dispatch.get_action(5500, (2.0, 2.0, 0.0), 1.0, Some(1.0), None)
+            .await
+            .unwrap().unwrap(), Action::Move(node) if node.name == "A3")
         );
         assert!(
-            matches!(dispatch.get_action(5500, (1.0, 2.0, 0.0), 1.0, Some(1.0))
-            .await.unwrap(), Action::Move(node) if node.name == "A5")
+            matches!(dispatch.get_action(5500, (1.0, 2.0, 0.0), 1.0, Some(1.0), None)
+            .await
+            .unwrap().unwrap(), Action::Move(node) if node.name == "A5")
         );
         assert!(
-            matches!(dispatch.get_action(5500, (0.0, 2.0, 0.0), 1.0, Some(1.0))
-            .await.unwrap(), Action::Move(node) if node.name == "S3")
+            matches!(dispatch.get_action(5500, (0.0, 2.0, 0.0), 1.0, Some(1.0), None)
+            .await
+            .unwrap().unwrap(), Action::Move(node) if node.name == "S3")
         );
         assert!(matches!(
             dispatch
-                .get_action(5500, (-1.0, 2.0, 0.0), 1.0, Some(1.0))
+                .get_action(5500, (-1.0, 2.0, 0.0), 1.0, Some(1.0), None)
                 .await
+                .unwrap()
                 .unwrap(),
-            Action::Fill
+            Action::Fill(_)
         ));
         assert!(
-            matches!(dispatch.get_action(5500, (-1.0, 2.0, 0.0), 1.0, Some(1.0))
-            .await.unwrap(), Action::Move(node) if node.name == "A5")
+            matches!(dispatch.get_action(5500, (-1.0, 2.0, 0.0), 1.0, Some(1.0), None)
+            .await
+            .unwrap().unwrap(), Action::Move(node) if node.name == "A5")
         );
 
-        adder.trans_fluid("S1", "S2").await.unwrap();
+        dispatch
+            .adder()
+            .trans_fluid("S1", "S2", &Load::default())
+            .await
+            .unwrap();
         // Yield to recv next task
         tokio::time::sleep(tokio::time::Duration::from_secs(3)).await;
 
         assert!(
-            matches!(dispatch.get_action(5500, (-1.0, 2.0, 0.0), 1.0, Some(1.0))
-            .await.unwrap(), Action::Move(node) if node.name == "A5")
+            matches!(dispatch.get_action(5500, (-1.0, 2.0, 0.0), 1.0, Some(1.0), None)
+            .await
+            .unwrap().unwrap(), Action::Move(node) if node.name == "A5")
         );
         println!("{:#?}", dispatch.vehicles.read().await.get(&5500).unwrap());
         assert!(
-            matches!(dispatch.get_action(5500, (0.0, 2.0, 0.0), 1.0, Some(1.0))
-            .await.unwrap(), Action::Move(node) if node.name == "A6")
+            matches!(dispatch.get_action(5500, (0.0, 2.0, 0.0), 1.0, Some(1.0), None)
+            .await
+            .unwrap().unwrap(), Action::Move(node) if node.name == "A6")
         );
 
         assert!(
-            matches!(dispatch.get_action(5500, (0.0, 1.0, 0.0), 1.0, Some(1.0))
-            .await.unwrap(), Action::Move(node) if node.name == "A2")
+            matches!(dispatch.get_action(5500, (0.0, 1.0, 0.0), 1.0, Some(1.0), None)
+            .await
+            .unwrap().unwrap(), Action::Move(node) if node.name == "A2")
         );
         assert!(
-            matches!(dispatch.get_action(5500, (1.0, 1.0, 0.0), 1.0, Some(1.0))
-            .await.unwrap(), Action::Move(node) if node.name == "A1")
+            matches!(dispatch.get_action(5500, (1.0, 1.0, 0.0), 1.0, Some(1.0), None)
+            .await
+            .unwrap().unwrap(), Action::Move(node) if node.name == "A1")
         );
         assert!(
-            matches!(dispatch.get_action(5500, (2.0, 1.0, 0.0), 1.0, Some(1.0))
-            .await.unwrap(), Action::Move(node) if node.name == "A4")
+            matches!(dispatch.get_action(5500, (2.0, 1.0, 0.0), 1.0, Some(1.0), None)
+            .await
+            .unwrap().unwrap(), Action::Move(node) if node.name == "A4")
         );
         assert!(
-            matches!(dispatch .get_action(5500, (2.0, 2.0, 0.0), 1.0, Some(1.0))
-            .await.unwrap(), Action::Move(node) if node.name == "A3")
+            matches!(dispatch .get_action(5500, (2.0, 2.0, 0.0), 1.0, Some(1.0), None)
+            .await
+            .unwrap().unwrap(), Action::Move(node) if node.name == "A3")
         );
         assert!(
-            matches!(dispatch .get_action(5500, (1.0, 2.0, 0.0), 1.0, Some(1.0))
-            .await.unwrap(), Action::Move(node) if node.name == "S1")
+            matches!(dispatch .get_action(5500, (1.0, 2.0, 0.0), 1.0, Some(1.0), None)
+            .await
+            .unwrap().unwrap(), Action::Move(node) if node.name == "S1")
         );
         assert!(matches!(
             dispatch
-                .get_action(5500, (1.0, 3.0, 0.0), 1.0, Some(1.0))
+                .get_action(5500, (1.0, 3.0, 0.0), 1.0, Some(1.0), None)
                 .await
+                .unwrap()
                 .unwrap(),
-            Action::Suck
+            Action::Suck(_)
         ));
         assert!(
-            matches!(dispatch .get_action(5500, (1.0, 3.0, 0.0), 1.0, Some(1.0))
-            .await.unwrap(), Action::Move(node) if node.name == "A3")
+            matches!(dispatch .get_action(5500, (1.0, 3.0, 0.0), 1.0, Some(1.0), None)
+            .await
+            .unwrap().unwrap(), Action::Move(node) if node.name == "A3")
         );
         assert!(
-            matches!(dispatch .get_action(5500, (1.0, 2.0, 0.0), 1.0, Some(1.0))
-            .await.unwrap(), Action::Move(node) if node.name == "A5")
+            matches!(dispatch .get_action(5500, (1.0, 2.0, 0.0), 1.0, Some(1.0), None)
+            .await
+            .unwrap().unwrap(), Action::Move(node) if node.name == "A5")
         );
         assert!(
-            matches!(dispatch .get_action(5500, (0.0, 2.0, 0.0), 1.0, Some(1.0))
-            .await.unwrap(), Action::Move(node) if node.name == "A6")
+            matches!(dispatch .get_action(5500, (0.0, 2.0, 0.0), 1.0, Some(1.0), None)
+            .await
+            .unwrap().unwrap(), Action::Move(node) if node.name == "A6")
         );
         assert!(
-            matches!(dispatch .get_action(5500, (0.0, 1.0, 0.0), 1.0, Some(1.0))
-            .await.unwrap(), Action::Move(node) if node.name == "S2")
+            matches!(dispatch .get_action(5500, (0.0, 1.0, 0.0), 1.0, Some(1.0), None)
+            .await
+            .unwrap().unwrap(), Action::Move(node) if node.name == "S2")
         );
 
         assert!(matches!(
             dispatch
-                .get_action(5500, (-1.0, 1.0, 0.0), 1.0, Some(1.0))
+                .get_action(5500, (-1.0, 1.0, 0.0), 1.0, Some(1.0), None)
                 .await
+                .unwrap()
                 .unwrap(),
-            Action::Fill
+            Action::Fill(_)
         ));
 
         assert!(
-            matches!(dispatch .get_action(5500, (-1.0, 1.0, 0.0), 1.0, Some(1.0))
-            .await.unwrap(), Action::Move(node) if node.name == "A6")
+            matches!(dispatch .get_action(5500, (-1.0, 1.0, 0.0), 1.0, Some(1.0), None)
+            .await
+            .unwrap().unwrap(), Action::Move(node) if node.name == "A6")
         );
         assert!(
-            matches!(dispatch .get_action(5500, (0.0, 1.0, 0.0), 1.0, Some(1.0))
-            .await.unwrap(), Action::Move(node) if node.name == "A2")
+            matches!(dispatch .get_action(5500, (0.0, 1.0, 0.0), 1.0, Some(1.0), None)
+            .await
+            .unwrap().unwrap(), Action::Move(node) if node.name == "A2")
         );
         assert!(
-            matches!(dispatch .get_action(5500, (1.0, 1.0, 0.0), 1.0, Some(1.0))
-            .await.unwrap(), Action::Move(node) if node.name == "A1")
+            matches!(dispatch .get_action(5500, (1.0, 1.0, 0.0), 1.0, Some(1.0), None)
+            .await
+            .unwrap().unwrap(), Action::Move(node) if node.name == "A1")
         );
         assert!(
-            matches!(dispatch .get_action(5500, (2.0, 1.0, 0.0), 1.0, Some(1.0))
-            .await.unwrap(), Action::Move(node) if node.name == "A4")
+            matches!(dispatch .get_action(5500, (2.0, 1.0, 0.0), 1.0, Some(1.0), None)
+            .await
+            .unwrap().unwrap(), Action::Move(node) if node.name == "A4")
         );
         assert!(
-            matches!(dispatch .get_action(5500, (2.0, 2.0, 0.0), 1.0, Some(1.0))
-            .await.unwrap(), Action::Move(node) if node.name == "A3")
+            matches!(dispatch .get_action(5500, (2.0, 2.0, 0.0), 1.0, Some(1.0), None)
+            .await
+            .unwrap().unwrap(), Action::Move(node) if node.name == "A3")
         );
         assert!(
-            matches!(dispatch .get_action(5500, (1.0, 2.0, 0.0), 1.0, Some(1.0))
-            .await.unwrap(), Action::Move(node) if node.name == "A5")
+            matches!(dispatch .get_action(5500, (1.0, 2.0, 0.0), 1.0, Some(1.0), None)
+            .await
+            .unwrap().unwrap(), Action::Move(node) if node.name == "A5")
         );
         assert!(
-            matches!(dispatch .get_action(5500, (0.0, 2.0, 0.0), 1.0, Some(1.0))
-            .await.unwrap(), Action::Move(node) if node.name == "S3")
+            matches!(dispatch .get_action(5500, (0.0, 2.0, 0.0), 1.0, Some(1.0), None)
+            .await
+            .unwrap().unwrap(), Action::Move(node) if node.name == "S3")
         );
         assert!(matches!(
             dispatch
-                .get_action(5500, (-1.0, 2.0, 0.0), 1.0, Some(1.0))
+                .get_action(5500, (-1.0, 2.0, 0.0), 1.0, Some(1.0), None)
                 .await
+                .unwrap()
                 .unwrap(),
-            Action::Drop
+            Action::Drop(_)
         ));
         assert!(
-            matches!(dispatch .get_action(5500, (-1.0, 2.0, 0.0), 1.0, Some(1.0))
-            .await.unwrap(), Action::Move(node) if node.name == "A5")
+            matches!(dispatch .get_action(5500, (-1.0, 2.0, 0.0), 1.0, Some(1.0), None)
+            .await
+            .unwrap().unwrap(), Action::Move(node) if node.name == "A5")
         );
         assert!(
-            matches!(dispatch .get_action(5500, (0.0, 2.0, 0.0), 1.0, Some(1.0))
-            .await.unwrap(), Action::Move(node) if node.name == "A6")
+            matches!(dispatch .get_action(5500, (0.0, 2.0, 0.0), 1.0, Some(1.0), None)
+            .await
+            .unwrap().unwrap(), Action::Move(node) if node.name == "A6")
         );
         assert!(
-            matches!(dispatch .get_action(5500, (0.0, 1.0, 0.0), 1.0, Some(1.0))
-            .await.unwrap(), Action::Move(node) if node.name == "P2")
+            matches!(dispatch .get_action(5500, (0.0, 1.0, 0.0), 1.0, Some(1.0), None)
+            .await
+            .unwrap().unwrap(), Action::Move(node) if node.name == "P2")
         );
         assert!(
             dispatch
-                .get_action(5500, (0.0, 0.0, 0.0), 1.0, Some(1.0))
+                .get_action(5500, (0.0, 0.0, 0.0), 1.0, Some(1.0), None)
                 .await
+                .unwrap()
                 .is_none()
         );
+
+        // Written as the vehicles went.
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+        for (kind, vehicle_id) in [(TaskKind::Item, 2500), (TaskKind::Fluid, 5500)] {
+            let task = tasks.task(kind, 1).await.unwrap();
+            assert_eq!(task.state, TaskState::Completed);
+            assert_eq!(task.vehicle_id, Some(vehicle_id));
+        }
     }
 }
@@ -1,15 +1,74 @@
 mod action_planner;
 mod adder;
+mod deadlock;
 mod exec;
+mod query;
+mod registry;
 mod state_update;
+mod store;
+
+use serde::Deserialize;
+
+use crate::{
+    constant,
+    transport::{track, vehicle},
+};
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Seconds between two planning rounds.
+    pub planner_interval: u64,
+    /// Tool level below which a vehicle's reports are warned about.
+    pub tool_warn_level: f32,
+    pub vehicle: vehicle::Config,
+}
+
+impl Config {
+    pub fn check(&self) -> std::result::Result<(), String> {
+        if self.planner_interval == 0 {
+            return Err("planner_interval must be at least 1 second".to_string());
+        }
+        if self.vehicle.online_timeout <= 0 {
+            return Err("online_timeout must be at least 1 second".to_string());
+        }
+        self.vehicle.battery.check()
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            planner_interval: constant::VEHICLE_SCHEDULE_TIME,
+            tool_warn_level: constant::VEHICLE_TOOL_WARN_LEVEL,
+            vehicle: vehicle::Config::default(),
+        }
+    }
+}
 
 #[derive(Debug)]
 pub enum Error {
     VehicleBusy,
-    PathFind,
+    /// Not in the vehicle registry.
+    UnknownVehicle(i32),
+    VehicleDisabled(i32),
+    /// A home that is not a parking station.
+    NotParking(String),
+    PathFind(track::Error),
+    /// A transport task that begins where it ends.
+    SameNode(String),
+    /// A cargo or slot range no task could move.
+    InvalidLoad(String),
+    /// Battery levels a vehicle would go back and forth on.
+    InvalidBattery(String),
     Db(sqlx::Error),
 }
 pub type Result<T> = std::result::Result<T, Error>;
 
-pub use adder::ScheduleAdder;
-pub use exec::ScheduleExec;
+pub use adder::{Load, ScheduleAdder};
+pub use exec::{ScheduleExec, Stopper};
+pub use query::{TaskFilter, TaskKind, TaskQuery};
+pub use registry::{VehicleInfo, VehicleRegistry};
+#[cfg(test)]
+pub use store::MemoryTaskStore;
+pub use store::{PgTaskStore, TaskStore};
@@ -0,0 +1,208 @@
+use std::sync::Arc;
+
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, query_as, query_scalar};
+
+use crate::{
+    constant,
+    db_manager::DbManager,
+    transport::{
+        schedule::{Error, Result},
+        vehicle::{Skill, ToolType},
+    },
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskKind {
+    Item,
+    Fluid,
+    UseTool,
+}
+
+impl TaskKind {
+    pub(super) fn name(&self) -> &'static str {
+        match self {
+            TaskKind::Item => "item",
+            TaskKind::Fluid => "fluid",
+            TaskKind::UseTool => "use_tool",
+        }
+    }
+}
+
+impl From<&Skill> for TaskKind {
+    fn from(skill: &Skill) -> Self {
+        match skill {
+            Skill::Item => TaskKind::Item,
+            Skill::Fluid => TaskKind::Fluid,
+            Skill::UseTool(_) => TaskKind::UseTool,
+        }
+    }
+}
+
+impl TryFrom<String> for TaskKind {
+    type Error = String;
+
+    fn try_from(value: String) -> std::result::Result<Self, Self::Error> {
+        match value.as_str() {
+            "item" => Ok(TaskKind::Item),
+            "fluid" => Ok(TaskKind::Fluid),
+            "use_tool" => Ok(TaskKind::UseTool),
+            _ => Err(format!("unknown task kind {}", value)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, sqlx::Type, Serialize, Deserialize)]
+#[sqlx(type_name = "state", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum TaskState {
+    Pending,
+    Processing,
+    Completed,
+    Failed,
+}
+
+/// Every field narrows the result, a task touches `node` when it begins or ends there.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct TaskFilter {
+    pub kind: Option<TaskKind>,
+    pub id: Option<i32>,
+    pub state: Option<TaskState>,
+    pub node: Option<String>,
+    pub vehicle_id: Option<i32>,
+    pub offset: i64,
+    pub limit: Option<i64>,
+}
+
+impl TaskFilter {
+    fn limit(&self) -> i64 {
+        self.limit
+            .unwrap_or(constant::TASK_QUERY_DEFAULT_LIMIT)
+            .clamp(0, constant::TASK_QUERY_MAX_LIMIT)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct Task {
+    #[sqlx(try_from = "String")]
+    pub kind: TaskKind,
+    pub id: i32,
+    pub begin_node_name: Option<String>,
+    pub end_node_name: String,
+    pub tool_type: Option<ToolType>,
+    pub cargo_name: Option<String>,
+    pub quantity: Option<i32>,
+    pub state: Option<TaskState>,
+    pub vehicle_id: Option<i32>,
+    pub fail_reason: Option<String>,
+    pub date_created: Option<NaiveDateTime>,
+    pub date_updated: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TaskPage {
+    /// Tasks matching the filter, before offset and limit.
+    pub total: i64,
+    pub tasks: Vec<Task>,
+}
+
+const TASKS: &str = "
+    SELECT * FROM (
+        SELECT 'item' AS kind, id, rtrim(begin_node_name) AS begin_node_name,
+            rtrim(end_node_name) AS end_node_name, NULL::tooltype AS tool_type, cargo_name,
+            quantity, state, vehicle_id, fail_reason, date_created, date_updated
+        FROM item
+        UNION ALL
+        SELECT 'fluid', id, rtrim(begin_node_name), rtrim(end_node_name), NULL, cargo_name,
+            quantity, state, vehicle_id, fail_reason, date_created, date_updated
+        FROM fluid
+        UNION ALL
+        SELECT 'use_tool', id, NULL, rtrim(end_node_name), tool_type, NULL, NULL, state,
+            vehicle_id, fail_reason, date_created, date_updated
+        FROM use_tool
+    ) AS task
+    WHERE ($1::text IS NULL OR kind = $1)
+        AND ($2::int IS NULL OR id = $2)
+        AND ($3::state IS NULL OR state = $3)
+        AND ($4::text IS NULL OR begin_node_name = $4 OR end_node_name = $4)
+        AND ($5::int IS NULL OR vehicle_id = $5)
+";
+
+/// Read-only view of the item, fluid and use_tool tables.
+#[derive(Debug)]
+pub struct TaskQuery {
+    db: Arc<DbManager>,
+}
+
+impl TaskQuery {
+    pub fn new(db: Arc<DbManager>) -> Self {
+        Self { db }
+    }
+
+    /// Newest first.
+    pub async fn tasks(&self, filter: &TaskFilter) -> Result<TaskPage> {
+        let mut conn = self.db.transport().await.map_err(Error::Db)?;
+        let kind = filter.kind.map(|kind| kind.name());
+        let total = query_scalar(&format!("SELECT count(*) FROM ({}) AS filtered", TASKS))
+            .bind(kind)
+            .bind(filter.id)
+            .bind(filter.state)
+            .bind(&filter.node)
+            .bind(filter.vehicle_id)
+            .fetch_one(&mut *conn)
+            .await
+            .map_err(Error::Db)?;
+        let tasks = query_as(&format!(
+            "{} ORDER BY date_created DESC, kind, id DESC LIMIT $6 OFFSET $7",
+            TASKS
+        ))
+        .bind(kind)
+        .bind(filter.id)
+        .bind(filter.state)
+        .bind(&filter.node)
+        .bind(filter.vehicle_id)
+        .bind(filter.limit())
+        .bind(filter.offset.max(0))
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(Error::Db)?;
+        Ok(TaskPage { total, tasks })
+    }
+
+    pub async fn task(&self, kind: TaskKind, id: i32) -> Result<Option<Task>> {
+        let filter = TaskFilter {
+            kind: Some(kind),
+            id: Some(id),
+            ..Default::default()
+        };
+        Ok(self.tasks(&filter).await?.tasks.pop())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn filter() {
+        let filter: TaskFilter = serde_json::from_value(json!({})).unwrap();
+        assert_eq!(filter.limit(), constant::TASK_QUERY_DEFAULT_LIMIT);
+
+        let filter: TaskFilter = serde_json::from_value(json!({
+            "kind": "use_tool",
+            "state": "failed",
+            "node": "S1",
+            "limit": 100000
+        }))
+        .unwrap();
+        assert_eq!(filter.kind, Some(TaskKind::UseTool));
+        assert_eq!(filter.state, Some(TaskState::Failed));
+        assert_eq!(filter.limit(), constant::TASK_QUERY_MAX_LIMIT);
+        assert!(serde_json::from_value::<TaskFilter>(json!({"state": "lost"})).is_err());
+    }
+}
@@ -0,0 +1,341 @@
+use std::{collections::HashMap, sync::Arc};
+
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, query, query_as, types::Json};
+use tokio::sync::RwLock;
+use tracing::error;
+
+use crate::{
+    db_manager::DbManager,
+    transport::{
+        schedule::{Error, Result},
+        track::{Graph, NodeType},
+        vehicle::{BatteryConfig, BatteryOverride, BatteryPolicy, Skill, ToolType},
+    },
+};
+
+/// A vehicle allowed to call in, and what it is set up for.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VehicleInfo {
+    pub id: i32,
+    pub name: String,
+    /// What the vehicle is built for, an item carrier has no tank and a tool vehicle holds
+    /// one tool, so one is all it ever has. Registering it again changes it.
+    pub skill: Skill,
+    /// Levels set here win over the configured policy for this vehicle.
+    #[serde(default)]
+    pub battery: Option<BatteryOverride>,
+    #[serde(default = "enabled")]
+    pub enabled: bool,
+    /// Parking node the vehicle prefers over the nearest free one.
+    #[serde(default)]
+    pub home: Option<String>,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, sqlx::Type)]
+#[sqlx(type_name = "skill", rename_all = "snake_case")]
+enum SkillKind {
+    Item,
+    Fluid,
+    UseTool,
+}
+
+#[derive(Debug, FromRow)]
+struct VehicleRow {
+    id: i32,
+    name: String,
+    skill: SkillKind,
+    tool_type: Option<ToolType>,
+    battery: Option<Json<BatteryOverride>>,
+    enabled: bool,
+    home_node_name: Option<String>,
+}
+
+impl VehicleRow {
+    fn into_info(self) -> Option<VehicleInfo> {
+        let skill = match (self.skill, self.tool_type) {
+            (SkillKind::Item, _) => Skill::Item,
+            (SkillKind::Fluid, _) => Skill::Fluid,
+            (SkillKind::UseTool, Some(tool_type)) => Skill::UseTool(tool_type),
+            (SkillKind::UseTool, None) => return None,
+        };
+        Some(VehicleInfo {
+            id: self.id,
+            name: self.name,
+            skill,
+            battery: self.battery.map(|battery| battery.0),
+            enabled: self.enabled,
+            home: self.home_node_name.map(|home| home.trim_end().to_string()),
+        })
+    }
+}
+
+/// The `vehicle` table, cached since every report looks it up.
+#[derive(Debug)]
+pub struct VehicleRegistry {
+    db: Arc<DbManager>,
+    track_graph: Arc<Graph>,
+    battery: BatteryConfig,
+    vehicles: RwLock<HashMap<i32, VehicleInfo>>,
+}
+
+impl VehicleRegistry {
+    pub fn new(db: Arc<DbManager>, track_graph: Arc<Graph>, battery: BatteryConfig) -> Self {
+        Self {
+            db,
+            track_graph,
+            battery,
+            vehicles: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// The configured policy for the vehicle with its own levels on top.
+    pub fn battery_policy(&self, info: &VehicleInfo) -> Result<BatteryPolicy> {
+        let policy = self.battery.policy(info.id, &info.skill);
+        let policy = info.battery.map_or(policy, |levels| levels.over(policy));
+        policy.check().map_err(Error::InvalidBattery)?;
+        Ok(policy)
+    }
+
+    /// Whether the home is a parking station on the track and the battery levels hold up.
+    pub async fn check(&self, info: &VehicleInfo) -> Result<()> {
+        if let Some(home) = &info.home {
+            let node = self.track_graph.node(home).await.map_err(Error::PathFind)?;
+            if node.node_type != NodeType::ParkingStation {
+                return Err(Error::NotParking(home.clone()));
+            }
+        }
+        self.battery_policy(info)?;
+        Ok(())
+    }
+
+    pub async fn reload(&self) -> Result<()> {
+        let mut conn = self.db.transport().await.map_err(Error::Db)?;
+        let rows: Vec<VehicleRow> = query_as(
+            "
+            SELECT id, name, skill, tool_type, battery, enabled, home_node_name
+            FROM vehicle;
+        ",
+        )
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(Error::Db)?;
+        let mut vehicles = HashMap::new();
+        for row in rows {
+            let id = row.id;
+            let Some(info) = row.into_info() else {
+                error!("vehicle({}): use_tool vehicle without tool type.", id);
+                continue;
+            };
+            if let Err(e) = self.check(&info).await {
+                error!("vehicle({}): not loaded, {:?}.", id, e);
+                continue;
+            }
+            vehicles.insert(id, info);
+        }
+        *self.vehicles.write().await = vehicles;
+        Ok(())
+    }
+
+    pub async fn get(&self, id: i32) -> Option<VehicleInfo> {
+        self.vehicles.read().await.get(&id).cloned()
+    }
+
+    /// By id.
+    pub async fn list(&self) -> Vec<VehicleInfo> {
+        let mut vehicles: Vec<VehicleInfo> = self.vehicles.read().await.values().cloned().collect();
+        vehicles.sort_by_key(|info| info.id);
+        vehicles
+    }
+
+    /// Adds the vehicle or replaces what is stored under its id.
+    pub async fn upsert(&self, info: VehicleInfo) -> Result<()> {
+        self.check(&info).await?;
+        let (skill, tool_type) = match &info.skill {
+            Skill::Item => (SkillKind::Item, None),
+            Skill::Fluid => (SkillKind::Fluid, None),
+            Skill::UseTool(tool_type) => (SkillKind::UseTool, Some(tool_type.clone())),
+        };
+        let mut conn = self.db.transport().await.map_err(Error::Db)?;
+        query(
+            "
+            INSERT INTO vehicle(id, name, skill, tool_type, battery, enabled, home_node_name)
+            VALUES($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT (id) DO UPDATE SET
+                name = EXCLUDED.name,
+                skill = EXCLUDED.skill,
+                tool_type = EXCLUDED.tool_type,
+                battery = EXCLUDED.battery,
+                enabled = EXCLUDED.enabled,
+                home_node_name = EXCLUDED.home_node_name;
+        ",
+        )
+        .bind(info.id)
+        .bind(&info.name)
+        .bind(skill)
+        .bind(tool_type)
+        .bind(info.battery.map(Json))
+        .bind(info.enabled)
+        .bind(&info.home)
+        .execute(&mut *conn)
+        .await
+        .map_err(Error::Db)?;
+        self.vehicles.write().await.insert(info.id, info);
+        Ok(())
+    }
+
+    pub async fn set_enabled(&self, id: i32, enabled: bool) -> Result<()> {
+        let mut conn = self.db.transport().await.map_err(Error::Db)?;
+        let updated = query("UPDATE vehicle SET enabled = $2 WHERE id = $1;")
+            .bind(id)
+            .bind(enabled)
+            .execute(&mut *conn)
+            .await
+            .map_err(Error::Db)?
+            .rows_affected();
+        if updated == 0 {
+            return Err(Error::UnknownVehicle(id));
+        }
+        if let Some(info) = self.vehicles.write().await.get_mut(&id) {
+            info.enabled = enabled;
+        }
+        Ok(())
+    }
+
+    /// Known from now on without being stored.
+    #[cfg(test)]
+    pub(crate) async fn insert(&self, info: VehicleInfo) {
+        self.vehicles.write().await.insert(info.id, info);
+    }
+
+    pub async fn remove(&self, id: i32) -> Result<()> {
+        let mut conn = self.db.transport().await.map_err(Error::Db)?;
+        let removed = query("DELETE FROM vehicle WHERE id = $1;")
+            .bind(id)
+            .execute(&mut *conn)
+            .await
+            .map_err(Error::Db)?
+            .rows_affected();
+        if removed == 0 {
+            return Err(Error::UnknownVehicle(id));
+        }
+        self.vehicles.write().await.remove(&id);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::transport::track::{self, offline_sample};
+
+    async fn registry(battery: BatteryConfig) -> VehicleRegistry {
+        // Checks never reach the pool.
+        let (db, track_graph) = offline_sample().await;
+        VehicleRegistry::new(db, Arc::new(track_graph), battery)
+    }
+
+    #[test]
+    fn vehicle_info() {
+        let info: VehicleInfo = serde_json::from_value(json!({
+            "id": 101,
+            "name": "wrench-1",
+            "skill": {"use_tool": "wrench"}
+        }))
+        .unwrap();
+        assert_eq!(info.skill, Skill::UseTool(ToolType::Wrench));
+        assert!(info.enabled);
+        assert_eq!((info.battery, info.home), (None, None));
+
+        let row = VehicleRow {
+            id: 4001,
+            name: "fluid-1".to_string(),
+            skill: SkillKind::Fluid,
+            tool_type: None,
+            battery: Some(Json(BatteryOverride {
+                charge: Some(0.4),
+                ..Default::default()
+            })),
+            enabled: false,
+            home_node_name: Some("P1    ".to_string()),
+        };
+        let info = row.into_info().unwrap();
+        assert_eq!(info.skill, Skill::Fluid);
+        assert_eq!(info.home.as_deref(), Some("P1"));
+        assert_eq!(
+            serde_json::to_value(&info).unwrap()["skill"],
+            json!("fluid")
+        );
+
+        let row = VehicleRow {
+            id: 100,
+            name: "tool".to_string(),
+            skill: SkillKind::UseTool,
+            tool_type: None,
+            battery: None,
+            enabled: true,
+            home_node_name: None,
+        };
+        assert!(row.into_info().is_none());
+    }
+
+    #[tokio::test]
+    async fn battery_policy() {
+        let config: BatteryConfig =
+            serde_json::from_value(json!({"fluid": {"charge": 0.4}})).unwrap();
+        let registry = registry(config).await;
+        let mut info: VehicleInfo = serde_json::from_value(json!({
+            "id": 4001,
+            "name": "fluid-1",
+            "skill": "fluid",
+            "battery": {"critical": 0.2}
+        }))
+        .unwrap();
+        let policy = registry.battery_policy(&info).unwrap();
+        assert_eq!((policy.critical, policy.charge), (0.2, 0.4));
+
+        // Tops up at 0.96 and stops at 0.95, over and over.
+        info.battery = Some(BatteryOverride {
+            opportunistic: Some(0.96),
+            ..Default::default()
+        });
+        assert!(matches!(
+            registry.battery_policy(&info),
+            Err(Error::InvalidBattery(_))
+        ));
+        assert!(matches!(
+            registry.upsert(info).await,
+            Err(Error::InvalidBattery(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn home() {
+        let registry = registry(BatteryConfig::default()).await;
+        let mut info: VehicleInfo = serde_json::from_value(json!({
+            "id": 2000,
+            "name": "item-1",
+            "skill": "item",
+            "home": "P1"
+        }))
+        .unwrap();
+        registry.check(&info).await.unwrap();
+
+        info.home = Some("S1".to_string());
+        assert!(matches!(
+            registry.check(&info).await,
+            Err(Error::NotParking(_))
+        ));
+        info.home = Some("P9".to_string());
+        assert!(matches!(
+            registry.check(&info).await,
+            Err(Error::PathFind(track::Error::UnknownNode(_)))
+        ));
+    }
+}
@@ -1,64 +1,60 @@
-use crate::db_manager::DbManager;
-use crate::transport::schedule::{Error, Result};
+use crate::transport::schedule::{Error, Result, TaskStore};
 use crate::transport::vehicle;
-use crate::transport::vehicle::Skill;
-use sqlx::{PgConnection, query};
 use std::sync::Arc;
-use tokio::sync::mpsc;
+use tokio::{
+    sync::{mpsc, watch},
+    task::JoinHandle,
+};
 use tracing::error;
 
+/// Writes task events to the task store, one at a time in the order vehicles sent them.
 pub struct StateUpdate {
     vehicle_event_receiver: mpsc::Receiver<vehicle::Event>,
-    db: Arc<DbManager>,
+    tasks: Arc<dyn TaskStore>,
 }
 
 impl StateUpdate {
-    pub fn run(vehicle_event_receiver: mpsc::Receiver<vehicle::Event>, db: Arc<DbManager>) {
+    pub fn run(
+        vehicle_event_receiver: mpsc::Receiver<vehicle::Event>,
+        tasks: Arc<dyn TaskStore>,
+        stop: watch::Receiver<bool>,
+    ) -> JoinHandle<()> {
         let status_update = Self {
             vehicle_event_receiver,
-            db,
+            tasks,
         };
-        tokio::spawn(status_update.task());
+        tokio::spawn(status_update.task(stop))
     }
 
-    async fn task(mut self) {
-        while let Some(event) = self.vehicle_event_receiver.recv().await {
-            match self.db.transport().await {
-                Ok(mut conn) => {
-                    if let Err(e) = Self::process_event(&event, &mut *conn).await {
-                        error!("Schedule State Update suffer error. {:#?}.", e);
-                    }
-                }
-                Err(e) => {
-                    error!("Schedule State Update suffer error. {:#?}.", e);
+    /// On stop the queue is closed, events already in it are still written.
+    async fn task(mut self, mut stop: watch::Receiver<bool>) {
+        loop {
+            let event = tokio::select! {
+                event = self.vehicle_event_receiver.recv() => event,
+                _ = stop.changed() => {
+                    self.vehicle_event_receiver.close();
+                    self.vehicle_event_receiver.recv().await
                 }
+            };
+            let Some(event) = event else {
+                break;
+            };
+            if let Err(e) = Self::process_event(&event, &*self.tasks).await {
+                error!("Schedule State Update suffer error. {:#?}.", e);
             }
         }
     }
 
-    async fn process_event(event: &vehicle::Event, conn: &mut PgConnection) -> Result<()> {
+    async fn process_event(event: &vehicle::Event, tasks: &dyn TaskStore) -> Result<()> {
         match event {
             vehicle::Event::ProcessDone {
                 vehicle_id: _,
                 vehicle_skill,
                 task_id,
+                shortfall,
             } => {
-                let table_name = match vehicle_skill {
-                    Skill::Item => "item",
-                    Skill::Fluid => "fluid",
-                    Skill::UseTool(_) => "use_tool",
-                };
-                let query_sql = format!(
-                    "
-                    UPDATE {}
-                    SET state = 'completed'
-                    WHERE id = $1;
-                ",
-                    table_name
-                );
-                query(&query_sql)
-                    .bind(task_id)
-                    .execute(conn)
+                tasks
+                    .complete(vehicle_skill.into(), *task_id, shortfall.as_deref())
                     .await
                     .map_err(Error::Db)?;
             }
@@ -67,27 +63,31 @@ impl StateUpdate {
                 vehicle_skill,
                 task_id,
             } => {
-                let table_name = match vehicle_skill {
-                    Skill::Item => "item",
-                    Skill::Fluid => "fluid",
-                    Skill::UseTool(_) => "use_tool",
-                };
-                let query_sql = format!(
-                    "
-                    UPDATE {}
-                    SET vehicle_id = $1,state = 'processing'
-                    WHERE id = $2;
-                ",
-                    table_name
-                );
-                sqlx::query(&query_sql)
-                    .bind(vehicle_id)
-                    .bind(task_id)
-                    .execute(conn)
+                tasks
+                    .start(vehicle_skill.into(), *task_id, *vehicle_id)
                     .await
                     .map_err(Error::Db)?;
             }
-            vehicle::Event::ChargeStart | vehicle::Event::ChargeDone => {}
+            vehicle::Event::ProcessAborted {
+                vehicle_id: _,
+                vehicle_skill,
+                task_id,
+                requeue,
+                reason,
+            } => {
+                let kind = vehicle_skill.into();
+                if *requeue {
+                    tasks.requeue(kind, *task_id, reason).await
+                } else {
+                    tasks.fail(kind, *task_id, reason).await
+                }
+                .map_err(Error::Db)?;
+            }
+            vehicle::Event::StateChanged { .. }
+            | vehicle::Event::Moved { .. }
+            | vehicle::Event::ChargeStart { .. }
+            | vehicle::Event::ChargeDone { .. }
+            | vehicle::Event::Deadlock { .. } => {}
         }
 
         Ok(())
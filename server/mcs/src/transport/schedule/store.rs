@@ -0,0 +1,409 @@
+use std::{fmt, sync::Arc};
+
+use async_trait::async_trait;
+use sqlx::{Result, prelude::FromRow, query, query_as, query_scalar};
+#[cfg(test)]
+use tokio::sync::RwLock;
+
+#[cfg(test)]
+use crate::transport::schedule::query::TaskState;
+use crate::{
+    db_manager::DbManager,
+    transport::{
+        schedule::{Load, TaskKind},
+        track::Slots,
+        vehicle::{Cargo, ToolType},
+    },
+};
+
+/// Pending tasks handed to the planner in one round.
+const PENDING_LIMIT: i64 = 20;
+
+#[derive(Debug, Clone, FromRow)]
+pub struct ItemFluidRow {
+    pub id: i32,
+    pub begin_node_name: String,
+    pub end_node_name: String,
+    pub cargo_name: Option<String>,
+    pub quantity: Option<i32>,
+    pub from_slot_first: Option<i32>,
+    pub from_slot_last: Option<i32>,
+    pub to_slot_first: Option<i32>,
+    pub to_slot_last: Option<i32>,
+}
+
+impl ItemFluidRow {
+    pub fn load(&self) -> Load {
+        let slots = |first: Option<i32>, last: Option<i32>| {
+            first.zip(last).map(|(first, last)| Slots { first, last })
+        };
+        Load {
+            cargo: Cargo {
+                name: self.cargo_name.clone(),
+                quantity: self.quantity,
+            },
+            from_slots: slots(self.from_slot_first, self.from_slot_last),
+            to_slots: slots(self.to_slot_first, self.to_slot_last),
+        }
+    }
+}
+
+#[derive(Debug, Clone, FromRow)]
+pub struct UseToolRow {
+    pub id: i32,
+    pub end_node_name: String,
+    pub tool_type: ToolType,
+}
+
+/// Where tasks wait for a vehicle and how far they got.
+#[async_trait]
+pub trait TaskStore: Send + Sync + fmt::Debug {
+    /// Item or fluid tasks nobody took yet, oldest first.
+    async fn pending_trans(&self, kind: TaskKind) -> Result<Vec<ItemFluidRow>>;
+
+    /// Oldest first.
+    async fn pending_use_tool(&self) -> Result<Vec<UseToolRow>>;
+
+    /// Returns the id of the new task.
+    async fn add_trans(&self, kind: TaskKind, from: &str, to: &str, load: &Load) -> Result<i32>;
+
+    async fn add_use_tool(&self, node: &str, tool_type: ToolType) -> Result<i32>;
+
+    async fn start(&self, kind: TaskKind, id: i32, vehicle_id: i32) -> Result<()>;
+
+    /// A delivery that came up short keeps what was missing as its fail reason.
+    async fn complete(&self, kind: TaskKind, id: i32, shortfall: Option<&str>) -> Result<()>;
+
+    /// Back to pending for any vehicle to take.
+    async fn requeue(&self, kind: TaskKind, id: i32, reason: &str) -> Result<()>;
+
+    async fn fail(&self, kind: TaskKind, id: i32, reason: &str) -> Result<()>;
+}
+
+/// The item, fluid and use_tool tables.
+#[derive(Debug)]
+pub struct PgTaskStore {
+    db: Arc<DbManager>,
+}
+
+impl PgTaskStore {
+    pub fn new(db: Arc<DbManager>) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl TaskStore for PgTaskStore {
+    async fn pending_trans(&self, kind: TaskKind) -> Result<Vec<ItemFluidRow>> {
+        let mut conn = self.db.transport().await?;
+        let query_sql = format!(
+            "
+            SELECT id, begin_node_name, end_node_name, cargo_name, quantity,
+                from_slot_first, from_slot_last, to_slot_first, to_slot_last
+            FROM {}
+            WHERE state = 'pending'
+            ORDER BY date_created
+            LIMIT $1;
+        ",
+            kind.name()
+        );
+        query_as(&query_sql)
+            .bind(PENDING_LIMIT)
+            .fetch_all(&mut *conn)
+            .await
+    }
+
+    async fn pending_use_tool(&self) -> Result<Vec<UseToolRow>> {
+        let mut conn = self.db.transport().await?;
+        query_as(
+            "
+            SELECT id, end_node_name, tool_type
+            FROM use_tool
+            WHERE state = 'pending'
+            ORDER BY date_created
+            LIMIT $1;
+        ",
+        )
+        .bind(PENDING_LIMIT)
+        .fetch_all(&mut *conn)
+        .await
+    }
+
+    async fn add_trans(&self, kind: TaskKind, from: &str, to: &str, load: &Load) -> Result<i32> {
+        let mut conn = self.db.transport().await?;
+        let query_sql = format!(
+            "
+            INSERT INTO {}(begin_node_name, end_node_name, cargo_name, quantity,
+                from_slot_first, from_slot_last, to_slot_first, to_slot_last)
+            VALUES($1, $2, $3, $4, $5, $6, $7, $8)
+            RETURNING id;
+        ",
+            kind.name()
+        );
+        query_scalar(&query_sql)
+            .bind(from)
+            .bind(to)
+            .bind(&load.cargo.name)
+            .bind(load.cargo.quantity)
+            .bind(load.from_slots.map(|slots| slots.first))
+            .bind(load.from_slots.map(|slots| slots.last))
+            .bind(load.to_slots.map(|slots| slots.first))
+            .bind(load.to_slots.map(|slots| slots.last))
+            .fetch_one(&mut *conn)
+            .await
+    }
+
+    async fn add_use_tool(&self, node: &str, tool_type: ToolType) -> Result<i32> {
+        let mut conn = self.db.transport().await?;
+        query_scalar("INSERT INTO use_tool(end_node_name, tool_type) VALUES($1,$2) RETURNING id")
+            .bind(node)
+            .bind(tool_type)
+            .fetch_one(&mut *conn)
+            .await
+    }
+
+    async fn start(&self, kind: TaskKind, id: i32, vehicle_id: i32) -> Result<()> {
+        let mut conn = self.db.transport().await?;
+        let query_sql = format!(
+            "
+            UPDATE {}
+            SET vehicle_id = $1,state = 'processing'
+            WHERE id = $2;
+        ",
+            kind.name()
+        );
+        query(&query_sql)
+            .bind(vehicle_id)
+            .bind(id)
+            .execute(&mut *conn)
+            .await?;
+        Ok(())
+    }
+
+    async fn complete(&self, kind: TaskKind, id: i32, shortfall: Option<&str>) -> Result<()> {
+        let mut conn = self.db.transport().await?;
+        let query_sql = format!(
+            "
+            UPDATE {}
+            SET state = 'completed', fail_reason = $2
+            WHERE id = $1;
+        ",
+            kind.name()
+        );
+        query(&query_sql)
+            .bind(id)
+            .bind(shortfall)
+            .execute(&mut *conn)
+            .await?;
+        Ok(())
+    }
+
+    async fn requeue(&self, kind: TaskKind, id: i32, reason: &str) -> Result<()> {
+        let mut conn = self.db.transport().await?;
+        let query_sql = format!(
+            "
+            UPDATE {}
+            SET vehicle_id = NULL, state = 'pending', fail_reason = $2
+            WHERE id = $1;
+        ",
+            kind.name()
+        );
+        query(&query_sql)
+            .bind(id)
+            .bind(reason)
+            .execute(&mut *conn)
+            .await?;
+        Ok(())
+    }
+
+    async fn fail(&self, kind: TaskKind, id: i32, reason: &str) -> Result<()> {
+        let mut conn = self.db.transport().await?;
+        let query_sql = format!(
+            "
+            UPDATE {}
+            SET state = 'failed', fail_reason = $2
+            WHERE id = $1;
+        ",
+            kind.name()
+        );
+        query(&query_sql)
+            .bind(id)
+            .bind(reason)
+            .execute(&mut *conn)
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[derive(Debug, Clone)]
+enum MemoryRow {
+    Trans(ItemFluidRow),
+    UseTool(UseToolRow),
+}
+
+#[cfg(test)]
+#[derive(Debug, Clone)]
+pub struct MemoryTask {
+    pub kind: TaskKind,
+    pub state: TaskState,
+    pub vehicle_id: Option<i32>,
+    pub fail_reason: Option<String>,
+    row: MemoryRow,
+}
+
+#[cfg(test)]
+impl MemoryTask {
+    fn id(&self) -> i32 {
+        match &self.row {
+            MemoryRow::Trans(row) => row.id,
+            MemoryRow::UseTool(row) => row.id,
+        }
+    }
+}
+
+/// Tasks kept in the order they were added, ids count up per kind like the tables' serials.
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub struct MemoryTaskStore {
+    tasks: RwLock<Vec<MemoryTask>>,
+}
+
+#[cfg(test)]
+impl MemoryTaskStore {
+    pub async fn task(&self, kind: TaskKind, id: i32) -> Option<MemoryTask> {
+        self.tasks
+            .read()
+            .await
+            .iter()
+            .find(|task| task.kind == kind && task.id() == id)
+            .cloned()
+    }
+
+    async fn add(&self, kind: TaskKind, row: impl FnOnce(i32) -> MemoryRow) -> i32 {
+        let mut tasks = self.tasks.write().await;
+        let id = tasks.iter().filter(|task| task.kind == kind).count() as i32 + 1;
+        tasks.push(MemoryTask {
+            kind,
+            state: TaskState::Pending,
+            vehicle_id: None,
+            fail_reason: None,
+            row: row(id),
+        });
+        id
+    }
+
+    async fn update(&self, kind: TaskKind, id: i32, update: impl FnOnce(&mut MemoryTask)) {
+        if let Some(task) = self
+            .tasks
+            .write()
+            .await
+            .iter_mut()
+            .find(|task| task.kind == kind && task.id() == id)
+        {
+            update(task);
+        }
+    }
+
+    async fn pending(&self, kind: TaskKind) -> Vec<MemoryRow> {
+        self.tasks
+            .read()
+            .await
+            .iter()
+            .filter(|task| task.kind == kind && task.state == TaskState::Pending)
+            .take(PENDING_LIMIT as usize)
+            .map(|task| task.row.clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+#[async_trait]
+impl TaskStore for MemoryTaskStore {
+    async fn pending_trans(&self, kind: TaskKind) -> Result<Vec<ItemFluidRow>> {
+        let rows = self.pending(kind).await.into_iter();
+        Ok(rows
+            .filter_map(|row| match row {
+                MemoryRow::Trans(row) => Some(row),
+                MemoryRow::UseTool(_) => None,
+            })
+            .collect())
+    }
+
+    async fn pending_use_tool(&self) -> Result<Vec<UseToolRow>> {
+        let rows = self.pending(TaskKind::UseTool).await.into_iter();
+        Ok(rows
+            .filter_map(|row| match row {
+                MemoryRow::UseTool(row) => Some(row),
+                MemoryRow::Trans(_) => None,
+            })
+            .collect())
+    }
+
+    async fn add_trans(&self, kind: TaskKind, from: &str, to: &str, load: &Load) -> Result<i32> {
+        Ok(self
+            .add(kind, |id| {
+                MemoryRow::Trans(ItemFluidRow {
+                    id,
+                    begin_node_name: from.to_string(),
+                    end_node_name: to.to_string(),
+                    cargo_name: load.cargo.name.clone(),
+                    quantity: load.cargo.quantity,
+                    from_slot_first: load.from_slots.map(|slots| slots.first),
+                    from_slot_last: load.from_slots.map(|slots| slots.last),
+                    to_slot_first: load.to_slots.map(|slots| slots.first),
+                    to_slot_last: load.to_slots.map(|slots| slots.last),
+                })
+            })
+            .await)
+    }
+
+    async fn add_use_tool(&self, node: &str, tool_type: ToolType) -> Result<i32> {
+        Ok(self
+            .add(TaskKind::UseTool, |id| {
+                MemoryRow::UseTool(UseToolRow {
+                    id,
+                    end_node_name: node.to_string(),
+                    tool_type,
+                })
+            })
+            .await)
+    }
+
+    async fn start(&self, kind: TaskKind, id: i32, vehicle_id: i32) -> Result<()> {
+        self.update(kind, id, |task| {
+            task.vehicle_id = Some(vehicle_id);
+            task.state = TaskState::Processing;
+        })
+        .await;
+        Ok(())
+    }
+
+    async fn complete(&self, kind: TaskKind, id: i32, shortfall: Option<&str>) -> Result<()> {
+        self.update(kind, id, |task| {
+            task.state = TaskState::Completed;
+            task.fail_reason = shortfall.map(str::to_string);
+        })
+        .await;
+        Ok(())
+    }
+
+    async fn requeue(&self, kind: TaskKind, id: i32, reason: &str) -> Result<()> {
+        self.update(kind, id, |task| {
+            task.vehicle_id = None;
+            task.state = TaskState::Pending;
+            task.fail_reason = Some(reason.to_string());
+        })
+        .await;
+        Ok(())
+    }
+
+    async fn fail(&self, kind: TaskKind, id: i32, reason: &str) -> Result<()> {
+        self.update(kind, id, |task| {
+            task.state = TaskState::Failed;
+            task.fail_reason = Some(reason.to_string());
+        })
+        .await;
+        Ok(())
+    }
+}
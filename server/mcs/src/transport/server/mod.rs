@@ -1,90 +1,479 @@
-use crate::transport::{prelude::Position, schedule::ScheduleExec};
-use serde::{Deserialize, Serialize};
-use tokio::net::ToSocketAddrs;
+use crate::transport::{
+    prelude::Position,
+    schedule::{self, Load, ScheduleExec, TaskFilter, TaskKind, VehicleInfo},
+    track::{self, Format, Graph, Layout, Resource, layout},
+    vehicle::{self, ToolType},
+};
+use jsonrpsee::types::{
+    ErrorObjectOwned,
+    error::{INTERNAL_ERROR_CODE, INVALID_PARAMS_CODE},
+};
+use jsonrpsee::{PendingSubscriptionSink, core::SubscriptionResult, server::ServerHandle};
+use serde::Deserialize;
+use tokio::{net::ToSocketAddrs, sync::broadcast};
+use tracing::{error, warn};
 
-pub struct Server {
-    server: jsonrpsee::server::Server,
-    module: jsonrpsee::RpcModule<ScheduleExec>,
-}
+use protocol::{GetActionParams, GetActionResponse, PROTOCOL_VERSION};
+
+mod protocol;
+
+pub struct Server;
 
 impl Server {
-    pub async fn run(addr: impl ToSocketAddrs, schedule_exec: ScheduleExec) {
+    /// Stopping the returned handle lets in-flight calls finish before the server goes down.
+    pub async fn run(
+        addr: impl ToSocketAddrs,
+        schedule_exec: ScheduleExec,
+    ) -> std::io::Result<ServerHandle> {
         let mut module = jsonrpsee::RpcModule::new(schedule_exec);
         Self::register_method(&mut module);
-        let server = jsonrpsee::server::ServerBuilder::new()
-            .build(addr)
-            .await
-            .unwrap();
-        let handle = server.start(module);
-        tokio::spawn(async move {
-            handle.stopped().await;
-        });
+        Self::register_track_method(&mut module);
+        Self::register_task_method(&mut module);
+        Self::register_subscription(&mut module);
+        let server = jsonrpsee::server::ServerBuilder::new().build(addr).await?;
+        Ok(server.start(module))
     }
 
     fn register_method(module: &mut jsonrpsee::RpcModule<ScheduleExec>) {
+        module
+            .register_async_method("vehicle_list", async |_, schedule_exec, _| {
+                schedule_exec.vehicles().await
+            })
+            .unwrap();
+
         module
             .register_async_method("vehicle_get_action", async |params, schedule_exec, _| {
+                let params = params.parse::<GetActionParams>()?;
+                if !params.supported() {
+                    return Err(ErrorObjectOwned::owned(
+                        INVALID_PARAMS_CODE,
+                        format!(
+                            "unsupported protocol version {}, expect {}",
+                            params.version, PROTOCOL_VERSION
+                        ),
+                        None::<()>,
+                    ));
+                }
+                let position: Position = params.position.into();
+                let action = schedule_exec
+                    .get_action(
+                        params.id,
+                        position,
+                        params.battery_level,
+                        params.tool_level,
+                        params.outcome,
+                    )
+                    .await
+                    .map_err(schedule_error)?;
+                Ok::<_, ErrorObjectOwned>(GetActionResponse::new(action.as_ref(), &position))
+            })
+            .unwrap();
+
+        module
+            .register_async_method("vehicle_registry_list", async |_, schedule_exec, _| {
+                schedule_exec.registered_vehicles().await
+            })
+            .unwrap();
+
+        module
+            .register_async_method("vehicle_register", async |params, schedule_exec, _| {
+                let info = params.parse::<VehicleInfo>()?;
+                schedule_exec
+                    .register_vehicle(info)
+                    .await
+                    .map_err(schedule_error)
+            })
+            .unwrap();
+
+        module
+            .register_async_method("vehicle_enable", async |params, schedule_exec, _| {
                 #[derive(Deserialize, Debug)]
                 struct Params {
                     id: i32,
-                    position: (f64, f64, f64),
-                    battery_level: f32,
-                    tool_level: Option<f32>,
+                    enabled: bool,
                 }
-                #[derive(Serialize, Debug)]
-                struct Response {
-                    action: String,
-                    position: Position,
+                let params = params.parse::<Params>()?;
+                schedule_exec
+                    .set_vehicle_enabled(params.id, params.enabled)
+                    .await
+                    .map_err(schedule_error)
+            })
+            .unwrap();
+
+        module
+            .register_async_method("vehicle_remove", async |params, schedule_exec, _| {
+                #[derive(Deserialize, Debug)]
+                struct Params {
+                    id: i32,
                 }
-                match params.parse::<Params>() {
-                    Ok(params) => {
-                        let position: Position = params.position.into();
-                        let action = schedule_exec
-                            .get_action(
-                                params.id,
-                                position,
-                                params.battery_level,
-                                params.tool_level,
-                            )
-                            .await;
-                        format!("{:?}", action)
+                let params = params.parse::<Params>()?;
+                schedule_exec
+                    .remove_vehicle(params.id)
+                    .await
+                    .map_err(schedule_error)
+            })
+            .unwrap();
+    }
+
+    fn register_subscription(module: &mut jsonrpsee::RpcModule<ScheduleExec>) {
+        module
+            .register_subscription(
+                "subscribe_events",
+                "event",
+                "unsubscribe_events",
+                async |params, pending, schedule_exec, _| {
+                    #[derive(Deserialize, Debug, Default)]
+                    struct Params {
+                        vehicle_id: Option<i32>,
                     }
-                    Err(e) => {
-                        println!("parse error {:#?}", e);
-                        "".to_string()
+                    let params = match params.parse::<Option<Params>>() {
+                        Ok(params) => params.unwrap_or_default(),
+                        Err(e) => {
+                            pending.reject(e).await;
+                            return Ok(());
+                        }
+                    };
+                    let receiver = schedule_exec.subscribe();
+                    Self::feed_events(pending, receiver, params.vehicle_id).await
+                },
+            )
+            .unwrap();
+    }
+
+    /// Streams events to one subscriber until it leaves or the feed closes.
+    async fn feed_events(
+        pending: PendingSubscriptionSink,
+        mut receiver: broadcast::Receiver<vehicle::Event>,
+        vehicle_id: Option<i32>,
+    ) -> SubscriptionResult {
+        let sink = pending.accept().await?;
+        loop {
+            let event = tokio::select! {
+                _ = sink.closed() => break,
+                event = receiver.recv() => event,
+            };
+            let event = match event {
+                Ok(event) => event,
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    warn!(
+                        "event subscriber {:?} fell behind, {} events skipped.",
+                        sink.subscription_id(),
+                        skipped
+                    );
+                    continue;
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            };
+            if vehicle_id.is_some_and(|id| !event.involves(id)) {
+                continue;
+            }
+            let message = serde_json::value::to_raw_value(&event)?;
+            if sink.send(message).await.is_err() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn register_task_method(module: &mut jsonrpsee::RpcModule<ScheduleExec>) {
+        #[derive(Deserialize, Debug)]
+        struct TransParams {
+            from: String,
+            to: String,
+            #[serde(flatten)]
+            load: Load,
+        }
+
+        module
+            .register_async_method("task_trans_item", async |params, schedule_exec, _| {
+                let params = params.parse::<TransParams>()?;
+                schedule_exec
+                    .adder()
+                    .trans_items(&params.from, &params.to, &params.load)
+                    .await
+                    .map_err(schedule_error)
+            })
+            .unwrap();
+
+        module
+            .register_async_method("task_trans_fluid", async |params, schedule_exec, _| {
+                let params = params.parse::<TransParams>()?;
+                schedule_exec
+                    .adder()
+                    .trans_fluid(&params.from, &params.to, &params.load)
+                    .await
+                    .map_err(schedule_error)
+            })
+            .unwrap();
+
+        module
+            .register_async_method("task_list", async |params, schedule_exec, _| {
+                let filter = params.parse::<Option<TaskFilter>>()?.unwrap_or_default();
+                schedule_exec
+                    .tasks()
+                    .tasks(&filter)
+                    .await
+                    .map_err(schedule_error)
+            })
+            .unwrap();
+
+        module
+            .register_async_method("task_get", async |params, schedule_exec, _| {
+                #[derive(Deserialize, Debug)]
+                struct Params {
+                    kind: TaskKind,
+                    id: i32,
+                }
+                let params = params.parse::<Params>()?;
+                schedule_exec
+                    .tasks()
+                    .task(params.kind, params.id)
+                    .await
+                    .map_err(schedule_error)
+            })
+            .unwrap();
+
+        module
+            .register_async_method("task_use_tool", async |params, schedule_exec, _| {
+                #[derive(Deserialize, Debug)]
+                struct Params {
+                    node: String,
+                    tool_type: ToolType,
+                }
+                let params = params.parse::<Params>()?;
+                schedule_exec
+                    .adder()
+                    .use_tool(&params.node, params.tool_type)
+                    .await
+                    .map_err(schedule_error)
+            })
+            .unwrap();
+    }
+
+    fn register_track_method(module: &mut jsonrpsee::RpcModule<ScheduleExec>) {
+        module
+            .register_async_method("track_export_layout", async |_, schedule_exec, _| {
+                schedule_exec
+                    .track_graph()
+                    .export()
+                    .await
+                    .map_err(track_error)
+            })
+            .unwrap();
+
+        module
+            .register_async_method("track_locks", async |_, schedule_exec, _| {
+                schedule_exec.track_graph().locks().await
+            })
+            .unwrap();
+
+        module
+            .register_async_method("track_reservations", async |_, schedule_exec, _| {
+                schedule_exec.track_graph().reservations().await
+            })
+            .unwrap();
+
+        module
+            .register_async_method("track_maintenance", async |_, schedule_exec, _| {
+                schedule_exec.track_graph().maintenance().await
+            })
+            .unwrap();
+
+        module
+            .register_async_method("track_disable", async |params, schedule_exec, _| {
+                #[derive(Deserialize, Debug)]
+                struct Params {
+                    resource: Resource,
+                    reason: String,
+                    operator: String,
+                }
+                let params = params.parse::<Params>()?;
+                schedule_exec
+                    .disable(params.resource, &params.reason, &params.operator)
+                    .await
+                    .map_err(track_error)
+            })
+            .unwrap();
+
+        module
+            .register_async_method("track_enable", async |params, schedule_exec, _| {
+                #[derive(Deserialize, Debug)]
+                struct Params {
+                    resource: Resource,
+                }
+                let params = params.parse::<Params>()?;
+                schedule_exec
+                    .track_graph()
+                    .enable(params.resource)
+                    .await
+                    .map_err(track_error)
+            })
+            .unwrap();
+
+        module
+            .register_async_method("track_render", async |params, schedule_exec, _| {
+                #[derive(Deserialize, Debug)]
+                #[serde(default)]
+                struct Params {
+                    format: Format,
+                    vehicles: bool,
+                }
+                impl Default for Params {
+                    fn default() -> Self {
+                        Self {
+                            format: Format::Svg,
+                            vehicles: true,
+                        }
                     }
                 }
+                let params = params.parse::<Option<Params>>()?.unwrap_or_default();
+                Ok::<_, ErrorObjectOwned>(
+                    schedule_exec.render(params.format, params.vehicles).await,
+                )
+            })
+            .unwrap();
+
+        module
+            .register_async_method("track_validate", async |params, schedule_exec, _| {
+                #[derive(Deserialize, Debug, Default)]
+                struct Params {
+                    layout: Option<Layout>,
+                }
+                let params = params.parse::<Option<Params>>()?.unwrap_or_default();
+                match params.layout {
+                    Some(layout) => Graph::validate_layout(&layout).map_err(layout_error),
+                    None => schedule_exec
+                        .track_graph()
+                        .validate()
+                        .await
+                        .map_err(track_error),
+                }
             })
             .unwrap();
+
+        module
+            .register_async_method("track_import_layout", async |params, schedule_exec, _| {
+                #[derive(Deserialize, Debug)]
+                struct Params {
+                    layout: Layout,
+                }
+                let params = params.parse::<Params>()?;
+                schedule_exec
+                    .import_layout(&params.layout)
+                    .await
+                    .map_err(layout_error)
+            })
+            .unwrap();
+    }
+}
+
+fn layout_error(e: layout::Error) -> ErrorObjectOwned {
+    error!("track layout error: {:?}.", e);
+    match e {
+        layout::Error::Version(version) => ErrorObjectOwned::owned(
+            INVALID_PARAMS_CODE,
+            format!("unsupported layout version {}", version),
+            None::<()>,
+        ),
+        layout::Error::UnknownNode(name) => ErrorObjectOwned::owned(
+            INVALID_PARAMS_CODE,
+            format!("unknown node {}", name),
+            None::<()>,
+        ),
+        layout::Error::InUse(vehicles) => ErrorObjectOwned::owned(
+            INTERNAL_ERROR_CODE,
+            format!("vehicles {:?} are on the track", vehicles),
+            None::<()>,
+        ),
+        layout::Error::Db(e) => {
+            ErrorObjectOwned::owned(INTERNAL_ERROR_CODE, e.to_string(), None::<()>)
+        }
+    }
+}
+
+fn track_error(e: track::Error) -> ErrorObjectOwned {
+    error!("track error: {:?}.", e);
+    let code = match e {
+        track::Error::UnknownNode(_)
+        | track::Error::UnknownEdge(_)
+        | track::Error::NoRoute { .. } => INVALID_PARAMS_CODE,
+        track::Error::Db(_) => INTERNAL_ERROR_CODE,
+    };
+    ErrorObjectOwned::owned(code, e.to_string(), None::<()>)
+}
+
+fn schedule_error(e: schedule::Error) -> ErrorObjectOwned {
+    match e {
+        schedule::Error::PathFind(e) => track_error(e),
+        schedule::Error::SameNode(name) => {
+            error!("schedule error: task begins and ends at {}.", name);
+            ErrorObjectOwned::owned(
+                INVALID_PARAMS_CODE,
+                format!("task begins and ends at {}", name),
+                None::<()>,
+            )
+        }
+        schedule::Error::InvalidLoad(reason) => {
+            error!("schedule error: invalid load, {}.", reason);
+            ErrorObjectOwned::owned(INVALID_PARAMS_CODE, reason, None::<()>)
+        }
+        schedule::Error::InvalidBattery(reason) => {
+            error!("schedule error: invalid battery policy, {}.", reason);
+            ErrorObjectOwned::owned(INVALID_PARAMS_CODE, reason, None::<()>)
+        }
+        schedule::Error::VehicleBusy => {
+            error!("schedule error: vehicle busy.");
+            ErrorObjectOwned::owned(INTERNAL_ERROR_CODE, "vehicle busy", None::<()>)
+        }
+        schedule::Error::UnknownVehicle(id) => {
+            warn!("schedule error: vehicle {} is not registered.", id);
+            ErrorObjectOwned::owned(
+                INVALID_PARAMS_CODE,
+                format!("vehicle {} is not registered", id),
+                None::<()>,
+            )
+        }
+        schedule::Error::VehicleDisabled(id) => {
+            warn!("schedule error: vehicle {} is disabled.", id);
+            ErrorObjectOwned::owned(
+                INVALID_PARAMS_CODE,
+                format!("vehicle {} is disabled", id),
+                None::<()>,
+            )
+        }
+        schedule::Error::NotParking(name) => {
+            error!("schedule error: {} is not a parking station.", name);
+            ErrorObjectOwned::owned(
+                INVALID_PARAMS_CODE,
+                format!("{} is not a parking station", name),
+                None::<()>,
+            )
+        }
+        schedule::Error::Db(e) => {
+            error!("schedule error: {:?}.", e);
+            ErrorObjectOwned::owned(INTERNAL_ERROR_CODE, e.to_string(), None::<()>)
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::{thread, time::Duration};
+    use std::sync::Arc;
 
     use super::*;
 
-    use crate::{db_manager::DbManager, transport::track::Graph};
-    use dotenvy::dotenv;
-    use jsonrpsee::core::client::ClientT;
-    use sqlx::postgres::PgPoolOptions;
-    use tokio::time::sleep;
+    use crate::transport::track::offline_sample;
 
     #[tokio::test]
     async fn jsonrpc_server() {
-        dotenv().ok();
-        let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
-        println!("Connecting to database: {}", database_url);
-        let pool = PgPoolOptions::new()
-            .max_connections(5)
-            .connect(&database_url)
-            .await
-            .expect("Failed to create pool");
-        let db = DbManager::new(pool);
-        let track_graph = Graph::new(db.clone()).await;
-        let schedule_exec = ScheduleExec::new(track_graph, db).await;
-        Server::run("0.0.0.0:5000", schedule_exec).await;
+        // Starting up only logs what it can not load from a missing database.
+        let (db, track_graph) = offline_sample().await;
+        let task_store = Arc::new(schedule::MemoryTaskStore::default());
+        let schedule_exec =
+            ScheduleExec::new(track_graph, db, task_store, schedule::Config::default()).await;
+        let handle = Server::run("127.0.0.1:0", schedule_exec).await.unwrap();
+        handle.stop().unwrap();
+        handle.stopped().await;
 
         // sleep(Duration::from_secs(9999999999)).await;
         // thread::sleep(Duration::from_secs(99999999999999));
@@ -0,0 +1,269 @@
+use serde::{Deserialize, Serialize};
+
+use crate::transport::{
+    prelude::Position,
+    track::{Interaction, Node},
+    vehicle::{Action, Cargo, Outcome, Station},
+};
+
+/// Bumped whenever a field changes meaning or goes away, new optional fields keep the version.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+fn default_version() -> u32 {
+    PROTOCOL_VERSION
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GetActionParams {
+    /// Protocol the vehicle speaks, vehicles before versioning speak 1.
+    #[serde(default = "default_version")]
+    pub version: u32,
+    pub id: i32,
+    pub position: (f64, f64, f64),
+    pub battery_level: f32,
+    pub tool_level: Option<f32>,
+    /// How the station action from the last response went, left out after anything else.
+    #[serde(default)]
+    pub outcome: Option<Outcome>,
+}
+
+impl GetActionParams {
+    /// Older and newer vehicles alike would misread the actions sent back.
+    pub fn supported(&self) -> bool {
+        self.version == PROTOCOL_VERSION
+    }
+}
+
+/// Same shape as the vehicle's `Pos`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Pos {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl From<&Position> for Pos {
+    fn from(position: &Position) -> Self {
+        Self {
+            x: position.0,
+            y: position.1,
+            z: position.2,
+        }
+    }
+}
+
+/// Node the action heads for or works at.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Target {
+    pub node: String,
+    pub position: Pos,
+}
+
+impl From<&Node> for Target {
+    fn from(node: &Node) -> Self {
+        Self {
+            node: node.name.clone(),
+            position: (&node.position).into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Work {
+    #[serde(flatten)]
+    pub target: Option<Target>,
+    #[serde(flatten)]
+    pub interaction: Interaction,
+    #[serde(flatten)]
+    pub cargo: Cargo,
+}
+
+impl From<&Station> for Work {
+    fn from(station: &Station) -> Self {
+        Self {
+            target: station.node.as_deref().map(Target::from),
+            interaction: station.interaction.clone(),
+            cargo: station.cargo.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WireAction {
+    Move(Target),
+    Drop(Work),
+    Suck(Work),
+    Fill(Work),
+    Use(Work),
+    Wait,
+    WaitForLift(Target),
+    /// Nothing to do until asked again.
+    Idle,
+}
+
+impl From<Option<&Action>> for WireAction {
+    fn from(action: Option<&Action>) -> Self {
+        match action {
+            Some(Action::Move(node)) => Self::Move(node.as_ref().into()),
+            Some(Action::Drop(station)) => Self::Drop(station.into()),
+            Some(Action::Suck(station)) => Self::Suck(station.into()),
+            Some(Action::Fill(station)) => Self::Fill(station.into()),
+            Some(Action::Use(station)) => Self::Use(station.into()),
+            Some(Action::Wait) => Self::Wait,
+            Some(Action::WaitForLift(node)) => Self::WaitForLift(node.as_ref().into()),
+            None => Self::Idle,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GetActionResponse {
+    pub version: u32,
+    pub action: WireAction,
+    /// Where the vehicle reported to be when asking.
+    pub position: Pos,
+}
+
+impl GetActionResponse {
+    pub fn new(action: Option<&Action>, position: &Position) -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            action: action.into(),
+            position: position.into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::transport::{
+        track::{NodeType, Side, Slots},
+        vehicle::ActionSequenceBuilder,
+    };
+
+    #[test]
+    fn tagged_json() {
+        let node = Arc::new(Node {
+            id: 5,
+            name: "S2".to_string(),
+            node_type: NodeType::ItemStocker,
+            position: Position(-1.0, 1.0, 0.0),
+            capacity: 1,
+            interaction: Interaction {
+                side: Some(Side::NegY),
+                slots: Some(Slots { first: 1, last: 8 }),
+                port: None,
+            },
+            comment: None,
+        });
+        let mut actions = ActionSequenceBuilder::new()
+            .move_to(node.clone())
+            .suck()
+            .build();
+        let position = Position(-1.0, 2.0, 0.0);
+
+        let response = GetActionResponse::new(actions.next_action(), &position);
+        assert_eq!(
+            serde_json::to_value(&response).unwrap(),
+            json!({
+                "version": 1,
+                "action": {"type": "move", "node": "S2", "position": {"x": -1.0, "y": 1.0, "z": 0.0}},
+                "position": {"x": -1.0, "y": 2.0, "z": 0.0}
+            })
+        );
+
+        actions.pop_next_action();
+        let response = GetActionResponse::new(actions.next_action(), &position);
+        assert_eq!(
+            serde_json::to_value(&response.action).unwrap(),
+            json!({
+                "type": "suck",
+                "node": "S2",
+                "position": {"x": -1.0, "y": 1.0, "z": 0.0},
+                "side": "negy",
+                "slots": {"first": 1, "last": 8}
+            })
+        );
+
+        let cargo = Cargo {
+            name: Some("copper_plate".to_string()),
+            quantity: Some(32),
+        };
+        let mut actions = ActionSequenceBuilder::new()
+            .move_to(node)
+            .drop()
+            .cargo(&cargo, Some(Slots { first: 2, last: 3 }))
+            .build();
+        actions.pop_next_action();
+        let response = GetActionResponse::new(actions.next_action(), &position);
+        assert_eq!(
+            serde_json::to_value(&response.action).unwrap(),
+            json!({
+                "type": "drop",
+                "node": "S2",
+                "position": {"x": -1.0, "y": 1.0, "z": 0.0},
+                "side": "negy",
+                "slots": {"first": 2, "last": 3},
+                "name": "copper_plate",
+                "quantity": 32
+            })
+        );
+
+        let response = GetActionResponse::new(None, &position);
+        assert_eq!(
+            serde_json::to_value(&response.action).unwrap(),
+            json!({"type": "idle"})
+        );
+    }
+
+    #[test]
+    fn params() {
+        let params: GetActionParams = serde_json::from_value(json!({
+            "id": 2000,
+            "position": [1.0, 2.0, 0.0],
+            "battery_level": 0.5,
+            "tool_level": null
+        }))
+        .unwrap();
+        assert_eq!(params.version, 1);
+        assert!(params.supported());
+        assert_eq!(params.outcome, None);
+
+        let params: GetActionParams = serde_json::from_value(json!({
+            "id": 2000,
+            "position": [1.0, 2.0, 0.0],
+            "battery_level": 0.5,
+            "tool_level": null,
+            "outcome": {"status": "partial", "count": 3, "expected": 16}
+        }))
+        .unwrap();
+        assert_eq!(
+            params.outcome,
+            Some(Outcome::Partial {
+                count: 3,
+                expected: 16
+            })
+        );
+        assert!(
+            serde_json::from_value::<GetActionParams>(json!({"id": "2000", "position": [1.0]}))
+                .is_err()
+        );
+        for version in [0, 2] {
+            let params: GetActionParams = serde_json::from_value(json!({
+                "version": version,
+                "id": 2000,
+                "position": [1.0, 2.0, 0.0],
+                "battery_level": 0.5,
+                "tool_level": null
+            }))
+            .unwrap();
+            assert!(!params.supported());
+        }
+    }
+}
@@ -0,0 +1,214 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::transport::{
+    prelude::Position,
+    track::{Edge, Interaction, Node, NodeType, Side, Slots},
+};
+
+pub const LAYOUT_VERSION: u32 = 1;
+
+#[derive(Debug)]
+pub enum Error {
+    Version(u32),
+    UnknownNode(String),
+    /// Vehicles still on the track, node ids are renumbered under them.
+    InUse(Vec<i32>),
+    Db(sqlx::Error),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+fn default_capacity() -> i32 {
+    1
+}
+
+fn is_default_capacity(capacity: &i32) -> bool {
+    *capacity == 1
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LayoutNode {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub node_type: NodeType,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    /// Slots for vehicles, only written when a station holds more than one.
+    #[serde(
+        default = "default_capacity",
+        skip_serializing_if = "is_default_capacity"
+    )]
+    pub capacity: i32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub side: Option<Side>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slots: Option<Slots>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LayoutEdge {
+    pub begin: String,
+    pub end: String,
+    pub cost: f64,
+    /// Negative when the edge can only be travelled from `begin` to `end`.
+    pub reverse_cost: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Layout {
+    pub version: u32,
+    pub nodes: Vec<LayoutNode>,
+    pub edges: Vec<LayoutEdge>,
+}
+
+impl Layout {
+    fn check_version(&self) -> Result<()> {
+        if self.version != LAYOUT_VERSION {
+            return Err(Error::Version(self.version));
+        }
+        Ok(())
+    }
+
+    pub fn from_graph(nodes: &[Node], edges: &[Edge]) -> Self {
+        let mut nodes: Vec<&Node> = nodes.iter().collect();
+        nodes.sort_by_key(|node| node.id);
+        let names: HashMap<i32, &str> = nodes
+            .iter()
+            .map(|node| (node.id, node.name.as_str()))
+            .collect();
+
+        let mut edges: Vec<&Edge> = edges.iter().collect();
+        edges.sort_by_key(|edge| edge.id);
+
+        Self {
+            version: LAYOUT_VERSION,
+            nodes: nodes
+                .into_iter()
+                .map(|node| LayoutNode {
+                    name: node.name.clone(),
+                    node_type: node.node_type,
+                    x: node.position.0,
+                    y: node.position.1,
+                    z: node.position.2,
+                    capacity: node.capacity,
+                    side: node.interaction.side,
+                    slots: node.interaction.slots,
+                    port: node.interaction.port.clone(),
+                    comment: node.comment.clone(),
+                })
+                .collect(),
+            edges: edges
+                .into_iter()
+                .filter_map(|edge| {
+                    Some(LayoutEdge {
+                        begin: names.get(&edge.begin_node_id)?.to_string(),
+                        end: names.get(&edge.end_node_id)?.to_string(),
+                        cost: edge.cost,
+                        reverse_cost: edge.reverse_cost,
+                    })
+                })
+                .collect(),
+        }
+    }
+
+    /// Builds nodes and edges with ids numbered in file order.
+    pub fn to_graph(&self) -> Result<(Vec<Node>, Vec<Edge>)> {
+        self.check_version()?;
+        let nodes: Vec<Node> = self
+            .nodes
+            .iter()
+            .zip(1..)
+            .map(|(node, id)| Node {
+                id,
+                name: node.name.clone(),
+                node_type: node.node_type,
+                position: Position(node.x, node.y, node.z),
+                capacity: node.capacity,
+                interaction: Interaction {
+                    side: node.side,
+                    slots: node.slots,
+                    port: node.port.clone(),
+                },
+                comment: node.comment.clone(),
+            })
+            .collect();
+        let ids: HashMap<&str, i32> = nodes
+            .iter()
+            .map(|node| (node.name.as_str(), node.id))
+            .collect();
+        let id_of = |name: &str| {
+            ids.get(name)
+                .copied()
+                .ok_or_else(|| Error::UnknownNode(name.to_string()))
+        };
+
+        let mut edges = Vec::with_capacity(self.edges.len());
+        for (edge, id) in self.edges.iter().zip(1..) {
+            edges.push(Edge {
+                id,
+                begin_node_id: id_of(&edge.begin)?,
+                end_node_id: id_of(&edge.end)?,
+                cost: edge.cost,
+                reverse_cost: edge.reverse_cost,
+            });
+        }
+        Ok((nodes, edges))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::track::sample_layout;
+
+    #[test]
+    fn round_trip() {
+        let (nodes, edges) = sample_layout();
+        let layout = Layout::from_graph(&nodes, &edges);
+        let json = serde_json::to_string_pretty(&layout).unwrap();
+        let parsed: Layout = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, layout);
+
+        let (parsed_nodes, parsed_edges) = parsed.to_graph().unwrap();
+        assert_eq!(parsed_nodes.len(), nodes.len());
+        assert_eq!(parsed_edges.len(), edges.len());
+        assert_eq!(Layout::from_graph(&parsed_nodes, &parsed_edges), layout);
+    }
+
+    #[test]
+    fn graph_test_file() {
+        let layout: Layout =
+            serde_json::from_str(include_str!("../../../layout/graph_test.json")).unwrap();
+        let (nodes, edges) = sample_layout();
+        assert_eq!(layout, Layout::from_graph(&nodes, &edges));
+    }
+
+    #[test]
+    fn reject_bad_layout() {
+        for version in [0, 99] {
+            let layout = Layout {
+                version,
+                nodes: Vec::new(),
+                edges: Vec::new(),
+            };
+            assert!(matches!(layout.to_graph(), Err(Error::Version(v)) if v == version));
+        }
+
+        let layout = serde_json::from_str::<Layout>(
+            r#"{
+                "version": 1,
+                "nodes": [{"name": "P1", "type": "parking_station", "x": 0, "y": 0, "z": 0}],
+                "edges": [{"begin": "P1", "end": "A1", "cost": 1, "reverse_cost": 1}]
+            }"#,
+        )
+        .unwrap();
+        assert!(matches!(layout.to_graph(), Err(Error::UnknownNode(name)) if name == "A1"));
+    }
+}
@@ -0,0 +1,769 @@
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap, HashSet, VecDeque},
+    sync::Arc,
+};
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    constant,
+    transport::{
+        prelude::Position,
+        track::{
+            Edge, Maintenance, Node, NodeLock, NodeType, Snap,
+            reservation::{self, Reservations, Resource},
+        },
+    },
+};
+
+#[derive(Debug, Clone, Copy)]
+pub struct Link {
+    pub edge_id: i32,
+    pub to: i32,
+    pub cost: f64,
+}
+
+#[derive(Debug, Default)]
+pub struct TrackMap {
+    nodes: HashMap<i32, Arc<Node>>,
+    names: HashMap<String, i32>,
+    edges: HashMap<i32, Edge>,
+    adjacency: HashMap<i32, Vec<Link>>,
+    locks: HashMap<i32, Vec<NodeLock>>,
+    maintenance: HashMap<Resource, Maintenance>,
+    /// Lift node to the lowest node id of its shaft.
+    shafts: HashMap<i32, i32>,
+    /// Keeps the A* estimate a lower bound on layouts pricing some track below its length.
+    heuristic_scale: f64,
+}
+
+#[derive(Debug, PartialEq)]
+struct Candidate {
+    estimate: f64,
+    node_id: i32,
+}
+
+impl Eq for Candidate {}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // BinaryHeap is a max-heap, reverse to pop the lowest estimate first.
+        other
+            .estimate
+            .total_cmp(&self.estimate)
+            .then_with(|| other.node_id.cmp(&self.node_id))
+    }
+}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl TrackMap {
+    pub fn new(nodes: Vec<Node>, edges: Vec<Edge>) -> Self {
+        let mut map = Self::default();
+        for node in nodes {
+            map.names.insert(node.name.clone(), node.id);
+            map.nodes.insert(node.id, Arc::new(node));
+        }
+        for edge in edges {
+            if !map.nodes.contains_key(&edge.begin_node_id)
+                || !map.nodes.contains_key(&edge.end_node_id)
+            {
+                continue;
+            }
+            // Lift cars are slower than vehicles on the rails, cost stays comparable as time.
+            let factor = if map.is_lift(edge.begin_node_id) && map.is_lift(edge.end_node_id) {
+                constant::VEHICLE_SPEED / constant::LIFT_SPEED
+            } else {
+                1.0
+            };
+            // pgRouting convention: a negative cost means the direction does not exist.
+            if edge.cost >= 0.0 {
+                map.adjacency
+                    .entry(edge.begin_node_id)
+                    .or_default()
+                    .push(Link {
+                        edge_id: edge.id,
+                        to: edge.end_node_id,
+                        cost: edge.cost * factor,
+                    });
+            }
+            if edge.reverse_cost >= 0.0 {
+                map.adjacency
+                    .entry(edge.end_node_id)
+                    .or_default()
+                    .push(Link {
+                        edge_id: edge.id,
+                        to: edge.begin_node_id,
+                        cost: edge.reverse_cost * factor,
+                    });
+            }
+            map.edges.insert(edge.id, edge);
+        }
+        map.shafts = map.find_shafts();
+        map.heuristic_scale = map.find_heuristic_scale();
+        map
+    }
+
+    /// The cheapest cost per unit of estimate over every link, at most 1.
+    fn find_heuristic_scale(&self) -> f64 {
+        let mut scale: f64 = 1.0;
+        for (from, links) in &self.adjacency {
+            for link in links {
+                let length = estimate(&self.nodes[from].position, &self.nodes[&link.to].position);
+                if length > 0.0 {
+                    scale = scale.min(link.cost / length);
+                }
+            }
+        }
+        scale
+    }
+
+    fn is_lift(&self, node_id: i32) -> bool {
+        self.nodes
+            .get(&node_id)
+            .is_some_and(|node| node.node_type == NodeType::Lift)
+    }
+
+    fn find_shafts(&self) -> HashMap<i32, i32> {
+        let mut shafts = HashMap::new();
+        for node in self.nodes() {
+            if node.node_type != NodeType::Lift || shafts.contains_key(&node.id) {
+                continue;
+            }
+            let mut stack = vec![node.id];
+            while let Some(id) = stack.pop() {
+                if shafts.insert(id, node.id).is_some() {
+                    continue;
+                }
+                for edge in self.edges.values() {
+                    let other = if edge.begin_node_id == id {
+                        edge.end_node_id
+                    } else if edge.end_node_id == id {
+                        edge.begin_node_id
+                    } else {
+                        continue;
+                    };
+                    if self.is_lift(other) && !shafts.contains_key(&other) {
+                        stack.push(other);
+                    }
+                }
+            }
+        }
+        shafts
+    }
+
+    /// The shaft a lift node belongs to, named by its lowest node id.
+    pub fn shaft(&self, node_id: i32) -> Option<i32> {
+        self.shafts.get(&node_id).copied()
+    }
+
+    /// The zone only one vehicle may be in at a time: the shaft of a lift node, or a fork itself.
+    pub fn zone(&self, node_id: i32) -> Option<i32> {
+        self.shaft(node_id).or_else(|| {
+            self.nodes
+                .get(&node_id)
+                .filter(|node| node.node_type == NodeType::Fork)
+                .map(|node| node.id)
+        })
+    }
+
+    pub fn node(&self, id: i32) -> Option<&Arc<Node>> {
+        self.nodes.get(&id)
+    }
+
+    pub fn nodes(&self) -> Vec<&Arc<Node>> {
+        let mut nodes: Vec<&Arc<Node>> = self.nodes.values().collect();
+        nodes.sort_by_key(|node| node.id);
+        nodes
+    }
+
+    pub fn edges(&self) -> Vec<&Edge> {
+        let mut edges: Vec<&Edge> = self.edges.values().collect();
+        edges.sort_by_key(|edge| edge.id);
+        edges
+    }
+
+    pub fn node_by_name(&self, name: &str) -> Option<&Arc<Node>> {
+        self.nodes.get(self.names.get(name)?)
+    }
+
+    /// The cheapest link in service leading from `from` to `to`.
+    pub fn link(&self, from: i32, to: i32) -> Option<Link> {
+        self.adjacency
+            .get(&from)?
+            .iter()
+            .filter(|link| link.to == to && !self.is_disabled(Resource::Edge(link.edge_id)))
+            .min_by(|a, b| a.cost.total_cmp(&b.cost))
+            .copied()
+    }
+
+    pub fn set_lock(&mut self, lock: NodeLock) {
+        let locks = self.locks.entry(lock.node_id).or_default();
+        locks.retain(|held| held.vehicle_id != lock.vehicle_id);
+        locks.push(lock);
+    }
+
+    pub fn remove_lock(&mut self, node_id: i32, vehicle_id: i32) {
+        if let Some(locks) = self.locks.get_mut(&node_id) {
+            locks.retain(|lock| lock.vehicle_id != vehicle_id);
+            if locks.is_empty() {
+                self.locks.remove(&node_id);
+            }
+        }
+    }
+
+    pub fn has_locks(&self, vehicle_id: i32) -> bool {
+        self.locks
+            .values()
+            .flatten()
+            .any(|lock| lock.vehicle_id == vehicle_id)
+    }
+
+    pub fn renew_locks(&mut self, vehicle_id: i32, expires_at: DateTime<Utc>) {
+        for lock in self.locks.values_mut().flatten() {
+            if lock.vehicle_id == vehicle_id {
+                lock.expires_at = expires_at;
+            }
+        }
+    }
+
+    pub fn locks(&self) -> Vec<NodeLock> {
+        let mut locks: Vec<NodeLock> = self.locks.values().flatten().cloned().collect();
+        locks.sort_by_key(|lock| (lock.node_id, lock.vehicle_id));
+        locks
+    }
+
+    pub fn contains(&self, resource: Resource) -> bool {
+        match resource {
+            Resource::Node(id) => self.nodes.contains_key(&id),
+            Resource::Edge(id) => self.edges.contains_key(&id),
+        }
+    }
+
+    pub fn set_maintenance(&mut self, maintenance: Maintenance) {
+        self.maintenance.insert(maintenance.resource, maintenance);
+    }
+
+    pub fn remove_maintenance(&mut self, resource: Resource) {
+        self.maintenance.remove(&resource);
+    }
+
+    pub fn maintenance(&self) -> Vec<Maintenance> {
+        let mut maintenance: Vec<Maintenance> = self.maintenance.values().cloned().collect();
+        maintenance.sort_by_key(|maintenance| maintenance.disabled_at);
+        maintenance
+    }
+
+    pub fn is_disabled(&self, resource: Resource) -> bool {
+        self.maintenance.contains_key(&resource)
+    }
+
+    /// The vehicle may stand on a disabled first node, it only must not go further in.
+    pub fn crosses_disabled(&self, route: &[Arc<Node>]) -> bool {
+        route
+            .iter()
+            .skip(1)
+            .any(|node| self.is_disabled(Resource::Node(node.id)))
+            || route
+                .windows(2)
+                .any(|pair| pair[0].id != pair[1].id && self.link(pair[0].id, pair[1].id).is_none())
+    }
+
+    /// Vehicles holding an unexpired slot on the node.
+    pub fn occupants(&self, node_id: i32) -> Vec<i32> {
+        self.locks
+            .get(&node_id)
+            .into_iter()
+            .flatten()
+            .filter(|lock| !lock.is_expired())
+            .map(|lock| lock.vehicle_id)
+            .collect()
+    }
+
+    /// Every slot of the node is taken.
+    pub fn is_full(&self, node_id: i32) -> bool {
+        self.nodes
+            .get(&node_id)
+            .is_some_and(|node| self.occupants(node_id).len() >= node.capacity.max(1) as usize)
+    }
+
+    /// Projects the position onto the closest edge, ties go to the lower edge id. Disabled edges
+    /// and edges touching a disabled node are skipped, routing would not lead off them either.
+    pub fn snap(&self, position: &Position) -> Option<Snap> {
+        let (edge, begin, end, t, _) = self
+            .edges()
+            .into_iter()
+            .filter(|edge| {
+                !self.is_disabled(Resource::Edge(edge.id))
+                    && !self.is_disabled(Resource::Node(edge.begin_node_id))
+                    && !self.is_disabled(Resource::Node(edge.end_node_id))
+            })
+            .filter_map(|edge| {
+                let begin = self.nodes.get(&edge.begin_node_id)?;
+                let end = self.nodes.get(&edge.end_node_id)?;
+                let (t, distance) = project(position, &begin.position, &end.position);
+                Some((edge, begin, end, t, distance))
+            })
+            .min_by(|a, b| a.4.total_cmp(&b.4))?;
+
+        let length = distance(&begin.position, &end.position);
+        let offset = t * length;
+        let mut entries = Vec::new();
+        if edge.cost >= 0.0 {
+            entries.push((end.clone(), length - offset));
+        }
+        if edge.reverse_cost >= 0.0 {
+            entries.push((begin.clone(), offset));
+        }
+        entries.sort_by(|a, b| a.1.total_cmp(&b.1));
+        Some(Snap {
+            offset,
+            length,
+            entries: entries.into_iter().map(|(node, _)| node).collect(),
+            begin_node_id: begin.id,
+        })
+    }
+
+    /// Full or disabled nodes can be left but not entered.
+    pub fn find_path(&self, begin_id: i32, end_id: i32) -> Option<Vec<Arc<Node>>> {
+        let target = self.nodes.get(&end_id)?.position;
+        self.search(
+            begin_id,
+            None,
+            |node| node.id == end_id,
+            |node| self.heuristic_scale * estimate(&node.position, &target),
+            |_, _| false,
+        )
+    }
+
+    /// Whether any track leads from one node to the other, full, disabled or not.
+    pub fn connected(&self, begin_id: i32, end_id: i32) -> bool {
+        let mut seen = HashSet::from([begin_id]);
+        let mut queue = VecDeque::from([begin_id]);
+        while let Some(node_id) = queue.pop_front() {
+            if node_id == end_id {
+                return true;
+            }
+            for link in self.adjacency.get(&node_id).into_iter().flatten() {
+                if seen.insert(link.to) {
+                    queue.push_back(link.to);
+                }
+            }
+        }
+        false
+    }
+
+    /// The cheapest route to a node of the type with a free slot.
+    pub fn find_free_station(&self, begin_id: i32, node_type: NodeType) -> Option<Vec<Arc<Node>>> {
+        self.search(
+            begin_id,
+            None,
+            |node| {
+                node.node_type == node_type
+                    && !self.is_full(node.id)
+                    && !self.is_disabled(Resource::Node(node.id))
+            },
+            |_| 0.0,
+            |_, _| false,
+        )
+    }
+
+    /// Like `find_path`, also avoiding edges and forks other vehicles reserved for the time
+    /// this vehicle would pass them. Full nodes this vehicle holds a slot on can be entered.
+    pub fn find_timed_path(
+        &self,
+        begin_id: i32,
+        end_id: i32,
+        vehicle_id: i32,
+        depart: DateTime<Utc>,
+        reservations: &Reservations,
+    ) -> Option<Vec<Arc<Node>>> {
+        let target = self.nodes.get(&end_id)?.position;
+        let margin = reservation::margin();
+        self.search(
+            begin_id,
+            Some(vehicle_id),
+            |node| node.id == end_id,
+            |node| self.heuristic_scale * estimate(&node.position, &target),
+            |link, cost| {
+                let leave = depart + reservation::travel_time(cost);
+                let arrive = leave + reservation::travel_time(link.cost);
+                if !reservations.is_free(
+                    vehicle_id,
+                    Resource::Edge(link.edge_id),
+                    leave - margin,
+                    arrive + margin,
+                ) {
+                    return true;
+                }
+                self.nodes[&link.to].node_type == NodeType::Fork
+                    && !reservations.is_free(
+                        vehicle_id,
+                        Resource::Node(link.to),
+                        arrive - margin,
+                        arrive + margin,
+                    )
+            },
+        )
+    }
+
+    /// A* from `begin_id` to the first node reaching `goal`, `blocked` is asked before following a
+    /// link with the cost it took to reach the link. A zero `heuristic` makes it Dijkstra.
+    fn search(
+        &self,
+        begin_id: i32,
+        vehicle_id: Option<i32>,
+        goal: impl Fn(&Node) -> bool,
+        heuristic: impl Fn(&Node) -> f64,
+        blocked: impl Fn(&Link, f64) -> bool,
+    ) -> Option<Vec<Arc<Node>>> {
+        self.nodes.get(&begin_id)?;
+
+        let mut open = BinaryHeap::new();
+        let mut cost_so_far: HashMap<i32, f64> = HashMap::new();
+        let mut came_from: HashMap<i32, i32> = HashMap::new();
+        cost_so_far.insert(begin_id, 0.0);
+        open.push(Candidate {
+            estimate: 0.0,
+            node_id: begin_id,
+        });
+
+        while let Some(Candidate { node_id, .. }) = open.pop() {
+            if goal(&self.nodes[&node_id]) {
+                return Some(self.reconstruct(&came_from, node_id));
+            }
+            let cost = cost_so_far[&node_id];
+            for link in self.adjacency.get(&node_id).into_iter().flatten() {
+                if (self.is_full(link.to)
+                    && vehicle_id.is_none_or(|id| !self.occupants(link.to).contains(&id)))
+                    || self.is_disabled(Resource::Node(link.to))
+                    || self.is_disabled(Resource::Edge(link.edge_id))
+                    || blocked(link, cost)
+                {
+                    continue;
+                }
+                let next_cost = cost + link.cost;
+                if cost_so_far
+                    .get(&link.to)
+                    .is_some_and(|known| *known <= next_cost)
+                {
+                    continue;
+                }
+                cost_so_far.insert(link.to, next_cost);
+                came_from.insert(link.to, node_id);
+                open.push(Candidate {
+                    estimate: next_cost + heuristic(&self.nodes[&link.to]),
+                    node_id: link.to,
+                });
+            }
+        }
+        None
+    }
+
+    fn reconstruct(&self, came_from: &HashMap<i32, i32>, end_id: i32) -> Vec<Arc<Node>> {
+        let mut path = vec![self.nodes[&end_id].clone()];
+        let mut current = end_id;
+        while let Some(previous) = came_from.get(&current) {
+            path.push(self.nodes[previous].clone());
+            current = *previous;
+        }
+        path.reverse();
+        path
+    }
+}
+
+/// Lower bound of the cost between two points as long as no track is priced below its length, a
+/// floor change is never cheaper than the height it climbs.
+fn estimate(a: &Position, b: &Position) -> f64 {
+    ((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2)).sqrt() + (a.2 - b.2).abs()
+}
+
+fn distance(a: &Position, b: &Position) -> f64 {
+    ((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2) + (a.2 - b.2).powi(2)).sqrt()
+}
+
+/// Where along `a` - `b` the closest point to `p` lies, as a fraction of the segment, and how
+/// far away it is.
+fn project(p: &Position, a: &Position, b: &Position) -> (f64, f64) {
+    let ab = (b.0 - a.0, b.1 - a.1, b.2 - a.2);
+    let ap = (p.0 - a.0, p.1 - a.1, p.2 - a.2);
+    let length = ab.0 * ab.0 + ab.1 * ab.1 + ab.2 * ab.2;
+    let t = if length == 0.0 {
+        0.0
+    } else {
+        ((ap.0 * ab.0 + ap.1 * ab.1 + ap.2 * ab.2) / length).clamp(0.0, 1.0)
+    };
+    let closest = Position(a.0 + ab.0 * t, a.1 + ab.1 * t, a.2 + ab.2 * t);
+    (t, distance(p, &closest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::track::{Layout, sample_layout};
+
+    fn sample() -> TrackMap {
+        let (nodes, edges) = sample_layout();
+        TrackMap::new(nodes, edges)
+    }
+
+    fn names(path: &[Arc<Node>]) -> Vec<&str> {
+        path.iter().map(|node| node.name.as_str()).collect()
+    }
+
+    // A - B - D is cheaper than A - C - D.
+    fn diamond() -> TrackMap {
+        let layout = serde_json::from_str::<Layout>(
+            r#"{
+                "version": 1,
+                "nodes": [
+                    {"name": "A", "type": "fork", "x": 0, "y": 0, "z": 0},
+                    {"name": "B", "type": "fork", "x": 1, "y": 1, "z": 0},
+                    {"name": "C", "type": "fork", "x": 1, "y": -1, "z": 0},
+                    {"name": "D", "type": "fork", "x": 2, "y": 0, "z": 0}
+                ],
+                "edges": [
+                    {"begin": "A", "end": "B", "cost": 1, "reverse_cost": 1},
+                    {"begin": "B", "end": "D", "cost": 1, "reverse_cost": 1},
+                    {"begin": "A", "end": "C", "cost": 1.5, "reverse_cost": 1.5},
+                    {"begin": "C", "end": "D", "cost": 1.5, "reverse_cost": 1.5}
+                ]
+            }"#,
+        )
+        .unwrap();
+        let (nodes, edges) = layout.to_graph().unwrap();
+        TrackMap::new(nodes, edges)
+    }
+
+    #[test]
+    fn find_path() {
+        let map = sample();
+        let path = map.find_path(5, 4).unwrap();
+        assert_eq!(names(&path), ["S2", "A6", "A2", "A1", "A4", "A3", "S1"]);
+
+        let path = map.find_path(6, 2).unwrap();
+        assert_eq!(names(&path), ["S3", "A5", "A6", "P2"]);
+    }
+
+    #[test]
+    fn cheap_detour() {
+        // A - C is priced far below its length, the estimate must not rule the detour out.
+        let layout = serde_json::from_str::<Layout>(
+            r#"{
+                "version": 1,
+                "nodes": [
+                    {"name": "A", "type": "fork", "x": 0, "y": 0, "z": 0},
+                    {"name": "B", "type": "fork", "x": 10, "y": 0, "z": 0},
+                    {"name": "C", "type": "fork", "x": 0, "y": 20, "z": 0}
+                ],
+                "edges": [
+                    {"begin": "A", "end": "B", "cost": 10, "reverse_cost": 10},
+                    {"begin": "A", "end": "C", "cost": 1, "reverse_cost": 1},
+                    {"begin": "C", "end": "B", "cost": 1, "reverse_cost": 1}
+                ]
+            }"#,
+        )
+        .unwrap();
+        let (nodes, edges) = layout.to_graph().unwrap();
+        let map = TrackMap::new(nodes, edges);
+        assert_eq!(names(&map.find_path(1, 2).unwrap()), ["A", "C", "B"]);
+    }
+
+    #[test]
+    fn find_path_skip_locked() {
+        let mut map = sample();
+        map.set_lock(NodeLock::new(3, 2000));
+        assert!(map.find_path(5, 3).is_none());
+        // The owner standing on a locked node can still leave it.
+        let path = map.find_path(3, 5).unwrap();
+        assert_eq!(
+            names(&path),
+            ["C1", "A2", "A1", "A4", "A3", "A5", "A6", "S2"]
+        );
+
+        map.remove_lock(3, 2000);
+        let path = map.find_path(5, 3).unwrap();
+        assert_eq!(names(&path), ["S2", "A6", "A2", "C1"]);
+
+        // An expired lease no longer blocks the node.
+        map.set_lock(NodeLock {
+            node_id: 3,
+            vehicle_id: 2000,
+            expires_at: Utc::now() - chrono::TimeDelta::seconds(1),
+        });
+        assert!(map.find_path(5, 3).is_some());
+    }
+
+    #[test]
+    fn find_timed_path_avoid_reserved() {
+        let map = diamond();
+        let now = Utc::now();
+
+        let mut reservations = Reservations::default();
+        let path = map.find_timed_path(1, 4, 2000, now, &reservations).unwrap();
+        assert_eq!(names(&path), ["A", "B", "D"]);
+
+        let b = map.node(2).unwrap().clone();
+        reservations.replace(
+            2500,
+            Reservations::plan(&map, 2500, &[(b, chrono::TimeDelta::seconds(30))], now),
+        );
+        let path = map.find_timed_path(1, 4, 2000, now, &reservations).unwrap();
+        assert_eq!(names(&path), ["A", "C", "D"]);
+        // The owner itself is not held back by its reservation.
+        let path = map.find_timed_path(1, 4, 2500, now, &reservations).unwrap();
+        assert_eq!(names(&path), ["A", "B", "D"]);
+    }
+
+    #[test]
+    fn skip_disabled() {
+        let mut map = diamond();
+        let disable = |resource| Maintenance {
+            resource,
+            reason: "rail rebuild".to_string(),
+            operator: "ops".to_string(),
+            disabled_at: Utc::now(),
+        };
+        let ab = map.find_path(1, 4).unwrap();
+        assert_eq!(names(&ab), ["A", "B", "D"]);
+
+        map.set_maintenance(disable(Resource::Node(2)));
+        assert!(map.crosses_disabled(&ab));
+        assert_eq!(names(&map.find_path(1, 4).unwrap()), ["A", "C", "D"]);
+        // Standing on the disabled node does not keep the vehicle from leaving it.
+        assert_eq!(names(&map.find_path(2, 4).unwrap()), ["B", "D"]);
+
+        map.set_maintenance(disable(Resource::Edge(3)));
+        assert!(map.link(1, 3).is_none());
+        assert!(map.find_path(1, 4).is_none());
+        // Still joined once the maintenance is over.
+        assert!(map.connected(1, 4));
+
+        map.remove_maintenance(Resource::Node(2));
+        assert!(!map.crosses_disabled(&ab));
+        assert_eq!(map.maintenance().len(), 1);
+    }
+
+    #[test]
+    fn snap() {
+        let map = sample();
+
+        // Between C1 and A2, both ends can be driven to.
+        let snap = map.snap(&Position(0.9, 0.3, 0.0)).unwrap();
+        assert!((snap.offset - 0.3).abs() < 1e-9);
+        assert_eq!(names(&snap.entries), ["C1", "A2"]);
+        assert!((snap.distance_to(&snap.entries[1]) - 0.7).abs() < 1e-9);
+
+        // A3 -> A5 is one-way, only A5 is reachable even though A3 is nearer.
+        let snap = map.snap(&Position(0.7, 1.9, 0.0)).unwrap();
+        assert_eq!(names(&snap.entries), ["A5"]);
+
+        // Beyond the end of an edge snaps to its end point.
+        let snap = map.snap(&Position(1.0, 4.0, 0.0)).unwrap();
+        assert_eq!(names(&snap.entries), ["S1", "A3"]);
+        assert!(snap.distance_to(&snap.entries[0]).abs() < 1e-9);
+    }
+
+    #[test]
+    fn snap_skips_disabled() {
+        let mut map = diamond();
+        let disable = |resource| Maintenance {
+            resource,
+            reason: "rail rebuild".to_string(),
+            operator: "ops".to_string(),
+            disabled_at: Utc::now(),
+        };
+        let position = Position(0.5, 0.4, 0.0);
+        assert_eq!(names(&map.snap(&position).unwrap().entries), ["A", "B"]);
+
+        map.set_maintenance(disable(Resource::Node(2)));
+        assert_eq!(names(&map.snap(&position).unwrap().entries), ["A", "C"]);
+        map.set_maintenance(disable(Resource::Edge(3)));
+        assert_eq!(names(&map.snap(&position).unwrap().entries), ["C", "D"]);
+    }
+
+    #[test]
+    fn lift() {
+        // The lift climbs straight up, the ramp through R is longer but faster than the car.
+        let layout = serde_json::from_str::<Layout>(
+            r#"{
+                "version": 1,
+                "nodes": [
+                    {"name": "A", "type": "fork", "x": 0, "y": 0, "z": 0},
+                    {"name": "L1", "type": "lift", "x": 1, "y": 0, "z": 0},
+                    {"name": "L2", "type": "lift", "x": 1, "y": 0, "z": 3},
+                    {"name": "B", "type": "fork", "x": 2, "y": 0, "z": 3},
+                    {"name": "R", "type": "fork", "x": 1, "y": 1, "z": 1.5}
+                ],
+                "edges": [
+                    {"begin": "A", "end": "L1", "cost": 1, "reverse_cost": 1},
+                    {"begin": "L1", "end": "L2", "cost": 3, "reverse_cost": 3},
+                    {"begin": "L2", "end": "B", "cost": 1, "reverse_cost": 1},
+                    {"begin": "A", "end": "R", "cost": 3.5, "reverse_cost": 3.5},
+                    {"begin": "R", "end": "B", "cost": 3.5, "reverse_cost": 3.5}
+                ]
+            }"#,
+        )
+        .unwrap();
+        let (nodes, edges) = layout.to_graph().unwrap();
+        let mut map = TrackMap::new(nodes, edges);
+
+        assert_eq!(map.shaft(2), Some(2));
+        assert_eq!(map.shaft(3), Some(2));
+        assert_eq!(map.shaft(1), None);
+        let ride = map.link(2, 3).unwrap().cost;
+        assert_eq!(ride, 3.0 * constant::VEHICLE_SPEED / constant::LIFT_SPEED);
+        assert_eq!(names(&map.find_path(1, 4).unwrap()), ["A", "R", "B"]);
+
+        map.set_maintenance(Maintenance {
+            resource: Resource::Node(5),
+            reason: "ramp repair".to_string(),
+            operator: "ops".to_string(),
+            disabled_at: Utc::now(),
+        });
+        assert_eq!(names(&map.find_path(1, 4).unwrap()), ["A", "L1", "L2", "B"]);
+    }
+
+    #[test]
+    fn find_free_station() {
+        let mut map = sample();
+        let path = map.find_free_station(6, NodeType::ParkingStation).unwrap();
+        assert_eq!(names(&path), ["S3", "A5", "A6", "P2"]);
+
+        // P1 is further along the one-way loop but the only free slot left.
+        map.set_lock(NodeLock::new(2, 2000));
+        assert!(map.is_full(2));
+        let path = map.find_free_station(6, NodeType::ParkingStation).unwrap();
+        assert_eq!(names(&path), ["S3", "A5", "A6", "A2", "A1", "P1"]);
+
+        map.set_lock(NodeLock::new(1, 2500));
+        assert!(map.find_free_station(6, NodeType::ParkingStation).is_none());
+    }
+
+    #[test]
+    fn multi_slot_station() {
+        let (mut nodes, edges) = sample_layout();
+        nodes[1].capacity = 2;
+        let mut map = TrackMap::new(nodes, edges);
+
+        map.set_lock(NodeLock::new(2, 2000));
+        assert!(!map.is_full(2));
+        let path = map.find_free_station(6, NodeType::ParkingStation).unwrap();
+        assert_eq!(names(&path).last(), Some(&"P2"));
+
+        map.set_lock(NodeLock::new(2, 2500));
+        assert!(map.is_full(2));
+        assert_eq!(map.occupants(2).len(), 2);
+        let path = map.find_free_station(6, NodeType::ParkingStation).unwrap();
+        assert_eq!(names(&path).last(), Some(&"P1"));
+
+        map.remove_lock(2, 2000);
+        assert_eq!(map.occupants(2), [2500]);
+        assert!(!map.is_full(2));
+    }
+}
@@ -0,0 +1,806 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    ops::Deref,
+    sync::Arc,
+};
+
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::{sync::RwLock, time};
+use tracing::{error, info, warn};
+
+use crate::{constant, transport::prelude::Position};
+
+pub use layout::Layout;
+use map::TrackMap;
+pub use render::{Format, VehicleOverlay};
+use reservation::Reservations;
+pub use reservation::{Reservation, Resource};
+#[cfg(test)]
+pub use store::MemoryStore;
+pub use store::{PgStore, TrackStore};
+#[cfg(test)]
+pub(crate) use store::{offline_sample, sample_layout};
+pub use validate::Report;
+
+pub mod layout;
+mod map;
+mod render;
+mod reservation;
+mod store;
+mod validate;
+
+#[derive(Debug)]
+pub enum Error {
+    UnknownNode(String),
+    UnknownEdge(i32),
+    NoRoute { from: String, to: String },
+    Db(sqlx::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::UnknownNode(name) => write!(f, "unknown node {}", name),
+            Error::UnknownEdge(id) => write!(f, "unknown edge {}", id),
+            Error::NoRoute { from, to } => write!(f, "no route from {} to {}", from, to),
+            Error::Db(e) => write!(f, "database error: {}", e),
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, sqlx::Type, Serialize, Deserialize)]
+#[sqlx(type_name = "nodetype")]
+#[serde(rename_all = "snake_case")]
+pub enum NodeType {
+    #[sqlx(rename = "shipping_dock")]
+    ShippingDock,
+    #[sqlx(rename = "parking_station")]
+    ParkingStation,
+    #[sqlx(rename = "charging_station")]
+    ChargingStation,
+    #[sqlx(rename = "item_stocker")]
+    ItemStocker,
+    #[sqlx(rename = "fluid_stocker")]
+    FluidStocker,
+    #[sqlx(rename = "fork")]
+    Fork,
+    /// A floor stop of a lift, lift nodes joined by edges form one shaft.
+    #[sqlx(rename = "lift")]
+    Lift,
+}
+
+/// Side of the vehicle facing the inventory, named like the vehicle's `sides` table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, sqlx::Type, Serialize, Deserialize)]
+#[sqlx(type_name = "side", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum Side {
+    NegY,
+    PosY,
+    NegZ,
+    PosZ,
+    NegX,
+    PosX,
+}
+
+/// Inventory slots worked on, both ends included.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Slots {
+    pub first: i32,
+    pub last: i32,
+}
+
+/// How a vehicle works with the inventory at a node. Unset fields are left to the vehicle.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Interaction {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub side: Option<Side>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slots: Option<Slots>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Node {
+    pub id: i32,
+    pub name: String,
+    pub node_type: NodeType,
+    pub position: Position,
+    /// Vehicles the node can hold at once, stations may have several slots.
+    pub capacity: i32,
+    pub interaction: Interaction,
+    pub comment: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Edge {
+    pub id: i32,
+    pub begin_node_id: i32,
+    pub end_node_id: i32,
+    pub cost: f64,
+    pub reverse_cost: f64,
+}
+
+/// A node held by a vehicle until the lease expires or the owner releases it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NodeLock {
+    pub node_id: i32,
+    pub vehicle_id: i32,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl NodeLock {
+    fn new(node_id: i32, vehicle_id: i32) -> Self {
+        Self {
+            node_id,
+            vehicle_id,
+            expires_at: Utc::now() + lease(),
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at <= Utc::now()
+    }
+}
+
+/// A node or edge taken out of service, routing avoids it until it is enabled again.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Maintenance {
+    pub resource: Resource,
+    pub reason: String,
+    pub operator: String,
+    pub disabled_at: DateTime<Utc>,
+}
+
+fn lease() -> TimeDelta {
+    TimeDelta::seconds(constant::TRACK_LOCK_LEASE_TIME)
+}
+
+/// A position projected onto the nearest edge.
+#[derive(Debug, Clone)]
+pub struct Snap {
+    /// Distance along the edge from its begin node.
+    pub offset: f64,
+    pub length: f64,
+    /// Nodes the vehicle can drive to along the edge, nearest first.
+    pub entries: Vec<Arc<Node>>,
+    begin_node_id: i32,
+}
+
+impl Snap {
+    pub fn distance_to(&self, node: &Node) -> f64 {
+        if node.id == self.begin_node_id {
+            self.offset
+        } else {
+            self.length - self.offset
+        }
+    }
+
+    pub fn travel_time_to(&self, node: &Node) -> TimeDelta {
+        reservation::travel_time(self.distance_to(node))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Path(Vec<Arc<Node>>);
+
+impl Deref for Path {
+    type Target = [Arc<Node>];
+    fn deref(&self) -> &Self::Target {
+        self.0.as_slice()
+    }
+}
+
+#[derive(Debug)]
+pub struct Graph {
+    store: Arc<dyn TrackStore>,
+    map: Arc<RwLock<TrackMap>>,
+    reservations: RwLock<Reservations>,
+    /// Forks and lift shafts to the vehicle inside, only one vehicle is in a zone at a time.
+    zones: RwLock<HashMap<i32, i32>>,
+}
+
+impl Graph {
+    pub async fn new(store: impl TrackStore + 'static) -> Result<Self> {
+        let store: Arc<dyn TrackStore> = Arc::new(store);
+        store.listen().await.map_err(Error::Db)?;
+        // Locks survive a restart, their owners either come back and renew or the lease runs out.
+        for lock in store.reap_locks().await.map_err(Error::Db)? {
+            info!(
+                "node {} lock of vehicle {} expired.",
+                lock.node_id, lock.vehicle_id
+            );
+        }
+        let map = Arc::new(RwLock::new(Self::load(&*store).await.map_err(Error::Db)?));
+        Self::watch(store.clone(), map.clone());
+        Self::reap(store.clone(), map.clone());
+        Ok(Self {
+            store,
+            map,
+            reservations: RwLock::new(Reservations::default()),
+            zones: RwLock::new(HashMap::new()),
+        })
+    }
+
+    async fn load(store: &dyn TrackStore) -> sqlx::Result<TrackMap> {
+        let (nodes, edges) = store.load().await?;
+        let report = validate::validate(&nodes, &edges);
+        if !report.is_ok() {
+            warn!("track graph validation failed: {:#?}.", report);
+        }
+        let mut map = TrackMap::new(nodes, edges);
+        for lock in store.locks().await? {
+            map.set_lock(lock);
+        }
+        for maintenance in store.maintenance().await? {
+            map.set_maintenance(maintenance);
+        }
+        Ok(map)
+    }
+
+    fn watch(store: Arc<dyn TrackStore>, map: Arc<RwLock<TrackMap>>) {
+        tokio::spawn(async move {
+            loop {
+                if let Err(e) = store.changed().await {
+                    error!("track graph watch error: {:?}.", e);
+                    time::sleep(time::Duration::from_secs(5)).await;
+                }
+                info!("track graph changed, reloading.");
+                match Self::load(&*store).await {
+                    Ok(new_map) => *map.write().await = new_map,
+                    Err(e) => error!("track graph reload error: {:?}.", e),
+                }
+            }
+        });
+    }
+
+    fn reap(store: Arc<dyn TrackStore>, map: Arc<RwLock<TrackMap>>) {
+        tokio::spawn(async move {
+            let mut interval =
+                time::interval(time::Duration::from_secs(constant::TRACK_LOCK_REAP_TIME));
+            loop {
+                interval.tick().await;
+                match store.reap_locks().await {
+                    Ok(reaped) => {
+                        let mut map = map.write().await;
+                        for lock in reaped {
+                            warn!(
+                                "node {} lock of vehicle {} expired.",
+                                lock.node_id, lock.vehicle_id
+                            );
+                            map.remove_lock(lock.node_id, lock.vehicle_id);
+                        }
+                    }
+                    Err(e) => error!("track lock reap error: {:?}.", e),
+                }
+            }
+        });
+    }
+
+    pub async fn import(&self, layout: &Layout) -> layout::Result<()> {
+        layout.to_graph()?;
+        self.store
+            .replace(layout)
+            .await
+            .map_err(layout::Error::Db)?;
+        let new_map = Self::load(&*self.store).await.map_err(layout::Error::Db)?;
+        *self.map.write().await = new_map;
+        // Edge ids are renumbered, every vehicle reserves its route again as it moves on.
+        self.reservations.write().await.clear();
+        self.zones.write().await.clear();
+        Ok(())
+    }
+
+    pub async fn validate(&self) -> Result<Report> {
+        let (nodes, edges) = self.store.load().await.map_err(Error::Db)?;
+        Ok(validate::validate(&nodes, &edges))
+    }
+
+    pub fn validate_layout(layout: &Layout) -> layout::Result<Report> {
+        let (nodes, edges) = layout.to_graph()?;
+        Ok(validate::validate(&nodes, &edges))
+    }
+
+    pub async fn export(&self) -> Result<Layout> {
+        let (nodes, edges) = self.store.load().await.map_err(Error::Db)?;
+        Ok(Layout::from_graph(&nodes, &edges))
+    }
+
+    pub async fn snap(&self, position: &Position) -> Option<Snap> {
+        self.map.read().await.snap(position)
+    }
+
+    /// Returns false when other vehicles hold unexpired locks on every slot of the node.
+    pub async fn lock_node(&self, node_id: i32, vehicle_id: i32) -> Result<bool> {
+        let lock = NodeLock::new(node_id, vehicle_id);
+        if !self.store.acquire_lock(&lock).await.map_err(Error::Db)? {
+            return Ok(false);
+        }
+        self.map.write().await.set_lock(lock);
+        Ok(true)
+    }
+
+    /// Returns false when the node is not locked by `vehicle_id`.
+    pub async fn unlock_node(&self, node_id: i32, vehicle_id: i32) -> Result<bool> {
+        if !self
+            .store
+            .release_lock(node_id, vehicle_id)
+            .await
+            .map_err(Error::Db)?
+        {
+            return Ok(false);
+        }
+        self.map.write().await.remove_lock(node_id, vehicle_id);
+        Ok(true)
+    }
+
+    pub async fn renew_locks(&self, vehicle_id: i32) -> Result<()> {
+        if !self.map.read().await.has_locks(vehicle_id) {
+            return Ok(());
+        }
+        let expires_at = Utc::now() + lease();
+        self.store
+            .renew_locks(vehicle_id, expires_at)
+            .await
+            .map_err(Error::Db)?;
+        self.map.write().await.renew_locks(vehicle_id, expires_at);
+        Ok(())
+    }
+
+    pub async fn release_locks(&self, vehicle_id: i32) -> Result<Vec<NodeLock>> {
+        if !self.map.read().await.has_locks(vehicle_id) {
+            return Ok(Vec::new());
+        }
+        let released = self
+            .store
+            .release_locks(vehicle_id)
+            .await
+            .map_err(Error::Db)?;
+        let mut map = self.map.write().await;
+        for lock in &released {
+            map.remove_lock(lock.node_id, lock.vehicle_id);
+        }
+        Ok(released)
+    }
+
+    pub async fn locks(&self) -> Vec<NodeLock> {
+        self.map.read().await.locks()
+    }
+
+    /// Takes the node or edge out of service. Vehicles already routed across it are not touched
+    /// here, see `ScheduleExec::disable`.
+    pub async fn disable(
+        &self,
+        resource: Resource,
+        reason: &str,
+        operator: &str,
+    ) -> Result<Maintenance> {
+        if !self.map.read().await.contains(resource) {
+            return Err(match resource {
+                Resource::Node(id) => Error::UnknownNode(id.to_string()),
+                Resource::Edge(id) => Error::UnknownEdge(id),
+            });
+        }
+        let maintenance = Maintenance {
+            resource,
+            reason: reason.to_string(),
+            operator: operator.to_string(),
+            disabled_at: Utc::now(),
+        };
+        self.store.disable(&maintenance).await.map_err(Error::Db)?;
+        self.map.write().await.set_maintenance(maintenance.clone());
+        Ok(maintenance)
+    }
+
+    /// Returns false when the node or edge was not disabled.
+    pub async fn enable(&self, resource: Resource) -> Result<bool> {
+        if !self.store.enable(resource).await.map_err(Error::Db)? {
+            return Ok(false);
+        }
+        self.map.write().await.remove_maintenance(resource);
+        Ok(true)
+    }
+
+    pub async fn maintenance(&self) -> Vec<Maintenance> {
+        self.map.read().await.maintenance()
+    }
+
+    /// The route enters a disabled node or follows a disabled edge.
+    pub async fn crosses_disabled(&self, route: &[Arc<Node>]) -> bool {
+        self.map.read().await.crosses_disabled(route)
+    }
+
+    pub async fn node(&self, name: &str) -> Result<Arc<Node>> {
+        node_by_name(&*self.map.read().await, name)
+    }
+
+    #[cfg(test)]
+    pub async fn find_path(&self, begin_node_name: &str, end_node_name: &str) -> Result<Path> {
+        let map = self.map.read().await;
+        let begin = node_by_name(&map, begin_node_name)?;
+        let end = node_by_name(&map, end_node_name)?;
+        map.find_path(begin.id, end.id)
+            .map(Path)
+            .ok_or_else(|| no_route(begin_node_name, end_node_name))
+    }
+
+    /// Fails when no track leads from one node to the other, even with every node and edge in
+    /// service and empty.
+    pub async fn check_connected(&self, begin_node_name: &str, end_node_name: &str) -> Result<()> {
+        let map = self.map.read().await;
+        let begin = node_by_name(&map, begin_node_name)?;
+        let end = node_by_name(&map, end_node_name)?;
+        if !map.connected(begin.id, end.id) {
+            return Err(no_route(begin_node_name, end_node_name));
+        }
+        Ok(())
+    }
+
+    /// Fails when no parking or charging station, where vehicles start from, has track leading
+    /// to the node.
+    pub async fn check_reachable(&self, node_name: &str) -> Result<()> {
+        let map = self.map.read().await;
+        let end = node_by_name(&map, node_name)?;
+        let reachable = map.nodes().into_iter().any(|node| {
+            matches!(
+                node.node_type,
+                NodeType::ParkingStation | NodeType::ChargingStation
+            ) && map.connected(node.id, end.id)
+        });
+        if !reachable {
+            return Err(no_route("any parking or charging station", node_name));
+        }
+        Ok(())
+    }
+
+    /// Avoids what other vehicles reserved, falls back to ignoring reservations when every route
+    /// is taken so the vehicle gets held on the track instead.
+    pub async fn find_timed_path(
+        &self,
+        vehicle_id: i32,
+        begin_node_name: &str,
+        end_node_name: &str,
+        depart: DateTime<Utc>,
+    ) -> Result<Path> {
+        let map = self.map.read().await;
+        let begin = node_by_name(&map, begin_node_name)?;
+        let end = node_by_name(&map, end_node_name)?;
+        let mut reservations = self.reservations.write().await;
+        reservations.prune(Utc::now());
+        map.find_timed_path(begin.id, end.id, vehicle_id, depart, &reservations)
+            .or_else(|| {
+                map.find_timed_path(begin.id, end.id, vehicle_id, depart, &Default::default())
+            })
+            .map(Path)
+            .ok_or_else(|| no_route(begin_node_name, end_node_name))
+    }
+
+    pub async fn travel_time(&self, path: &Path) -> TimeDelta {
+        let map = self.map.read().await;
+        path.windows(2)
+            .filter_map(|pair| map.link(pair[0].id, pair[1].id))
+            .map(|link| reservation::travel_time(link.cost))
+            .sum()
+    }
+
+    /// Replaces the vehicle's reservations with windows for `route`, each stop paired with the
+    /// time spent working there. The vehicle keeps its place in the queue until released.
+    pub async fn reserve(
+        &self,
+        vehicle_id: i32,
+        route: &[(Arc<Node>, TimeDelta)],
+        depart: DateTime<Utc>,
+    ) {
+        let plan = Reservations::plan(&*self.map.read().await, vehicle_id, route, depart);
+        let mut reservations = self.reservations.write().await;
+        reservations.prune(Utc::now());
+        reservations.replace(vehicle_id, plan);
+    }
+
+    pub async fn release_reservations(&self, vehicle_id: i32) {
+        self.reservations.write().await.release(vehicle_id);
+    }
+
+    /// Takes the zone the node lies in for the vehicle. Returns the vehicle inside when the zone
+    /// is taken, nodes outside of any zone are always free.
+    pub async fn acquire_zone(&self, vehicle_id: i32, node_id: i32) -> Option<i32> {
+        let zone = self.map.read().await.zone(node_id)?;
+        let mut zones = self.zones.write().await;
+        match zones.get(&zone) {
+            Some(holder) if *holder != vehicle_id => Some(*holder),
+            _ => {
+                zones.insert(zone, vehicle_id);
+                None
+            }
+        }
+    }
+
+    /// Frees every zone the vehicle holds except the one `keep` lies in.
+    pub async fn release_zones(&self, vehicle_id: i32, keep: Option<i32>) {
+        let keep = match keep {
+            Some(node_id) => self.map.read().await.zone(node_id),
+            None => None,
+        };
+        self.zones
+            .write()
+            .await
+            .retain(|zone, holder| *holder != vehicle_id || Some(*zone) == keep);
+    }
+
+    /// Zones to the vehicle holding them, sorted by zone.
+    #[cfg(test)]
+    pub async fn zones(&self) -> Vec<(i32, i32)> {
+        let mut zones: Vec<(i32, i32)> = self
+            .zones
+            .read()
+            .await
+            .iter()
+            .map(|(zone, holder)| (*zone, *holder))
+            .collect();
+        zones.sort();
+        zones
+    }
+
+    /// The vehicle this one has to wait for before leaving `from_id` for `to_id`: an occupant of
+    /// a full `to_id`, the holder of its zone, or an older reservation of the edge or the next fork.
+    pub async fn blocker(&self, vehicle_id: i32, from_id: i32, to_id: i32) -> Option<i32> {
+        let map = self.map.read().await;
+        let occupants = map.occupants(to_id);
+        if map.is_full(to_id) && !occupants.contains(&vehicle_id) {
+            return occupants.into_iter().min();
+        }
+        if let Some(zone) = map.zone(to_id)
+            && let Some(holder) = self.zones.read().await.get(&zone)
+            && *holder != vehicle_id
+        {
+            return Some(*holder);
+        }
+        let link = map.link(from_id, to_id)?;
+        let now = Utc::now();
+        let arrive = now + reservation::travel_time(link.cost);
+        let margin = reservation::margin();
+        let reservations = self.reservations.read().await;
+        reservations
+            .yields_to(vehicle_id, Resource::Edge(link.edge_id), now, arrive)
+            .or_else(|| {
+                map.node(to_id)
+                    .filter(|node| node.node_type == NodeType::Fork)
+                    .and_then(|_| {
+                        reservations.yields_to(
+                            vehicle_id,
+                            Resource::Node(to_id),
+                            arrive - margin,
+                            arrive + margin,
+                        )
+                    })
+            })
+    }
+
+    pub async fn reservations(&self) -> Vec<Reservation> {
+        let mut reservations = self.reservations.write().await;
+        reservations.prune(Utc::now());
+        reservations.list()
+    }
+
+    /// Draws the track with locked nodes, reserved edges and disabled track highlighted, and
+    /// the given vehicles on top.
+    pub async fn render(&self, format: Format, vehicles: &[VehicleOverlay]) -> String {
+        let reserved: HashSet<i32> = self
+            .reservations()
+            .await
+            .into_iter()
+            .filter_map(|reservation| match reservation.resource {
+                Resource::Edge(id) => Some(id),
+                Resource::Node(_) => None,
+            })
+            .collect();
+        let map = self.map.read().await;
+        render::Scene::new(&map, reserved, vehicles).render(format)
+    }
+
+    /// Route to the station of the type with a free slot that is cheapest to reach.
+    pub async fn find_path_by_type(
+        &self,
+        begin_node_name: &str,
+        node_type: &NodeType,
+    ) -> Result<Path> {
+        let map = self.map.read().await;
+        let begin = node_by_name(&map, begin_node_name)?;
+        map.find_free_station(begin.id, *node_type)
+            .map(Path)
+            .ok_or_else(|| no_route(begin_node_name, &format!("{:?}", node_type)))
+    }
+
+    pub async fn find_parking_path(&self, from_node_name: &str) -> Result<Path> {
+        self.find_path_by_type(from_node_name, &NodeType::ParkingStation)
+            .await
+    }
+
+    /// Path to a vehicle's own parking spot, as long as nobody else is parked there.
+    pub async fn find_home_path(&self, from_node_name: &str, home_node_name: &str) -> Result<Path> {
+        let map = self.map.read().await;
+        let begin = node_by_name(&map, from_node_name)?;
+        let home = node_by_name(&map, home_node_name)?;
+        if map.is_full(home.id) || map.is_disabled(Resource::Node(home.id)) {
+            return Err(no_route(from_node_name, home_node_name));
+        }
+        map.find_path(begin.id, home.id)
+            .map(Path)
+            .ok_or_else(|| no_route(from_node_name, home_node_name))
+    }
+
+    pub async fn find_charging_path(&self, from_node_name: &str) -> Result<Path> {
+        self.find_path_by_type(from_node_name, &NodeType::ChargingStation)
+            .await
+    }
+
+    pub async fn find_shipping_dock_path(&self, from_node_name: &str) -> Result<Path> {
+        self.find_path_by_type(from_node_name, &NodeType::ShippingDock)
+            .await
+    }
+}
+
+fn node_by_name(map: &TrackMap, name: &str) -> Result<Arc<Node>> {
+    map.node_by_name(name)
+        .cloned()
+        .ok_or_else(|| Error::UnknownNode(name.to_string()))
+}
+
+fn no_route(from: &str, to: &str) -> Error {
+    Error::NoRoute {
+        from: from.to_string(),
+        to: to.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn get_track_graph() -> Graph {
+        let (nodes, edges) = sample_layout();
+        Graph::new(MemoryStore::new(nodes, edges)).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn find_path() {
+        let track_graph = get_track_graph().await;
+        let path = track_graph.find_path("S2", "S1").await.unwrap();
+
+        assert_eq!(path.0.first().unwrap().name, "S2");
+        assert_eq!(path.0.get(1).unwrap().name, "A6");
+        assert_eq!(path.0.get(2).unwrap().name, "A2");
+        assert_eq!(path.0.get(3).unwrap().name, "A1");
+        assert_eq!(path.0.get(4).unwrap().name, "A4");
+        assert_eq!(path.0.get(5).unwrap().name, "A3");
+        assert_eq!(path.0.get(6).unwrap().name, "S1");
+
+        let path = track_graph
+            .find_path_by_type("S3", &NodeType::ParkingStation)
+            .await
+            .unwrap();
+        assert_eq!(path.0.first().unwrap().name, "S3");
+        assert_eq!(path.0.get(1).unwrap().name, "A5");
+        assert_eq!(path.0.get(2).unwrap().name, "A6");
+        assert_eq!(path.0.get(3).unwrap().name, "P2");
+    }
+
+    #[tokio::test]
+    async fn node_lock_owner() {
+        let track_graph = get_track_graph().await;
+        assert!(track_graph.lock_node(2, 2000).await.unwrap());
+        assert!(track_graph.lock_node(2, 2000).await.unwrap());
+        assert!(!track_graph.lock_node(2, 2500).await.unwrap());
+        assert!(!track_graph.unlock_node(2, 2500).await.unwrap());
+
+        let path = track_graph
+            .find_path_by_type("S3", &NodeType::ParkingStation)
+            .await
+            .unwrap();
+        assert_eq!(path.last().unwrap().name, "P1");
+
+        assert!(track_graph.unlock_node(2, 2000).await.unwrap());
+        assert!(track_graph.lock_node(2, 2500).await.unwrap());
+        assert!(track_graph.lock_node(1, 2500).await.unwrap());
+        let released = track_graph.release_locks(2500).await.unwrap();
+        assert_eq!(released.len(), 2);
+        assert!(track_graph.locks().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn route_errors() {
+        let (mut nodes, edges) = sample_layout();
+        nodes.push(Node {
+            id: 13,
+            name: "S4".to_string(),
+            node_type: NodeType::ItemStocker,
+            position: Position(5.0, 5.0, 0.0),
+            capacity: 1,
+            interaction: Interaction::default(),
+            comment: None,
+        });
+        let track_graph = Graph::new(MemoryStore::new(nodes, edges)).await.unwrap();
+
+        let e = track_graph.find_path("S2", "S9").await.unwrap_err();
+        assert!(matches!(e, Error::UnknownNode(ref name) if name == "S9"));
+        let e = track_graph
+            .find_timed_path(2000, "S2", "S4", Utc::now())
+            .await
+            .unwrap_err();
+        assert_eq!(e.to_string(), "no route from S2 to S4");
+        assert!(track_graph.find_parking_path("S4").await.is_err());
+
+        let path = track_graph.find_path("S2", "S2").await.unwrap();
+        assert_eq!(path.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn multi_slot_lock() {
+        let (mut nodes, edges) = sample_layout();
+        nodes[1].capacity = 2;
+        let track_graph = Graph::new(MemoryStore::new(nodes, edges)).await.unwrap();
+        assert!(track_graph.lock_node(2, 2000).await.unwrap());
+        assert!(track_graph.lock_node(2, 2500).await.unwrap());
+        assert!(!track_graph.lock_node(2, 3000).await.unwrap());
+        assert_eq!(track_graph.blocker(3000, 12, 2).await, Some(2000));
+        assert_eq!(track_graph.blocker(2500, 12, 2).await, None);
+
+        let path = track_graph.find_parking_path("S3").await.unwrap();
+        assert_eq!(path.last().unwrap().name, "P1");
+
+        assert!(track_graph.unlock_node(2, 2000).await.unwrap());
+        assert!(track_graph.lock_node(2, 3000).await.unwrap());
+        assert_eq!(track_graph.locks().await.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn import_layout() {
+        let track_graph = get_track_graph().await;
+        let layout = serde_json::from_str::<Layout>(
+            r#"{
+                "version": 1,
+                "nodes": [
+                    {"name": "P1", "type": "parking_station", "x": 0, "y": 0, "z": 0},
+                    {"name": "A1", "type": "fork", "x": 0, "y": 1, "z": 0},
+                    {"name": "S1", "type": "item_stocker", "x": 0, "y": 2, "z": 0},
+                    {"name": "S3", "type": "item_stocker", "x": 1, "y": 1, "z": 0}
+                ],
+                "edges": [
+                    {"begin": "P1", "end": "A1", "cost": 1, "reverse_cost": 1},
+                    {"begin": "A1", "end": "S1", "cost": 1, "reverse_cost": -1},
+                    {"begin": "S3", "end": "A1", "cost": 1, "reverse_cost": -1}
+                ]
+            }"#,
+        )
+        .unwrap();
+        track_graph.import(&layout).await.unwrap();
+
+        let path = track_graph.find_path("P1", "S1").await.unwrap();
+        assert_eq!(path.len(), 3);
+        assert!(matches!(
+            track_graph.find_path("S1", "P1").await,
+            Err(Error::NoRoute { .. })
+        ));
+        assert!(matches!(
+            track_graph.find_path("S2", "S1").await,
+            Err(Error::UnknownNode(_))
+        ));
+        // One way only, whatever is in service.
+        track_graph.check_connected("P1", "S1").await.unwrap();
+        assert!(matches!(
+            track_graph.check_connected("S1", "P1").await,
+            Err(Error::NoRoute { .. })
+        ));
+        track_graph.check_reachable("S1").await.unwrap();
+        track_graph.check_reachable("P1").await.unwrap();
+        assert!(matches!(
+            track_graph.check_reachable("S3").await,
+            Err(Error::NoRoute { .. })
+        ));
+        assert_eq!(track_graph.export().await.unwrap(), layout);
+    }
+}
@@ -0,0 +1,372 @@
+use std::{collections::HashSet, fmt::Write, sync::Arc};
+
+use serde::Deserialize;
+
+use crate::transport::track::{Node, NodeType, Resource, map::TrackMap};
+
+const SCALE: f64 = 80.0;
+const MARGIN: f64 = 40.0;
+const NODE_RADIUS: f64 = 10.0;
+const LOCKED_COLOR: &str = "#d62728";
+const RESERVED_COLOR: &str = "#ff7f0e";
+const DISABLED_COLOR: &str = "#7f7f7f";
+const EDGE_COLOR: &str = "#444444";
+const VEHICLE_COLORS: [&str; 6] = [
+    "#1f77b4", "#9467bd", "#17becf", "#e377c2", "#bcbd22", "#8c564b",
+];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Format {
+    #[default]
+    Svg,
+    Dot,
+}
+
+/// Where a vehicle is and the nodes it still has to visit.
+#[derive(Debug, Clone)]
+pub struct VehicleOverlay {
+    pub vehicle_id: i32,
+    pub node: Option<Arc<Node>>,
+    pub route: Vec<Arc<Node>>,
+}
+
+pub struct Scene<'a> {
+    map: &'a TrackMap,
+    reserved: HashSet<i32>,
+    vehicles: &'a [VehicleOverlay],
+}
+
+fn color(node_type: NodeType) -> &'static str {
+    match node_type {
+        NodeType::ShippingDock => "#8dd3c7",
+        NodeType::ParkingStation => "#80b1d3",
+        NodeType::ChargingStation => "#b3de69",
+        NodeType::ItemStocker => "#fdb462",
+        NodeType::FluidStocker => "#bebada",
+        NodeType::Fork => "#d9d9d9",
+        NodeType::Lift => "#fccde5",
+    }
+}
+
+fn vehicle_color(index: usize) -> &'static str {
+    VEHICLE_COLORS[index % VEHICLE_COLORS.len()]
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+impl<'a> Scene<'a> {
+    /// `reserved` holds the ids of edges some vehicle reserved.
+    pub fn new(map: &'a TrackMap, reserved: HashSet<i32>, vehicles: &'a [VehicleOverlay]) -> Self {
+        Self {
+            map,
+            reserved,
+            vehicles,
+        }
+    }
+
+    pub fn render(&self, format: Format) -> String {
+        match format {
+            Format::Svg => self.svg(),
+            Format::Dot => self.dot(),
+        }
+    }
+
+    fn edge_color(&self, edge_id: i32) -> &'static str {
+        if self.map.is_disabled(Resource::Edge(edge_id)) {
+            DISABLED_COLOR
+        } else if self.reserved.contains(&edge_id) {
+            RESERVED_COLOR
+        } else {
+            EDGE_COLOR
+        }
+    }
+
+    pub fn dot(&self) -> String {
+        let mut dot = String::from("digraph track {\n    node [shape=circle, style=filled];\n");
+        for node in self.map.nodes() {
+            let mut attributes = format!(
+                "pos=\"{},{}!\", fillcolor=\"{}\"",
+                node.position.0,
+                node.position.1,
+                color(node.node_type)
+            );
+            if !self.map.occupants(node.id).is_empty() {
+                write!(attributes, ", color=\"{}\", penwidth=3", LOCKED_COLOR).unwrap();
+            }
+            if self.map.is_disabled(Resource::Node(node.id)) {
+                attributes.push_str(", style=\"filled,dashed\"");
+            }
+            writeln!(
+                dot,
+                "    \"{}\" [{}];",
+                node.name.replace('"', "\\\""),
+                attributes
+            )
+            .unwrap();
+        }
+        for edge in self.map.edges() {
+            let (Some(begin), Some(end)) = (
+                self.map.node(edge.begin_node_id),
+                self.map.node(edge.end_node_id),
+            ) else {
+                continue;
+            };
+            let mut attributes = format!("color=\"{}\"", self.edge_color(edge.id));
+            if edge.reverse_cost >= 0.0 {
+                attributes.push_str(", dir=both");
+            }
+            if self.reserved.contains(&edge.id) {
+                attributes.push_str(", penwidth=3");
+            }
+            if self.map.is_disabled(Resource::Edge(edge.id)) {
+                attributes.push_str(", style=dashed");
+            }
+            writeln!(
+                dot,
+                "    \"{}\" -> \"{}\" [{}];",
+                begin.name.replace('"', "\\\""),
+                end.name.replace('"', "\\\""),
+                attributes
+            )
+            .unwrap();
+        }
+        for (index, vehicle) in self.vehicles.iter().enumerate() {
+            let color = vehicle_color(index);
+            writeln!(
+                dot,
+                "    \"vehicle {}\" [shape=box, fillcolor=\"{}\"];",
+                vehicle.vehicle_id, color
+            )
+            .unwrap();
+            if let Some(node) = &vehicle.node {
+                writeln!(
+                    dot,
+                    "    \"vehicle {}\" -> \"{}\" [color=\"{}\", style=dotted, arrowhead=none];",
+                    vehicle.vehicle_id,
+                    node.name.replace('"', "\\\""),
+                    color
+                )
+                .unwrap();
+            }
+            for pair in vehicle.route.windows(2) {
+                writeln!(
+                    dot,
+                    "    \"{}\" -> \"{}\" [color=\"{}\", penwidth=2, constraint=false];",
+                    pair[0].name.replace('"', "\\\""),
+                    pair[1].name.replace('"', "\\\""),
+                    color
+                )
+                .unwrap();
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    pub fn svg(&self) -> String {
+        let nodes = self.map.nodes();
+        let (mut min_x, mut max_x, mut min_y, mut max_y) = (0.0_f64, 0.0_f64, 0.0_f64, 0.0_f64);
+        for (index, node) in nodes.iter().enumerate() {
+            let (x, y) = (node.position.0, node.position.1);
+            if index == 0 {
+                (min_x, max_x, min_y, max_y) = (x, x, y, y);
+            }
+            min_x = min_x.min(x);
+            max_x = max_x.max(x);
+            min_y = min_y.min(y);
+            max_y = max_y.max(y);
+        }
+        // Track y grows upwards, svg y downwards.
+        let point = |node: &Node| {
+            (
+                MARGIN + (node.position.0 - min_x) * SCALE,
+                MARGIN + (max_y - node.position.1) * SCALE,
+            )
+        };
+        let width = 2.0 * MARGIN + (max_x - min_x) * SCALE;
+        let height = 2.0 * MARGIN + (max_y - min_y) * SCALE;
+
+        let mut svg = String::new();
+        writeln!(
+            svg,
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" viewBox=\"0 0 {width} {height}\" font-family=\"sans-serif\" font-size=\"10\">"
+        )
+        .unwrap();
+        svg.push_str(
+            "<defs><marker id=\"arrow\" viewBox=\"0 0 10 10\" refX=\"20\" refY=\"5\" markerWidth=\"6\" markerHeight=\"6\" orient=\"auto-start-reverse\"><path d=\"M 0 0 L 10 5 L 0 10 z\"/></marker></defs>\n",
+        );
+
+        for edge in self.map.edges() {
+            let (Some(begin), Some(end)) = (
+                self.map.node(edge.begin_node_id),
+                self.map.node(edge.end_node_id),
+            ) else {
+                continue;
+            };
+            let ((x1, y1), (x2, y2)) = (point(begin), point(end));
+            let mut attributes = format!(
+                "stroke=\"{}\" stroke-width=\"{}\"",
+                self.edge_color(edge.id),
+                if self.reserved.contains(&edge.id) {
+                    4
+                } else {
+                    2
+                }
+            );
+            if edge.reverse_cost < 0.0 {
+                attributes.push_str(" marker-end=\"url(#arrow)\"");
+            }
+            if self.map.is_disabled(Resource::Edge(edge.id)) {
+                attributes.push_str(" stroke-dasharray=\"6 4\"");
+            }
+            writeln!(
+                svg,
+                "<line x1=\"{x1}\" y1=\"{y1}\" x2=\"{x2}\" y2=\"{y2}\" {attributes}><title>edge {} {} - {}</title></line>",
+                edge.id,
+                escape(&begin.name),
+                escape(&end.name)
+            )
+            .unwrap();
+        }
+
+        for (index, vehicle) in self.vehicles.iter().enumerate() {
+            if vehicle.route.len() < 2 {
+                continue;
+            }
+            let points: Vec<String> = vehicle
+                .route
+                .iter()
+                .map(|node| {
+                    let (x, y) = point(node);
+                    format!("{x},{y}")
+                })
+                .collect();
+            writeln!(
+                svg,
+                "<polyline points=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"6\" stroke-opacity=\"0.5\"><title>vehicle {} route</title></polyline>",
+                points.join(" "),
+                vehicle_color(index),
+                vehicle.vehicle_id
+            )
+            .unwrap();
+        }
+
+        for node in &nodes {
+            let (x, y) = point(node);
+            let mut attributes = format!("fill=\"{}\"", color(node.node_type));
+            let occupants = self.map.occupants(node.id);
+            if occupants.is_empty() {
+                attributes.push_str(" stroke=\"#000000\" stroke-width=\"1\"");
+            } else {
+                write!(
+                    attributes,
+                    " stroke=\"{}\" stroke-width=\"3\"",
+                    LOCKED_COLOR
+                )
+                .unwrap();
+            }
+            if self.map.is_disabled(Resource::Node(node.id)) {
+                attributes.push_str(" fill-opacity=\"0.4\" stroke-dasharray=\"3 2\"");
+            }
+            writeln!(
+                svg,
+                "<circle cx=\"{x}\" cy=\"{y}\" r=\"{NODE_RADIUS}\" {attributes}><title>{} ({:?}) locked by {:?}</title></circle>",
+                escape(&node.name),
+                node.node_type,
+                occupants
+            )
+            .unwrap();
+            writeln!(
+                svg,
+                "<text x=\"{x}\" y=\"{}\" text-anchor=\"middle\">{}</text>",
+                y + NODE_RADIUS + 12.0,
+                escape(&node.name)
+            )
+            .unwrap();
+        }
+
+        for (index, vehicle) in self.vehicles.iter().enumerate() {
+            let Some(node) = &vehicle.node else {
+                continue;
+            };
+            let (x, y) = point(node);
+            writeln!(
+                svg,
+                "<rect x=\"{}\" y=\"{}\" width=\"12\" height=\"12\" fill=\"{}\"><title>vehicle {}</title></rect>",
+                x + NODE_RADIUS,
+                y - NODE_RADIUS - 12.0,
+                vehicle_color(index),
+                vehicle.vehicle_id
+            )
+            .unwrap();
+            writeln!(
+                svg,
+                "<text x=\"{}\" y=\"{}\">{}</text>",
+                x + NODE_RADIUS + 14.0,
+                y - NODE_RADIUS - 2.0,
+                vehicle.vehicle_id
+            )
+            .unwrap();
+        }
+
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::track::{NodeLock, sample_layout};
+
+    fn sample() -> TrackMap {
+        let (nodes, edges) = sample_layout();
+        TrackMap::new(nodes, edges)
+    }
+
+    #[test]
+    fn dot() {
+        let mut map = sample();
+        map.set_lock(NodeLock::new(2, 2000));
+        let route = ["S3", "A5", "A6"]
+            .map(|name| map.node_by_name(name).unwrap().clone())
+            .to_vec();
+        let vehicles = [VehicleOverlay {
+            vehicle_id: 2000,
+            node: Some(route[0].clone()),
+            route,
+        }];
+        let dot = Scene::new(&map, HashSet::from([13]), &vehicles).dot();
+
+        assert!(dot.starts_with("digraph track {"));
+        assert!(dot.contains(
+            "\"P2\" [pos=\"0,0!\", fillcolor=\"#80b1d3\", color=\"#d62728\", penwidth=3];"
+        ));
+        assert!(dot.contains("\"P2\" -> \"A6\" [color=\"#444444\", dir=both];"));
+        assert!(dot.contains("\"A5\" -> \"A6\" [color=\"#ff7f0e\", penwidth=3];"));
+        assert!(dot.contains("\"vehicle 2000\" -> \"S3\""));
+        assert!(
+            dot.contains("\"S3\" -> \"A5\" [color=\"#1f77b4\", penwidth=2, constraint=false];")
+        );
+    }
+
+    #[test]
+    fn svg() {
+        let map = sample();
+        let svg = Scene::new(&map, HashSet::new(), &[]).svg();
+
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.trim_end().ends_with("</svg>"));
+        assert_eq!(svg.matches("<circle").count(), 12);
+        assert_eq!(svg.matches("<line").count(), 13);
+        assert_eq!(svg.matches("marker-end").count(), 7);
+        // P2 sits at the bottom left corner of the sample track.
+        assert!(svg.contains("<circle cx=\"120\" cy=\"280\""));
+    }
+}
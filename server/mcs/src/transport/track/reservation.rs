@@ -0,0 +1,271 @@
+use std::{collections::HashMap, sync::Arc};
+
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    constant,
+    transport::track::{Node, NodeType, map::TrackMap},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(tag = "type", content = "id", rename_all = "snake_case")]
+pub enum Resource {
+    Edge(i32),
+    Node(i32),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Reservation {
+    pub resource: Resource,
+    pub vehicle_id: i32,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+}
+
+impl Reservation {
+    fn overlaps(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> bool {
+        self.from < to && from < self.to
+    }
+}
+
+/// Reservations by resource, plus the time each vehicle took its ticket. Older tickets go first
+/// so waiting on reservations alone can never form a cycle.
+#[derive(Debug, Default)]
+pub struct Reservations {
+    reservations: HashMap<Resource, Vec<Reservation>>,
+    tickets: HashMap<i32, DateTime<Utc>>,
+}
+
+impl Reservations {
+    /// Windows for travelling `route`, each stop paired with the time spent working there.
+    pub fn plan(
+        map: &TrackMap,
+        vehicle_id: i32,
+        route: &[(Arc<Node>, TimeDelta)],
+        depart: DateTime<Utc>,
+    ) -> Vec<Reservation> {
+        let margin = margin();
+        let mut plan = Vec::new();
+        let Some((first, dwell)) = route.first() else {
+            return plan;
+        };
+        let mut leave = depart + *dwell;
+        if first.node_type == NodeType::Fork {
+            plan.push(Reservation {
+                resource: Resource::Node(first.id),
+                vehicle_id,
+                from: depart - margin,
+                to: leave + margin,
+            });
+        }
+        for pair in route.windows(2) {
+            let ((from, _), (to, dwell)) = (&pair[0], &pair[1]);
+            let Some(link) = map.link(from.id, to.id) else {
+                continue;
+            };
+            let arrive = leave + travel_time(link.cost);
+            plan.push(Reservation {
+                resource: Resource::Edge(link.edge_id),
+                vehicle_id,
+                from: leave - margin,
+                to: arrive + margin,
+            });
+            leave = arrive + *dwell;
+            if to.node_type == NodeType::Fork {
+                plan.push(Reservation {
+                    resource: Resource::Node(to.id),
+                    vehicle_id,
+                    from: arrive - margin,
+                    to: leave + margin,
+                });
+            }
+        }
+        plan
+    }
+
+    /// No other vehicle holds the resource during the window.
+    pub fn is_free(
+        &self,
+        vehicle_id: i32,
+        resource: Resource,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> bool {
+        self.reservations.get(&resource).is_none_or(|reservations| {
+            reservations.iter().all(|reservation| {
+                reservation.vehicle_id == vehicle_id || !reservation.overlaps(from, to)
+            })
+        })
+    }
+
+    /// The vehicle with an older ticket that reserved the resource for an overlapping window,
+    /// ties go to the lower vehicle id.
+    pub fn yields_to(
+        &self,
+        vehicle_id: i32,
+        resource: Resource,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Option<i32> {
+        let own = (self.ticket(vehicle_id), vehicle_id);
+        self.reservations
+            .get(&resource)?
+            .iter()
+            .filter(|reservation| {
+                reservation.vehicle_id != vehicle_id
+                    && reservation.overlaps(from, to)
+                    && (self.ticket(reservation.vehicle_id), reservation.vehicle_id) < own
+            })
+            .min_by_key(|reservation| (self.ticket(reservation.vehicle_id), reservation.vehicle_id))
+            .map(|reservation| reservation.vehicle_id)
+    }
+
+    fn ticket(&self, vehicle_id: i32) -> DateTime<Utc> {
+        self.tickets
+            .get(&vehicle_id)
+            .copied()
+            .unwrap_or(DateTime::<Utc>::MAX_UTC)
+    }
+
+    /// Replaces everything the vehicle held, keeping its ticket.
+    pub fn replace(&mut self, vehicle_id: i32, reservations: Vec<Reservation>) {
+        self.retain(|reservation| reservation.vehicle_id != vehicle_id);
+        self.tickets.entry(vehicle_id).or_insert_with(Utc::now);
+        for reservation in reservations {
+            self.reservations
+                .entry(reservation.resource)
+                .or_default()
+                .push(reservation);
+        }
+    }
+
+    /// Drops the vehicle's reservations along with its ticket.
+    pub fn release(&mut self, vehicle_id: i32) {
+        self.retain(|reservation| reservation.vehicle_id != vehicle_id);
+        self.tickets.remove(&vehicle_id);
+    }
+
+    pub fn prune(&mut self, now: DateTime<Utc>) {
+        self.retain(|reservation| reservation.to > now);
+    }
+
+    pub fn clear(&mut self) {
+        self.reservations.clear();
+    }
+
+    pub fn list(&self) -> Vec<Reservation> {
+        let mut list: Vec<Reservation> = self.reservations.values().flatten().cloned().collect();
+        list.sort_by_key(|reservation| (reservation.from, reservation.vehicle_id));
+        list
+    }
+
+    fn retain(&mut self, keep: impl Fn(&Reservation) -> bool) {
+        for reservations in self.reservations.values_mut() {
+            reservations.retain(&keep);
+        }
+        self.reservations
+            .retain(|_, reservations| !reservations.is_empty());
+    }
+}
+
+pub fn travel_time(cost: f64) -> TimeDelta {
+    TimeDelta::milliseconds((cost / constant::VEHICLE_SPEED * 1000.0) as i64)
+}
+
+pub fn margin() -> TimeDelta {
+    TimeDelta::seconds(constant::TRACK_RESERVATION_MARGIN)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::track::sample_layout;
+
+    fn sample() -> TrackMap {
+        let (nodes, edges) = sample_layout();
+        TrackMap::new(nodes, edges)
+    }
+
+    fn route(map: &TrackMap, names: &[&str]) -> Vec<(Arc<Node>, TimeDelta)> {
+        names
+            .iter()
+            .map(|name| (map.node_by_name(name).unwrap().clone(), TimeDelta::zero()))
+            .collect()
+    }
+
+    #[test]
+    fn plan_windows() {
+        let map = sample();
+        let depart = Utc::now();
+        let mut stops = route(&map, &["S3", "A5", "A6"]);
+        stops[1].1 = TimeDelta::seconds(5);
+        let plan = Reservations::plan(&map, 2000, &stops, depart);
+
+        let resources: Vec<Resource> = plan
+            .iter()
+            .map(|reservation| reservation.resource)
+            .collect();
+        assert_eq!(
+            resources,
+            [
+                Resource::Edge(6),
+                Resource::Node(11),
+                Resource::Edge(13),
+                Resource::Node(12)
+            ]
+        );
+        let arrive = depart + travel_time(1.0);
+        assert_eq!(plan[1].from, arrive - margin());
+        assert_eq!(plan[1].to, arrive + TimeDelta::seconds(5) + margin());
+        assert_eq!(plan[2].from, arrive + TimeDelta::seconds(5) - margin());
+    }
+
+    #[test]
+    fn older_ticket_wins() {
+        let map = sample();
+        let now = Utc::now();
+        let mut reservations = Reservations::default();
+        reservations.replace(
+            2000,
+            Reservations::plan(&map, 2000, &route(&map, &["S3", "A5"]), now),
+        );
+        reservations.replace(
+            2500,
+            Reservations::plan(
+                &map,
+                2500,
+                &route(&map, &["A5", "S3"]),
+                now + TimeDelta::seconds(1),
+            ),
+        );
+
+        let edge = Resource::Edge(6);
+        let later = now + TimeDelta::seconds(1);
+        assert!(!reservations.is_free(2500, edge, later, later + TimeDelta::seconds(1)));
+        assert_eq!(
+            reservations.yields_to(2500, edge, later, later + TimeDelta::seconds(1)),
+            Some(2000)
+        );
+        assert_eq!(reservations.yields_to(2000, edge, now, later), None);
+
+        // Moving the windows along keeps the ticket.
+        reservations.replace(
+            2000,
+            Reservations::plan(&map, 2000, &route(&map, &["S3", "A5"]), later),
+        );
+        assert_eq!(reservations.yields_to(2500, edge, now, later), Some(2000));
+
+        // Starting over takes a new ticket at the back of the queue.
+        std::thread::sleep(std::time::Duration::from_millis(1));
+        reservations.release(2000);
+        assert!(reservations.is_free(2500, edge, now, later));
+        reservations.replace(
+            2000,
+            Reservations::plan(&map, 2000, &route(&map, &["S3", "A5"]), now),
+        );
+        assert_eq!(reservations.yields_to(2000, edge, now, later), Some(2500));
+        reservations.prune(now + TimeDelta::seconds(60));
+        assert!(reservations.list().is_empty());
+    }
+}
@@ -0,0 +1,625 @@
+use std::{collections::HashMap, fmt, sync::Arc};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{Result, postgres::PgListener, prelude::FromRow, query, query_as, query_scalar};
+use tokio::sync::Mutex;
+#[cfg(test)]
+use tokio::sync::RwLock;
+
+use crate::{
+    db_manager::DbManager,
+    transport::{
+        prelude::Position,
+        track::{
+            Edge, Interaction, Layout, Maintenance, Node, NodeLock, NodeType, Resource, Side, Slots,
+        },
+    },
+};
+
+const TRACK_CHANGED_CHANNEL: &str = "track_changed";
+
+#[async_trait]
+pub trait TrackStore: Send + Sync + fmt::Debug {
+    async fn load(&self) -> Result<(Vec<Node>, Vec<Edge>)>;
+
+    async fn locks(&self) -> Result<Vec<NodeLock>>;
+
+    /// Takes or renews the lock unless another vehicle holds an unexpired lease.
+    async fn acquire_lock(&self, lock: &NodeLock) -> Result<bool>;
+
+    /// Releases the lock only when it is held by `vehicle_id`.
+    async fn release_lock(&self, node_id: i32, vehicle_id: i32) -> Result<bool>;
+
+    async fn renew_locks(&self, vehicle_id: i32, expires_at: DateTime<Utc>) -> Result<()>;
+
+    async fn release_locks(&self, vehicle_id: i32) -> Result<Vec<NodeLock>>;
+
+    /// Removes and returns the locks whose lease ran out.
+    async fn reap_locks(&self) -> Result<Vec<NodeLock>>;
+
+    async fn maintenance(&self) -> Result<Vec<Maintenance>>;
+
+    /// Overwrites an earlier record for the same node or edge.
+    async fn disable(&self, maintenance: &Maintenance) -> Result<()>;
+
+    async fn enable(&self, resource: Resource) -> Result<bool>;
+
+    /// Replaces every node and edge with the given layout.
+    async fn replace(&self, layout: &Layout) -> Result<()>;
+
+    /// Starts watching for changes, called before the first load so none slips in between.
+    async fn listen(&self) -> Result<()>;
+
+    /// Resolves when the stored layout changed and should be loaded again, also after the watch
+    /// was interrupted since a change may have gone by unseen.
+    async fn changed(&self) -> Result<()>;
+}
+
+#[derive(Debug, FromRow)]
+struct NodeRow {
+    id: i32,
+    name: String,
+    #[sqlx(rename = "type")]
+    node_type: NodeType,
+    x: f64,
+    y: f64,
+    z: f64,
+    capacity: i32,
+    side: Option<Side>,
+    slot_first: Option<i32>,
+    slot_last: Option<i32>,
+    port: Option<String>,
+    comment: Option<String>,
+}
+
+#[derive(Debug, FromRow)]
+struct EdgeRow {
+    id: i32,
+    begin_node_id: Option<i32>,
+    end_node_id: Option<i32>,
+    cost: Option<f64>,
+    reverse_cost: Option<f64>,
+}
+
+#[derive(Debug, FromRow)]
+struct LockRow {
+    node_id: i32,
+    vehicle_id: i32,
+    expires_at: DateTime<Utc>,
+}
+
+impl From<LockRow> for NodeLock {
+    fn from(row: LockRow) -> Self {
+        Self {
+            node_id: row.node_id,
+            vehicle_id: row.vehicle_id,
+            expires_at: row.expires_at,
+        }
+    }
+}
+
+#[derive(Debug, FromRow)]
+struct MaintenanceRow {
+    resource_type: String,
+    resource_id: i32,
+    reason: String,
+    operator: String,
+    disabled_at: DateTime<Utc>,
+}
+
+impl From<MaintenanceRow> for Maintenance {
+    fn from(row: MaintenanceRow) -> Self {
+        Self {
+            resource: match row.resource_type.as_str() {
+                "edge" => Resource::Edge(row.resource_id),
+                _ => Resource::Node(row.resource_id),
+            },
+            reason: row.reason,
+            operator: row.operator,
+            disabled_at: row.disabled_at,
+        }
+    }
+}
+
+fn resource_key(resource: Resource) -> (&'static str, i32) {
+    match resource {
+        Resource::Node(id) => ("node", id),
+        Resource::Edge(id) => ("edge", id),
+    }
+}
+
+#[derive(Debug)]
+pub struct PgStore {
+    db: Arc<DbManager>,
+    listener: Mutex<Option<PgListener>>,
+}
+
+impl PgStore {
+    pub fn new(db: Arc<DbManager>) -> Self {
+        Self {
+            db,
+            listener: Mutex::new(None),
+        }
+    }
+}
+
+#[async_trait]
+impl TrackStore for PgStore {
+    async fn load(&self) -> Result<(Vec<Node>, Vec<Edge>)> {
+        let mut conn = self.db.track().await?;
+        let node_rows = query_as::<_, NodeRow>(
+            "SELECT id, name, type, capacity, side, slot_first, slot_last, port, comment, ST_X(geom) as x, ST_Y(geom) as y, ST_Z(geom) as z FROM nodes;",
+        )
+        .fetch_all(&mut *conn)
+        .await?;
+        let edge_rows = query_as::<_, EdgeRow>(
+            "SELECT id, begin_node_id, end_node_id, cost, reverse_cost FROM edges;",
+        )
+        .fetch_all(&mut *conn)
+        .await?;
+
+        let nodes = node_rows
+            .into_iter()
+            .map(|row| Node {
+                id: row.id,
+                name: row.name,
+                node_type: row.node_type,
+                position: Position(row.x, row.y, row.z),
+                capacity: row.capacity,
+                interaction: Interaction {
+                    side: row.side,
+                    slots: row
+                        .slot_first
+                        .zip(row.slot_last)
+                        .map(|(first, last)| Slots { first, last }),
+                    port: row.port,
+                },
+                comment: row.comment,
+            })
+            .collect();
+        let edges = edge_rows
+            .into_iter()
+            .filter_map(|row| {
+                Some(Edge {
+                    id: row.id,
+                    begin_node_id: row.begin_node_id?,
+                    end_node_id: row.end_node_id?,
+                    cost: row.cost.unwrap_or(-1.0),
+                    reverse_cost: row.reverse_cost.unwrap_or(-1.0),
+                })
+            })
+            .collect();
+        Ok((nodes, edges))
+    }
+
+    async fn locks(&self) -> Result<Vec<NodeLock>> {
+        let mut conn = self.db.track().await?;
+        let rows =
+            query_as::<_, LockRow>("SELECT node_id, vehicle_id, expires_at FROM node_locks;")
+                .fetch_all(&mut *conn)
+                .await?;
+        Ok(rows.into_iter().map(NodeLock::from).collect())
+    }
+
+    async fn acquire_lock(&self, lock: &NodeLock) -> Result<bool> {
+        let mut conn = self.db.track().await?;
+        let mut tx = sqlx::Connection::begin(&mut *conn).await?;
+        // Locking the node row serializes vehicles racing for the last slot.
+        let capacity: i32 = query_scalar("SELECT capacity FROM nodes WHERE id = $1 FOR UPDATE;")
+            .bind(lock.node_id)
+            .fetch_one(&mut *tx)
+            .await?;
+        let taken: i64 = query_scalar(
+            "SELECT count(*) FROM node_locks WHERE node_id = $1 AND vehicle_id <> $2 AND expires_at > now();",
+        )
+        .bind(lock.node_id)
+        .bind(lock.vehicle_id)
+        .fetch_one(&mut *tx)
+        .await?;
+        if taken >= capacity as i64 {
+            return Ok(false);
+        }
+        query(
+            "
+            INSERT INTO node_locks(node_id, vehicle_id, expires_at) VALUES($1, $2, $3)
+            ON CONFLICT (node_id, vehicle_id) DO UPDATE SET expires_at = EXCLUDED.expires_at;
+        ",
+        )
+        .bind(lock.node_id)
+        .bind(lock.vehicle_id)
+        .bind(lock.expires_at)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    async fn release_lock(&self, node_id: i32, vehicle_id: i32) -> Result<bool> {
+        let mut conn = self.db.track().await?;
+        let released = query("DELETE FROM node_locks WHERE node_id = $1 AND vehicle_id = $2;")
+            .bind(node_id)
+            .bind(vehicle_id)
+            .execute(&mut *conn)
+            .await?;
+        Ok(released.rows_affected() > 0)
+    }
+
+    async fn renew_locks(&self, vehicle_id: i32, expires_at: DateTime<Utc>) -> Result<()> {
+        let mut conn = self.db.track().await?;
+        query("UPDATE node_locks SET expires_at = $2 WHERE vehicle_id = $1;")
+            .bind(vehicle_id)
+            .bind(expires_at)
+            .execute(&mut *conn)
+            .await?;
+        Ok(())
+    }
+
+    async fn release_locks(&self, vehicle_id: i32) -> Result<Vec<NodeLock>> {
+        let mut conn = self.db.track().await?;
+        let rows = query_as::<_, LockRow>(
+            "DELETE FROM node_locks WHERE vehicle_id = $1 RETURNING node_id, vehicle_id, expires_at;",
+        )
+        .bind(vehicle_id)
+        .fetch_all(&mut *conn)
+        .await?;
+        Ok(rows.into_iter().map(NodeLock::from).collect())
+    }
+
+    async fn reap_locks(&self) -> Result<Vec<NodeLock>> {
+        let mut conn = self.db.track().await?;
+        let rows = query_as::<_, LockRow>(
+            "DELETE FROM node_locks WHERE expires_at <= now() RETURNING node_id, vehicle_id, expires_at;",
+        )
+        .fetch_all(&mut *conn)
+        .await?;
+        Ok(rows.into_iter().map(NodeLock::from).collect())
+    }
+
+    async fn maintenance(&self) -> Result<Vec<Maintenance>> {
+        let mut conn = self.db.track().await?;
+        let rows = query_as::<_, MaintenanceRow>(
+            "SELECT resource_type, resource_id, reason, operator, disabled_at FROM maintenance;",
+        )
+        .fetch_all(&mut *conn)
+        .await?;
+        Ok(rows.into_iter().map(Maintenance::from).collect())
+    }
+
+    async fn disable(&self, maintenance: &Maintenance) -> Result<()> {
+        let mut conn = self.db.track().await?;
+        let (resource_type, resource_id) = resource_key(maintenance.resource);
+        query(
+            "
+            INSERT INTO maintenance(resource_type, resource_id, reason, operator, disabled_at)
+            VALUES($1, $2, $3, $4, $5)
+            ON CONFLICT (resource_type, resource_id) DO UPDATE
+            SET reason = EXCLUDED.reason, operator = EXCLUDED.operator, disabled_at = EXCLUDED.disabled_at;
+        ",
+        )
+        .bind(resource_type)
+        .bind(resource_id)
+        .bind(&maintenance.reason)
+        .bind(&maintenance.operator)
+        .bind(maintenance.disabled_at)
+        .execute(&mut *conn)
+        .await?;
+        Ok(())
+    }
+
+    async fn enable(&self, resource: Resource) -> Result<bool> {
+        let mut conn = self.db.track().await?;
+        let (resource_type, resource_id) = resource_key(resource);
+        let enabled =
+            query("DELETE FROM maintenance WHERE resource_type = $1 AND resource_id = $2;")
+                .bind(resource_type)
+                .bind(resource_id)
+                .execute(&mut *conn)
+                .await?;
+        Ok(enabled.rows_affected() > 0)
+    }
+
+    async fn replace(&self, layout: &Layout) -> Result<()> {
+        let mut conn = self.db.track().await?;
+        let mut tx = sqlx::Connection::begin(&mut *conn).await?;
+        // Ids are renumbered, records for the old layout would point at the wrong track.
+        query("DELETE FROM maintenance;").execute(&mut *tx).await?;
+        query("DELETE FROM edges;").execute(&mut *tx).await?;
+        query("DELETE FROM nodes;").execute(&mut *tx).await?;
+
+        let mut ids = HashMap::with_capacity(layout.nodes.len());
+        for node in &layout.nodes {
+            let id: i32 = query_scalar(
+                "INSERT INTO nodes(name, type, geom, capacity, side, slot_first, slot_last, port, comment) VALUES($1, $2, ST_MakePoint($3, $4, $5), $6, $7, $8, $9, $10, $11) RETURNING id;",
+            )
+            .bind(&node.name)
+            .bind(node.node_type)
+            .bind(node.x)
+            .bind(node.y)
+            .bind(node.z)
+            .bind(node.capacity)
+            .bind(node.side)
+            .bind(node.slots.map(|slots| slots.first))
+            .bind(node.slots.map(|slots| slots.last))
+            .bind(&node.port)
+            .bind(&node.comment)
+            .fetch_one(&mut *tx)
+            .await?;
+            ids.insert(node.name.as_str(), id);
+        }
+        for edge in &layout.edges {
+            let (Some(begin_node_id), Some(end_node_id)) =
+                (ids.get(edge.begin.as_str()), ids.get(edge.end.as_str()))
+            else {
+                return Err(sqlx::Error::RowNotFound);
+            };
+            let direction = if edge.reverse_cost >= 0.0 {
+                "bidirectional"
+            } else {
+                "unidirectional"
+            };
+            query(
+                "INSERT INTO edges(begin_node_id, end_node_id, direction, cost, reverse_cost) VALUES($1, $2, $3, $4, $5);",
+            )
+            .bind(begin_node_id)
+            .bind(end_node_id)
+            .bind(direction)
+            .bind(edge.cost)
+            .bind(edge.reverse_cost)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await
+    }
+
+    async fn listen(&self) -> Result<()> {
+        let mut listener = self.listener.lock().await;
+        if listener.is_none() {
+            *listener = Some(self.db.listen(TRACK_CHANGED_CHANNEL).await?);
+        }
+        Ok(())
+    }
+
+    async fn changed(&self) -> Result<()> {
+        let mut listener = self.listener.lock().await;
+        let notification = match listener.as_mut() {
+            Some(active) => active.try_recv().await,
+            None => Ok(None),
+        };
+        match notification {
+            Ok(Some(_)) => Ok(()),
+            // Listening again right away, the reload that follows catches up on what was missed.
+            Ok(None) | Err(_) => {
+                *listener = None;
+                *listener = Some(self.db.listen(TRACK_CHANGED_CHANNEL).await?);
+                notification.map(|_| ())
+            }
+        }
+    }
+}
+
+/// Keeps the track in memory, for tests that run without a database.
+#[cfg(test)]
+#[derive(Debug)]
+pub struct MemoryStore {
+    nodes: RwLock<Vec<Node>>,
+    edges: RwLock<Vec<Edge>>,
+    locks: RwLock<HashMap<(i32, i32), NodeLock>>,
+    maintenance: RwLock<HashMap<Resource, Maintenance>>,
+}
+
+#[cfg(test)]
+impl MemoryStore {
+    pub fn new(nodes: Vec<Node>, edges: Vec<Edge>) -> Self {
+        Self {
+            nodes: RwLock::new(nodes),
+            edges: RwLock::new(edges),
+            locks: RwLock::new(HashMap::new()),
+            maintenance: RwLock::new(HashMap::new()),
+        }
+    }
+}
+
+#[cfg(test)]
+#[async_trait]
+impl TrackStore for MemoryStore {
+    async fn load(&self) -> Result<(Vec<Node>, Vec<Edge>)> {
+        Ok((
+            self.nodes.read().await.clone(),
+            self.edges.read().await.clone(),
+        ))
+    }
+
+    async fn locks(&self) -> Result<Vec<NodeLock>> {
+        Ok(self.locks.read().await.values().cloned().collect())
+    }
+
+    async fn acquire_lock(&self, lock: &NodeLock) -> Result<bool> {
+        let capacity = self
+            .nodes
+            .read()
+            .await
+            .iter()
+            .find(|node| node.id == lock.node_id)
+            .map(|node| node.capacity)
+            .ok_or(sqlx::Error::RowNotFound)?;
+        let mut locks = self.locks.write().await;
+        let taken = locks
+            .values()
+            .filter(|held| {
+                held.node_id == lock.node_id
+                    && held.vehicle_id != lock.vehicle_id
+                    && !held.is_expired()
+            })
+            .count();
+        if taken >= capacity as usize {
+            return Ok(false);
+        }
+        locks.insert((lock.node_id, lock.vehicle_id), lock.clone());
+        Ok(true)
+    }
+
+    async fn release_lock(&self, node_id: i32, vehicle_id: i32) -> Result<bool> {
+        Ok(self
+            .locks
+            .write()
+            .await
+            .remove(&(node_id, vehicle_id))
+            .is_some())
+    }
+
+    async fn renew_locks(&self, vehicle_id: i32, expires_at: DateTime<Utc>) -> Result<()> {
+        for lock in self.locks.write().await.values_mut() {
+            if lock.vehicle_id == vehicle_id {
+                lock.expires_at = expires_at;
+            }
+        }
+        Ok(())
+    }
+
+    async fn release_locks(&self, vehicle_id: i32) -> Result<Vec<NodeLock>> {
+        let mut locks = self.locks.write().await;
+        let released: Vec<NodeLock> = locks
+            .values()
+            .filter(|lock| lock.vehicle_id == vehicle_id)
+            .cloned()
+            .collect();
+        for lock in &released {
+            locks.remove(&(lock.node_id, lock.vehicle_id));
+        }
+        Ok(released)
+    }
+
+    async fn reap_locks(&self) -> Result<Vec<NodeLock>> {
+        let mut locks = self.locks.write().await;
+        let reaped: Vec<NodeLock> = locks
+            .values()
+            .filter(|lock| lock.is_expired())
+            .cloned()
+            .collect();
+        for lock in &reaped {
+            locks.remove(&(lock.node_id, lock.vehicle_id));
+        }
+        Ok(reaped)
+    }
+
+    async fn maintenance(&self) -> Result<Vec<Maintenance>> {
+        Ok(self.maintenance.read().await.values().cloned().collect())
+    }
+
+    async fn disable(&self, maintenance: &Maintenance) -> Result<()> {
+        self.maintenance
+            .write()
+            .await
+            .insert(maintenance.resource, maintenance.clone());
+        Ok(())
+    }
+
+    async fn enable(&self, resource: Resource) -> Result<bool> {
+        Ok(self.maintenance.write().await.remove(&resource).is_some())
+    }
+
+    async fn replace(&self, layout: &Layout) -> Result<()> {
+        let (nodes, edges) = layout.to_graph().map_err(|_| sqlx::Error::RowNotFound)?;
+        *self.nodes.write().await = nodes;
+        *self.edges.write().await = edges;
+        self.locks.write().await.clear();
+        self.maintenance.write().await.clear();
+        Ok(())
+    }
+
+    async fn listen(&self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn changed(&self) -> Result<()> {
+        std::future::pending().await
+    }
+}
+
+#[cfg(test)]
+pub(crate) fn sample_layout() -> (Vec<Node>, Vec<Edge>) {
+    fn node(id: i32, name: &str, node_type: NodeType, x: f64, y: f64) -> Node {
+        Node {
+            id,
+            name: name.to_string(),
+            node_type,
+            position: Position(x, y, 0.0),
+            capacity: 1,
+            interaction: Interaction::default(),
+            comment: None,
+        }
+    }
+
+    fn edge(id: i32, begin: i32, end: i32, bidirectional: bool, nodes: &[Node]) -> Edge {
+        let a = &nodes[begin as usize - 1].position;
+        let b = &nodes[end as usize - 1].position;
+        let cost = (a.0 - b.0).abs() + (a.1 - b.1).abs() + (a.2 - b.2).abs();
+        Edge {
+            id,
+            begin_node_id: begin,
+            end_node_id: end,
+            cost,
+            reverse_cost: if bidirectional { cost } else { -1.0 },
+        }
+    }
+
+    // Same layout as sql/graph_test.sql.
+    let mut nodes = vec![
+        node(1, "P1", NodeType::ParkingStation, 2.0, 0.0),
+        node(2, "P2", NodeType::ParkingStation, 0.0, 0.0),
+        node(3, "C1", NodeType::ChargingStation, 1.0, 0.0),
+        node(4, "S1", NodeType::ItemStocker, 1.0, 3.0),
+        node(5, "S2", NodeType::ItemStocker, -1.0, 1.0),
+        node(6, "S3", NodeType::ShippingDock, -1.0, 2.0),
+        node(7, "A1", NodeType::Fork, 2.0, 1.0),
+        node(8, "A2", NodeType::Fork, 1.0, 1.0),
+        node(9, "A3", NodeType::Fork, 1.0, 2.0),
+        node(10, "A4", NodeType::Fork, 2.0, 2.0),
+        node(11, "A5", NodeType::Fork, 0.0, 2.0),
+        node(12, "A6", NodeType::Fork, 0.0, 1.0),
+    ];
+    // Stockers are worked on from below, S2 keeps the upper half of the inventory free.
+    nodes[3].interaction.side = Some(Side::NegY);
+    nodes[4].interaction = Interaction {
+        side: Some(Side::NegY),
+        slots: Some(Slots { first: 1, last: 8 }),
+        port: None,
+    };
+    nodes[5].interaction = Interaction {
+        side: Some(Side::NegY),
+        slots: None,
+        port: Some("dock".to_string()),
+    };
+    let edges = vec![
+        edge(1, 2, 12, true, &nodes),
+        edge(2, 3, 8, true, &nodes),
+        edge(3, 1, 7, true, &nodes),
+        edge(4, 4, 9, true, &nodes),
+        edge(5, 5, 12, true, &nodes),
+        edge(6, 6, 11, true, &nodes),
+        edge(7, 12, 8, false, &nodes),
+        edge(8, 8, 7, false, &nodes),
+        edge(9, 7, 10, false, &nodes),
+        edge(10, 10, 9, false, &nodes),
+        edge(11, 9, 8, false, &nodes),
+        edge(12, 9, 11, false, &nodes),
+        edge(13, 11, 12, false, &nodes),
+    ];
+    (nodes, edges)
+}
+
+/// The sample track in memory, with a pool that never connects: a failed database call errors
+/// within a second instead of hanging the test.
+#[cfg(test)]
+pub(crate) async fn offline_sample() -> (Arc<DbManager>, super::Graph) {
+    let pool = sqlx::postgres::PgPoolOptions::new()
+        .acquire_timeout(std::time::Duration::from_secs(1))
+        .connect_lazy("postgres://localhost/mcs")
+        .unwrap();
+    let (nodes, edges) = sample_layout();
+    let track_graph = super::Graph::new(MemoryStore::new(nodes, edges))
+        .await
+        .unwrap();
+    (DbManager::new(pool), track_graph)
+}
@@ -0,0 +1,249 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::Serialize;
+
+use crate::transport::track::{Edge, Node, NodeType};
+
+const REQUIRED_NODE_TYPES: [NodeType; 3] = [
+    NodeType::ParkingStation,
+    NodeType::ChargingStation,
+    NodeType::ShippingDock,
+];
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Component {
+    pub nodes: Vec<String>,
+    pub missing: Vec<NodeType>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BadEdge {
+    pub id: i32,
+    pub begin: String,
+    pub end: String,
+    pub cost: f64,
+    pub reverse_cost: f64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Report {
+    /// Strongly connected components, a healthy track has exactly one.
+    pub components: Vec<Component>,
+    /// Nodes a vehicle can not leave, or can not reach.
+    pub dead_ends: Vec<String>,
+    pub duplicate_names: Vec<String>,
+    pub bad_edges: Vec<BadEdge>,
+}
+
+impl Report {
+    pub fn is_ok(&self) -> bool {
+        self.components.len() <= 1
+            && self
+                .components
+                .iter()
+                .all(|component| component.missing.is_empty())
+            && self.dead_ends.is_empty()
+            && self.duplicate_names.is_empty()
+            && self.bad_edges.is_empty()
+    }
+}
+
+pub fn validate(nodes: &[Node], edges: &[Edge]) -> Report {
+    let mut nodes: Vec<&Node> = nodes.iter().collect();
+    nodes.sort_by_key(|node| node.id);
+    let by_id: HashMap<i32, &Node> = nodes.iter().map(|node| (node.id, *node)).collect();
+    let name_of = |id: i32| {
+        by_id
+            .get(&id)
+            .map(|node| node.name.clone())
+            .unwrap_or_else(|| id.to_string())
+    };
+
+    let mut seen = HashSet::new();
+    let mut duplicate_names = Vec::new();
+    for node in &nodes {
+        if !seen.insert(node.name.as_str()) && !duplicate_names.contains(&node.name) {
+            duplicate_names.push(node.name.clone());
+        }
+    }
+
+    let mut bad_edges = Vec::new();
+    let mut outgoing: HashMap<i32, Vec<i32>> = HashMap::new();
+    let mut incoming: HashMap<i32, Vec<i32>> = HashMap::new();
+    for edge in edges {
+        // A negative reverse cost marks a one-way edge, zero is never valid.
+        if edge.cost.is_nan()
+            || edge.cost <= 0.0
+            || edge.reverse_cost.is_nan()
+            || edge.reverse_cost == 0.0
+        {
+            bad_edges.push(BadEdge {
+                id: edge.id,
+                begin: name_of(edge.begin_node_id),
+                end: name_of(edge.end_node_id),
+                cost: edge.cost,
+                reverse_cost: edge.reverse_cost,
+            });
+        }
+        if !by_id.contains_key(&edge.begin_node_id) || !by_id.contains_key(&edge.end_node_id) {
+            continue;
+        }
+        if edge.cost >= 0.0 {
+            outgoing
+                .entry(edge.begin_node_id)
+                .or_default()
+                .push(edge.end_node_id);
+            incoming
+                .entry(edge.end_node_id)
+                .or_default()
+                .push(edge.begin_node_id);
+        }
+        if edge.reverse_cost >= 0.0 {
+            outgoing
+                .entry(edge.end_node_id)
+                .or_default()
+                .push(edge.begin_node_id);
+            incoming
+                .entry(edge.begin_node_id)
+                .or_default()
+                .push(edge.end_node_id);
+        }
+    }
+
+    let dead_ends = nodes
+        .iter()
+        .filter(|node| !outgoing.contains_key(&node.id) || !incoming.contains_key(&node.id))
+        .map(|node| node.name.clone())
+        .collect();
+
+    let ids: Vec<i32> = nodes.iter().map(|node| node.id).collect();
+    let components = strongly_connected(&ids, &outgoing, &incoming)
+        .into_iter()
+        .map(|component| {
+            let types: HashSet<NodeType> = component.iter().map(|id| by_id[id].node_type).collect();
+            Component {
+                nodes: component.into_iter().map(name_of).collect(),
+                missing: REQUIRED_NODE_TYPES
+                    .into_iter()
+                    .filter(|node_type| !types.contains(node_type))
+                    .collect(),
+            }
+        })
+        .collect();
+
+    Report {
+        components,
+        dead_ends,
+        duplicate_names,
+        bad_edges,
+    }
+}
+
+// Kosaraju, iterative so large layouts do not overflow the stack.
+fn strongly_connected(
+    ids: &[i32],
+    outgoing: &HashMap<i32, Vec<i32>>,
+    incoming: &HashMap<i32, Vec<i32>>,
+) -> Vec<Vec<i32>> {
+    let mut visited = HashSet::new();
+    let mut order = Vec::with_capacity(ids.len());
+    for &root in ids {
+        if !visited.insert(root) {
+            continue;
+        }
+        let mut stack = vec![(root, 0)];
+        while let Some((id, index)) = stack.pop() {
+            let next = outgoing.get(&id).and_then(|links| links.get(index));
+            match next {
+                Some(&to) => {
+                    stack.push((id, index + 1));
+                    if visited.insert(to) {
+                        stack.push((to, 0));
+                    }
+                }
+                None => order.push(id),
+            }
+        }
+    }
+
+    let mut assigned = HashSet::new();
+    let mut components = Vec::new();
+    for &root in order.iter().rev() {
+        if !assigned.insert(root) {
+            continue;
+        }
+        let mut component = vec![root];
+        let mut stack = vec![root];
+        while let Some(id) = stack.pop() {
+            for &from in incoming.get(&id).into_iter().flatten() {
+                if assigned.insert(from) {
+                    component.push(from);
+                    stack.push(from);
+                }
+            }
+        }
+        component.sort();
+        components.push(component);
+    }
+    components.sort();
+    components
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::{
+        prelude::Position,
+        track::{Interaction, sample_layout},
+    };
+
+    #[test]
+    fn sample_is_ok() {
+        let (nodes, edges) = sample_layout();
+        let report = validate(&nodes, &edges);
+        assert!(report.is_ok(), "{:#?}", report);
+        assert_eq!(report.components.len(), 1);
+        assert_eq!(report.components[0].nodes.len(), nodes.len());
+    }
+
+    #[test]
+    fn report_problems() {
+        let (mut nodes, mut edges) = sample_layout();
+        // C1 can still be left but never reached again.
+        edges[1].reverse_cost = -1.0;
+        edges[2].cost = 0.0;
+        nodes.push(Node {
+            id: 13,
+            name: "P1".to_string(),
+            node_type: NodeType::ParkingStation,
+            position: Position(5.0, 5.0, 0.0),
+            capacity: 1,
+            interaction: Interaction::default(),
+            comment: None,
+        });
+
+        let report = validate(&nodes, &edges);
+        assert!(!report.is_ok());
+        assert_eq!(report.duplicate_names, ["P1"]);
+        assert_eq!(report.dead_ends, ["C1", "P1"]);
+        assert_eq!(report.bad_edges.len(), 1);
+        assert_eq!(report.bad_edges[0].begin, "P1");
+        assert_eq!(report.bad_edges[0].end, "A1");
+
+        let c1 = report
+            .components
+            .iter()
+            .find(|component| component.nodes == ["C1"])
+            .unwrap();
+        assert_eq!(
+            c1.missing,
+            [NodeType::ParkingStation, NodeType::ShippingDock]
+        );
+        let main = report
+            .components
+            .iter()
+            .find(|component| component.nodes.contains(&"S1".to_string()))
+            .unwrap();
+        assert_eq!(main.missing, [NodeType::ChargingStation]);
+    }
+}
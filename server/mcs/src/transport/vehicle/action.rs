@@ -1,83 +1,325 @@
-use std::{collections::LinkedList, sync::Arc};
+use std::{collections::LinkedList, fmt, sync::Arc};
 
-use crate::transport::track;
+use chrono::TimeDelta;
+use serde::{Deserialize, Serialize};
+
+use crate::{constant, transport::track};
+
+/// What a station action moves, unset fields take whatever is there.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Cargo {
+    /// Item or fluid name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Items, or millibuckets of fluid.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quantity: Option<i32>,
+}
+
+impl Cargo {
+    /// Takes `amount` items or millibuckets off the quantity, which stops at zero.
+    pub fn shorten(&mut self, amount: u32) {
+        if let Some(quantity) = self.quantity.as_mut() {
+            let amount = i32::try_from(amount).unwrap_or(i32::MAX);
+            *quantity = quantity.saturating_sub(amount).max(0);
+        }
+    }
+}
+
+/// The node a station action happens at and how to work its inventory.
+#[derive(Debug, Clone, Default)]
+pub struct Station {
+    pub node: Option<Arc<track::Node>>,
+    pub interaction: track::Interaction,
+    pub cargo: Cargo,
+}
 
 #[derive(Debug, Clone)]
 pub enum Action {
     Move(Arc<track::Node>),
-    Drop,
-    Suck,
-    Fill,
-    Use,
+    Drop(Station),
+    Suck(Station),
+    Fill(Station),
+    Use(Station),
+    /// Stay put, the way ahead is reserved by another vehicle.
+    Wait,
+    /// Stay at the entrance until the lift is free, the next move rides it.
+    WaitForLift(Arc<track::Node>),
+}
+
+/// What the vehicle reports about the station action it was given last.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum Outcome {
+    Success,
+    /// Moved `count` of the `expected` items, or millibuckets of fluid.
+    Partial {
+        count: u32,
+        expected: u32,
+    },
+    Failure {
+        code: String,
+    },
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Outcome::Success => write!(f, "success"),
+            Outcome::Partial { count, expected } => write!(f, "partial {}/{}", count, expected),
+            Outcome::Failure { code } => write!(f, "failure {}", code),
+        }
+    }
 }
 
 #[derive(Debug)]
-pub struct ActionSequence(LinkedList<Action>);
+pub struct ActionSequence {
+    actions: LinkedList<Action>,
+    /// Picked up and not handed over yet.
+    loaded: bool,
+    /// Handed over what was picked up, what is left is the way back.
+    delivered: bool,
+    /// How a pickup came up short, kept on the task once it is done.
+    shortfall: Option<String>,
+}
 
 impl ActionSequence {
     pub fn next_action(&self) -> Option<&Action> {
-        self.0.front()
+        self.actions.front()
     }
 
     pub fn pop_next_action(&mut self) -> Option<Action> {
-        self.0.pop_front()
+        let action = self.actions.pop_front();
+        match action {
+            Some(Action::Suck(_)) => self.loaded = true,
+            Some(Action::Drop(_) | Action::Fill(_)) if self.loaded => {
+                self.loaded = false;
+                self.delivered = true;
+            }
+            _ => {}
+        }
+        action
+    }
+
+    /// The station the action ahead works at.
+    pub fn next_station_mut(&mut self) -> Option<&mut Station> {
+        match self.actions.front_mut()? {
+            Action::Drop(station)
+            | Action::Suck(station)
+            | Action::Fill(station)
+            | Action::Use(station) => Some(station),
+            _ => None,
+        }
+    }
+
+    /// Takes what the pickup ahead came up short off the delivery after it, `shortfall` says why.
+    pub fn shorten_delivery(&mut self, missing: u32, shortfall: String) {
+        self.shortfall = Some(shortfall);
+        let delivery = self
+            .actions
+            .iter_mut()
+            .skip(1)
+            .find_map(|action| match action {
+                Action::Drop(station) | Action::Fill(station) => Some(station),
+                _ => None,
+            });
+        if let Some(station) = delivery {
+            station.cargo.shorten(missing);
+        }
     }
 
     pub fn last_move_node(&self) -> Option<Arc<track::Node>> {
-        for action in self.0.iter().rev() {
+        for action in self.actions.iter().rev() {
             if let Action::Move(node) = action {
                 return Some(node.clone());
             }
         }
         None
     }
+
+    pub fn shortfall(&self) -> Option<&str> {
+        self.shortfall.as_deref()
+    }
+
+    /// Whether the vehicle holds cargo picked up by the sequence.
+    pub fn loaded(&self) -> bool {
+        self.loaded
+    }
+
+    pub fn delivered(&self) -> bool {
+        self.delivered
+    }
+
+    /// Last node of the moves ahead before the next action at a station.
+    pub fn leg_end(&self) -> Option<Arc<track::Node>> {
+        self.actions
+            .iter()
+            .take_while(|action| matches!(action, Action::Move(_) | Action::WaitForLift(_)))
+            .filter_map(|action| match action {
+                Action::Move(node) => Some(node.clone()),
+                _ => None,
+            })
+            .last()
+    }
+
+    /// Replaces the moves ahead with `path`, which starts at the current node.
+    pub fn reroute(&mut self, path: &track::Path) {
+        while let Some(Action::Move(_) | Action::WaitForLift(_)) = self.actions.front() {
+            self.actions.pop_front();
+        }
+        let mut moves = ActionSequenceBuilder::new().move_path(path).actions;
+        moves.append(&mut self.actions);
+        self.actions = moves;
+    }
+
+    /// Where each leg ahead ends, paired with the station actions done there. A leg without
+    /// moves works at the node the vehicle is on. Waits for lifts come back with the moves.
+    pub fn legs(&self) -> Vec<(Option<Arc<track::Node>>, Vec<Action>)> {
+        let mut legs = Vec::new();
+        let mut end = None;
+        let mut work = Vec::new();
+        for action in &self.actions {
+            match action {
+                Action::Move(node) => {
+                    if !work.is_empty() {
+                        legs.push((end.take(), std::mem::take(&mut work)));
+                    }
+                    end = Some(node.clone());
+                }
+                Action::WaitForLift(_) => {}
+                other => work.push(other.clone()),
+            }
+        }
+        if end.is_some() || !work.is_empty() {
+            legs.push((end, work));
+        }
+        legs
+    }
+
+    /// Nodes still to visit starting from `landmark`, each paired with the time spent working
+    /// there.
+    pub fn route(&self, landmark: Option<Arc<track::Node>>) -> Vec<(Arc<track::Node>, TimeDelta)> {
+        let mut route: Vec<(Arc<track::Node>, TimeDelta)> = landmark
+            .into_iter()
+            .map(|node| (node, TimeDelta::zero()))
+            .collect();
+        for action in &self.actions {
+            match action {
+                Action::Move(node) => route.push((node.clone(), TimeDelta::zero())),
+                Action::WaitForLift(_) => {}
+                _ => {
+                    if let Some((_, dwell)) = route.last_mut() {
+                        *dwell += TimeDelta::seconds(constant::VEHICLE_ACTION_TIME);
+                    }
+                }
+            }
+        }
+        route
+    }
 }
 
-pub struct ActionSequenceBuilder(LinkedList<Action>);
+pub struct ActionSequenceBuilder {
+    actions: LinkedList<Action>,
+    /// Node the vehicle is at after the moves so far, its interaction goes into station actions.
+    at: Option<Arc<track::Node>>,
+}
 
 impl ActionSequenceBuilder {
     pub fn new() -> Self {
-        Self(LinkedList::new())
+        Self {
+            actions: LinkedList::new(),
+            at: None,
+        }
+    }
+
+    /// Moving onto a lift from outside of it waits for the lift first.
+    fn push_move(&mut self, node: Arc<track::Node>) {
+        if node.node_type == track::NodeType::Lift
+            && self
+                .at
+                .as_ref()
+                .is_none_or(|at| at.node_type != track::NodeType::Lift)
+        {
+            self.actions.push_back(Action::WaitForLift(node.clone()));
+        }
+        self.actions.push_back(Action::Move(node.clone()));
+        self.at = Some(node);
     }
 
     pub fn move_path(mut self, path: &track::Path) -> Self {
+        if let Some(first) = path.first() {
+            self.at = Some(first.clone());
+        }
         for node in path.iter().skip(1) {
-            self.0.push_back(Action::Move(node.clone()));
+            self.push_move(node.clone());
         }
         self
     }
 
     pub fn move_to(mut self, node: Arc<track::Node>) -> Self {
-        self.0.push_back(Action::Move(node));
+        self.push_move(node);
         self
     }
 
+    fn station(&self) -> Station {
+        Station {
+            node: self.at.clone(),
+            interaction: self
+                .at
+                .as_ref()
+                .map(|node| node.interaction.clone())
+                .unwrap_or_default(),
+            cargo: Cargo::default(),
+        }
+    }
+
     pub fn drop(mut self) -> Self {
-        self.0.push_back(Action::Drop);
+        self.actions.push_back(Action::Drop(self.station()));
         self
     }
 
     pub fn suck(mut self) -> Self {
-        self.0.push_back(Action::Suck);
+        self.actions.push_back(Action::Suck(self.station()));
         self
     }
 
     pub fn fill(mut self) -> Self {
-        self.0.push_back(Action::Fill);
+        self.actions.push_back(Action::Fill(self.station()));
         self
     }
 
     pub fn use_tool(mut self) -> Self {
-        self.0.push_back(Action::Use);
+        self.actions.push_back(Action::Use(self.station()));
         self
     }
 
-    pub fn chain(mut self, mut sequence: Self) -> Self {
-        self.0.append(&mut sequence.0);
+    /// Narrows the station action just added to `cargo`, `slots` stand in for the station's own.
+    pub fn cargo(mut self, cargo: &Cargo, slots: Option<track::Slots>) -> Self {
+        if let Some(Action::Drop(station) | Action::Suck(station) | Action::Fill(station)) =
+            self.actions.back_mut()
+        {
+            station.cargo = cargo.clone();
+            if slots.is_some() {
+                station.interaction.slots = slots;
+            }
+        }
+        self
+    }
+
+    pub fn action(mut self, action: Action) -> Self {
+        match action {
+            Action::Move(node) => self.push_move(node),
+            action => self.actions.push_back(action),
+        }
         self
     }
 
     pub fn build(self) -> ActionSequence {
-        ActionSequence(self.0)
+        ActionSequence {
+            actions: self.actions,
+            loaded: false,
+            delivered: false,
+            shortfall: None,
+        }
     }
 }
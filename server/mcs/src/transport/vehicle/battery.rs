@@ -0,0 +1,226 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{constant, transport::vehicle::Skill};
+
+/// Battery levels a vehicle acts on, all between 0 and 1.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BatteryPolicy {
+    /// At or below, a vehicle without work goes charging.
+    pub charge: f32,
+    /// At or above, charging is done.
+    pub resume: f32,
+    /// At or below, a vehicle drops its task to charge.
+    pub critical: f32,
+    /// At or below, a parked vehicle with no work waiting for its skill tops up.
+    pub opportunistic: f32,
+    /// At or above, a charging vehicle can be pulled off the charger for work.
+    pub interruptible: f32,
+}
+
+impl Default for BatteryPolicy {
+    fn default() -> Self {
+        Self {
+            charge: constant::VEHICLE_CHARGE_LEVEL,
+            resume: constant::VEHICLE_CHARGED_LEVEL,
+            critical: constant::VEHICLE_CRITICAL_LEVEL,
+            opportunistic: constant::VEHICLE_OPPORTUNISTIC_LEVEL,
+            interruptible: constant::VEHICLE_INTERRUPTIBLE_LEVEL,
+        }
+    }
+}
+
+impl BatteryPolicy {
+    /// Whether a vehicle following the policy settles, instead of going back and forth.
+    pub fn check(&self) -> Result<(), String> {
+        let levels = [
+            ("charge", self.charge),
+            ("resume", self.resume),
+            ("critical", self.critical),
+            ("opportunistic", self.opportunistic),
+            ("interruptible", self.interruptible),
+        ];
+        if let Some((name, level)) = levels
+            .iter()
+            .find(|(_, level)| !(0.0..=1.0).contains(level))
+        {
+            return Err(format!("{} level {} is not between 0 and 1", name, level));
+        }
+        if self.critical > self.charge {
+            return Err(format!(
+                "critical level {} is above charge level {}",
+                self.critical, self.charge
+            ));
+        }
+        if self.charge >= self.resume {
+            return Err(format!(
+                "charge level {} is not below resume level {}",
+                self.charge, self.resume
+            ));
+        }
+        if self.opportunistic >= self.resume {
+            return Err(format!(
+                "opportunistic level {} is not below resume level {}",
+                self.opportunistic, self.resume
+            ));
+        }
+        // Pulled off at or below critical, a vehicle would drop the task it was pulled off for.
+        if self.interruptible <= self.critical || self.interruptible > self.resume {
+            return Err(format!(
+                "interruptible level {} is not above critical level {} and up to resume level {}",
+                self.interruptible, self.critical, self.resume
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Levels set for a skill or a vehicle, the unset ones come from the policy below it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct BatteryOverride {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub charge: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resume: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub critical: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub opportunistic: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interruptible: Option<f32>,
+}
+
+impl BatteryOverride {
+    pub fn over(&self, policy: BatteryPolicy) -> BatteryPolicy {
+        BatteryPolicy {
+            charge: self.charge.unwrap_or(policy.charge),
+            resume: self.resume.unwrap_or(policy.resume),
+            critical: self.critical.unwrap_or(policy.critical),
+            opportunistic: self.opportunistic.unwrap_or(policy.opportunistic),
+            interruptible: self.interruptible.unwrap_or(policy.interruptible),
+        }
+    }
+}
+
+/// The default policy, refined by skill, then by vehicle id.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct BatteryConfig {
+    pub default: BatteryPolicy,
+    pub item: BatteryOverride,
+    pub fluid: BatteryOverride,
+    pub use_tool: BatteryOverride,
+    pub vehicles: HashMap<i32, BatteryOverride>,
+}
+
+impl BatteryConfig {
+    pub fn policy(&self, id: i32, skill: &Skill) -> BatteryPolicy {
+        let policy = self.skill_policy(skill);
+        match self.vehicles.get(&id) {
+            Some(levels) => levels.over(policy),
+            None => policy,
+        }
+    }
+
+    fn skill_policy(&self, skill: &Skill) -> BatteryPolicy {
+        let levels = match skill {
+            Skill::Item => self.item,
+            Skill::Fluid => self.fluid,
+            Skill::UseTool(_) => self.use_tool,
+        };
+        levels.over(self.default)
+    }
+
+    /// Checks the default and skill policies, a vehicle's own levels are checked once its skill
+    /// is known.
+    pub fn check(&self) -> Result<(), String> {
+        self.default
+            .check()
+            .map_err(|e| format!("default battery policy: {}", e))?;
+        for (name, levels) in [
+            ("item", self.item),
+            ("fluid", self.fluid),
+            ("use_tool", self.use_tool),
+        ] {
+            levels
+                .over(self.default)
+                .check()
+                .map_err(|e| format!("{} battery policy: {}", name, e))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::transport::vehicle::ToolType;
+
+    #[test]
+    fn policy() {
+        let config: BatteryConfig = serde_json::from_value(json!({
+            "default": {"charge": 0.25},
+            "fluid": {"charge": 0.5, "resume": 0.9},
+            "vehicles": {"4001": {"critical": 0.2}}
+        }))
+        .unwrap();
+        config.check().unwrap();
+
+        let policy = config.policy(2000, &Skill::Item);
+        assert_eq!(policy.charge, 0.25);
+        assert_eq!(policy.resume, constant::VEHICLE_CHARGED_LEVEL);
+        assert_eq!(config.policy(4000, &Skill::Fluid).charge, 0.5);
+        // Unset levels come from the skill, then the default.
+        let policy = config.policy(4001, &Skill::Fluid);
+        assert_eq!(
+            (policy.critical, policy.charge, policy.resume),
+            (0.2, 0.5, 0.9)
+        );
+        assert_eq!(config.policy(4001, &Skill::Item).charge, 0.25);
+        assert_eq!(
+            config.policy(100, &Skill::UseTool(ToolType::Wrench)),
+            config.default
+        );
+    }
+
+    #[test]
+    fn check() {
+        assert!(BatteryPolicy::default().check().is_ok());
+        let flapping = BatteryPolicy {
+            opportunistic: 0.95,
+            ..Default::default()
+        };
+        assert!(flapping.check().is_err());
+        let out_of_range = BatteryPolicy {
+            resume: 1.5,
+            ..Default::default()
+        };
+        assert!(out_of_range.check().is_err());
+        let pulled_off_dead = BatteryPolicy {
+            interruptible: 0.1,
+            ..Default::default()
+        };
+        assert!(pulled_off_dead.check().is_err());
+        let never_interruptible = BatteryPolicy {
+            interruptible: 0.96,
+            ..Default::default()
+        };
+        assert!(never_interruptible.check().is_err());
+        let at_resume = BatteryPolicy {
+            interruptible: 0.95,
+            ..Default::default()
+        };
+        assert!(at_resume.check().is_ok());
+
+        let config: BatteryConfig = serde_json::from_value(json!({
+            "default": {"charge": 0.25},
+            "item": {"resume": 0.2}
+        }))
+        .unwrap();
+        assert!(config.check().unwrap_err().starts_with("item"));
+    }
+}
@@ -1,44 +1,172 @@
 use std::fmt;
 use std::sync::Arc;
 
-use tokio::sync::{RwLock, mpsc};
-use tracing::error;
+use chrono::{TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{RwLock, broadcast, mpsc};
+use tracing::{error, info, warn};
 
 use super::track;
+use crate::constant;
 use crate::transport::prelude::*;
 use crate::transport::track::Graph;
-pub use crate::transport::vehicle::action::{Action, ActionSequence, ActionSequenceBuilder};
+pub use crate::transport::vehicle::action::{
+    Action, ActionSequence, ActionSequenceBuilder, Cargo, Outcome, Station,
+};
+pub use crate::transport::vehicle::battery::{BatteryConfig, BatteryOverride, BatteryPolicy};
 pub use crate::transport::vehicle::skill::Skill;
 pub use crate::transport::vehicle::skill::ToolType;
 use crate::transport::vehicle::timeout::Timeout;
 
 mod action;
+mod battery;
 mod skill;
 mod timeout;
 
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Seconds without a report before the vehicle counts as offline.
+    pub online_timeout: i64,
+    pub battery: BatteryConfig,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            online_timeout: constant::VEHICLE_ONLINE_UPDATE_TIMEOUT,
+            battery: BatteryConfig::default(),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum Error {
     State,
     TrackGraph,
     NotInTrackGraph,
-    Db(sqlx::Error),
+    NodeLocked(i32),
+    Track(track::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::State => write!(f, "unexpected state"),
+            Error::TrackGraph => write!(f, "track graph error"),
+            Error::NotInTrackGraph => write!(f, "not in the track graph"),
+            Error::NodeLocked(id) => write!(f, "node {} locked by another vehicle", id),
+            Error::Track(e) => write!(f, "{}", e),
+        }
+    }
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
 
+/// Everything that happens to a vehicle, persisted by `StateUpdate` and pushed to subscribers.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
 pub enum Event {
+    StateChanged {
+        vehicle_id: i32,
+        from: &'static str,
+        to: &'static str,
+    },
+    /// Reported position moved, `node` is the landmark after the report.
+    Moved {
+        vehicle_id: i32,
+        position: (f64, f64, f64),
+        node: Option<String>,
+    },
     ProcessStart {
         vehicle_id: i32,
         vehicle_skill: Skill,
         task_id: i32,
     },
+    /// Done, with `shortfall` when less was delivered than the task asked for.
     ProcessDone {
         vehicle_id: i32,
         vehicle_skill: Skill,
         task_id: i32,
+        shortfall: Option<String>,
+    },
+    /// The task was dropped, it goes back to pending if `requeue` and fails with `reason`
+    /// otherwise.
+    ProcessAborted {
+        vehicle_id: i32,
+        vehicle_skill: Skill,
+        task_id: i32,
+        requeue: bool,
+        reason: String,
+    },
+    ChargeStart {
+        vehicle_id: i32,
+    },
+    ChargeDone {
+        vehicle_id: i32,
+    },
+    Deadlock {
+        vehicle_ids: Vec<i32>,
+        victim: i32,
+        resolution: GiveWay,
     },
-    ChargeStart,
-    ChargeDone,
+}
+
+impl Event {
+    pub fn involves(&self, id: i32) -> bool {
+        match self {
+            Event::StateChanged { vehicle_id, .. }
+            | Event::Moved { vehicle_id, .. }
+            | Event::ProcessStart { vehicle_id, .. }
+            | Event::ProcessDone { vehicle_id, .. }
+            | Event::ProcessAborted { vehicle_id, .. }
+            | Event::ChargeStart { vehicle_id }
+            | Event::ChargeDone { vehicle_id } => *vehicle_id == id,
+            Event::Deadlock { vehicle_ids, .. } => vehicle_ids.contains(&id),
+        }
+    }
+
+    /// Task events, the ones `StateUpdate` writes to the database.
+    pub fn persisted(&self) -> bool {
+        matches!(
+            self,
+            Event::ProcessStart { .. } | Event::ProcessDone { .. } | Event::ProcessAborted { .. }
+        )
+    }
+}
+
+/// Pushes every event to subscribers without waiting, and queues task events for `StateUpdate`.
+#[derive(Debug, Clone)]
+pub struct EventSender {
+    feed: broadcast::Sender<Event>,
+    persist: mpsc::Sender<Event>,
+}
+
+impl EventSender {
+    pub fn new(feed: broadcast::Sender<Event>, persist: mpsc::Sender<Event>) -> Self {
+        Self { feed, persist }
+    }
+
+    /// Only fails once `StateUpdate` has stopped taking task events.
+    pub async fn send(
+        &self,
+        event: Event,
+    ) -> std::result::Result<(), mpsc::error::SendError<Event>> {
+        // No subscribers is not an error.
+        let _ = self.feed.send(event.clone());
+        if event.persisted() {
+            self.persist.send(event).await?;
+        }
+        Ok(())
+    }
+}
+
+/// How a vehicle got out of a deadlock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GiveWay {
+    Rerouted,
+    BackedOff,
 }
 
 #[derive(Debug)]
@@ -52,6 +180,54 @@ enum State {
     Parking(ActionSequence),
     ProcessDone,
     Processing(ActionSequence),
+    /// Taking what is left aboard of a failed task back to the shipping dock.
+    Unloading(ActionSequence),
+}
+
+impl State {
+    fn name(&self) -> &'static str {
+        match self {
+            State::Initing(_) => "initing",
+            State::InitDone => "init_done",
+            State::ChargeDone => "charge_done",
+            State::Charging(_) => "charging",
+            State::Offline => "offline",
+            State::ParkDone => "park_done",
+            State::Parking(_) => "parking",
+            State::ProcessDone => "process_done",
+            State::Processing(_) => "processing",
+            State::Unloading(_) => "unloading",
+        }
+    }
+}
+
+/// What to do about the station action ahead after the vehicle reported on it.
+enum Settled {
+    Done,
+    Retry(Action),
+    /// Retries are used up, with the reason the task fails for.
+    GaveUp(String),
+}
+
+/// How the station action ahead went so far.
+#[derive(Debug, Default)]
+struct Attempt {
+    retries: u32,
+    /// Items or millibuckets moved by the partial tries.
+    moved: u32,
+}
+
+/// What a vehicle last reported and what it is doing, for the fleet query.
+#[derive(Debug, Clone, Serialize)]
+pub struct Status {
+    pub id: i32,
+    pub skill: Skill,
+    pub state: &'static str,
+    pub node: Option<String>,
+    pub battery_level: Option<f32>,
+    pub tool_level: Option<f32>,
+    pub task_id: Option<i32>,
+    pub waiting_for: Option<i32>,
 }
 
 pub struct Vehicle {
@@ -61,34 +237,84 @@ pub struct Vehicle {
     overtime: Timeout,
     track_graph: Arc<Graph>,
     node: Option<Arc<track::Node>>,
+    waiting_for: Option<i32>,
     current_task_id: Option<i32>,
-    sender: Option<mpsc::Sender<Event>>,
+    battery_level: Option<f32>,
+    tool_level: Option<f32>,
+    position: Option<Position>,
+    battery: BatteryPolicy,
+    /// Set by the planner when tasks for this skill are waiting.
+    pending_work: bool,
+    home: Option<String>,
+    /// Reported with the last poll, for the station action ahead.
+    outcome: Option<Outcome>,
+    attempt: Attempt,
+    sender: Option<EventSender>,
 }
 
 impl Vehicle {
-    pub async fn new(id: i32, track_graph: Arc<Graph>) -> Self {
-        let skill = Skill::from_id(&id);
+    #[cfg(test)]
+    pub async fn new(id: i32, skill: Skill, track_graph: Arc<Graph>) -> Self {
+        Self::with_config(id, skill, track_graph, Config::default()).await
+    }
+
+    pub async fn with_config(
+        id: i32,
+        skill: Skill,
+        track_graph: Arc<Graph>,
+        config: Config,
+    ) -> Self {
         let state = Arc::new(RwLock::new(State::Offline));
+        let battery = config.battery.policy(id, &skill);
         Self {
             id,
-            overtime: Timeout::new(state.clone()),
+            overtime: Timeout::new(id, state.clone(), config.online_timeout),
             state,
             skill,
             track_graph,
             node: None,
+            waiting_for: None,
             current_task_id: None,
+            battery_level: None,
+            tool_level: None,
+            position: None,
+            battery,
+            pending_work: false,
+            home: None,
+            outcome: None,
+            attempt: Attempt::default(),
             sender: None,
         }
     }
 
-    pub fn set_event_sender(&mut self, sender: mpsc::Sender<Event>) {
-        self.sender = Some(sender)
+    pub fn set_battery_policy(&mut self, battery: BatteryPolicy) {
+        self.battery = battery;
+    }
+
+    /// Parking node the vehicle goes to whenever it is free, instead of the nearest one.
+    pub fn set_home(&mut self, home: Option<String>) {
+        self.home = home;
     }
 
-    pub async fn offline(&mut self) {
+    pub fn task_id(&self) -> Option<i32> {
+        self.current_task_id
+    }
+
+    /// Gives back everything the vehicle holds on the track before it is dropped.
+    pub async fn retire(&mut self) {
+        self.track_graph.release_reservations(self.id).await;
+        self.track_graph.release_zones(self.id, None).await;
+        if let Err(e) = self.track_graph.release_locks(self.id).await {
+            error!("vehicle({}): release node locks error: {:?}.", self.id, e);
+        }
         *self.state.write().await = State::Offline;
     }
 
+    pub async fn set_event_sender(&mut self, sender: EventSender) {
+        self.overtime.set_event_sender(sender.clone()).await;
+        self.sender = Some(sender)
+    }
+
     async fn initing(&self, current_position: &Position, state: &mut State) -> Result<()> {
         if let State::Offline = state {
         } else {
@@ -100,45 +326,89 @@ impl Vehicle {
             return Err(Error::State);
         }
 
-        let shortest_node = self
+        // Whatever was held before going offline is stale, the sequence starts over.
+        for lock in self
             .track_graph
-            .find_shortest_node(current_position).await
-            .map_err(|e| {
-                error!(
-                    "vehicle({}): find shortest node path error in initing. error type: {:?}.current position is {:?}.",
-                    self.id, e,current_position
-                );
-                Error::Db(e)
-            })?;
-        let shortest_node_to_shipping_dock_path = self
+            .release_locks(self.id)
+            .await
+            .map_err(Error::Track)?
+        {
+            info!(
+                "vehicle({}): released stale lock on node {}.",
+                self.id, lock.node_id
+            );
+        }
+        self.track_graph.release_reservations(self.id).await;
+        self.track_graph.release_zones(self.id, None).await;
+
+        let snap = self
             .track_graph
-            .find_shipping_dock_path(&shortest_node.name)
+            .snap(current_position)
             .await
-            .map_err(|e| {
+            .ok_or_else(|| {
                 error!(
-                    "vehicle({}): find shortest node to shipping dock path error in initing. error type: {:?}.current position is {:?}.",
-                    self.id, e,current_position
+                    "vehicle({}): no track near current position {:?} in initing.",
+                    self.id, current_position
                 );
-                Error::Db(e)
+                Error::NotInTrackGraph
             })?;
-        let mut actions = ActionSequenceBuilder::new().move_to(shortest_node.clone());
+        let mut actions = ActionSequenceBuilder::new();
         match self.skill {
             Skill::Item => {
-                actions = actions
-                    .move_path(&shortest_node_to_shipping_dock_path)
-                    .drop();
+                let (entry, path) = self.entry_to_shipping_dock(&snap, current_position).await?;
+                actions = actions.move_to(entry).move_path(&path).drop();
             }
             Skill::Fluid => {
-                actions = actions
-                    .move_path(&shortest_node_to_shipping_dock_path)
-                    .fill();
+                let (entry, path) = self.entry_to_shipping_dock(&snap, current_position).await?;
+                actions = actions.move_to(entry).move_path(&path).fill();
+            }
+            _ => {
+                let entry = snap.entries.first().ok_or(Error::NotInTrackGraph)?;
+                actions = actions.move_to(entry.clone());
             }
-            _ => (),
         }
-        *state = State::Initing(actions.build());
+        let actions = actions.build();
+        Self::reserve_new(&self.track_graph, self.id, &None, &actions).await;
+        *state = State::Initing(actions);
         Ok(())
     }
 
+    /// The end of the edge the vehicle stands on that gets it to the shipping dock first.
+    async fn entry_to_shipping_dock(
+        &self,
+        snap: &track::Snap,
+        current_position: &Position,
+    ) -> Result<(Arc<track::Node>, track::Path)> {
+        let mut best: Option<(TimeDelta, Arc<track::Node>, track::Path)> = None;
+        let mut last_error = None;
+        for entry in &snap.entries {
+            match self.track_graph.find_shipping_dock_path(&entry.name).await {
+                Ok(path) => {
+                    let time =
+                        snap.travel_time_to(entry) + self.track_graph.travel_time(&path).await;
+                    if best
+                        .as_ref()
+                        .is_none_or(|(best_time, ..)| time < *best_time)
+                    {
+                        best = Some((time, entry.clone(), path));
+                    }
+                }
+                Err(e) => last_error = Some(e),
+            }
+        }
+        match (best, last_error) {
+            (Some((_, entry, path)), _) => Ok((entry, path)),
+            (None, Some(e)) => {
+                error!(
+                    "vehicle({}): find shipping dock path error in initing. error type: {:?}.current position is {:?}.",
+                    self.id, e, current_position
+                );
+                Err(Error::Track(e))
+            }
+            (None, None) => Err(Error::NotInTrackGraph),
+        }
+    }
+
     pub fn node(&self) -> Result<Arc<track::Node>> {
         self.node.clone().ok_or(Error::NotInTrackGraph)
     }
@@ -147,6 +417,143 @@ impl Vehicle {
         &self.skill
     }
 
+    /// The vehicle this one is held by on the track.
+    pub fn waiting_for(&self) -> Option<i32> {
+        self.waiting_for
+    }
+
+    /// Who gives way first in a deadlock, low battery goes before work before everything else.
+    pub async fn priority(&self) -> u8 {
+        match *self.state.read().await {
+            State::Charging(_) => 3,
+            State::Processing(_) => 2,
+            State::Initing(_) | State::Unloading(_) => 1,
+            _ => 0,
+        }
+    }
+
+    /// Breaks a deadlock this vehicle is part of, by taking another way to the end of the current
+    /// leg if there is one, or else by going to the back of the reservation queue. Either way the
+    /// forks and lifts ahead it already holds are let go.
+    pub async fn give_way(&mut self) -> GiveWay {
+        self.waiting_for = None;
+        self.track_graph
+            .release_zones(self.id, self.node.as_ref().map(|node| node.id))
+            .await;
+        let mut state = self.state.write().await;
+        let (State::Initing(actions)
+        | State::Charging(actions)
+        | State::Parking(actions)
+        | State::Processing(actions)
+        | State::Unloading(actions)) = &mut *state
+        else {
+            return GiveWay::BackedOff;
+        };
+        self.track_graph.release_reservations(self.id).await;
+
+        if let Some(landmark) = &self.node
+            && let Some(end) = actions.leg_end()
+            && let Some(Action::Move(blocked)) = actions.next_action()
+            && let Ok(path) = self
+                .track_graph
+                .find_timed_path(self.id, &landmark.name, &end.name, Utc::now())
+                .await
+            && path.get(1).is_some_and(|next| next.id != blocked.id)
+        {
+            actions.reroute(&path);
+            Self::reserve(&self.track_graph, self.id, &self.node, actions).await;
+            return GiveWay::Rerouted;
+        }
+
+        Self::reserve(&self.track_graph, self.id, &self.node, actions).await;
+        GiveWay::BackedOff
+    }
+
+    /// Routes every leg ahead again from the current node when the sequence crosses disabled
+    /// track, the station actions stay in place. Returns whether the sequence changed.
+    pub async fn reroute_disabled(&mut self) -> Result<bool> {
+        let mut state = self.state.write().await;
+        let (State::Initing(actions)
+        | State::Charging(actions)
+        | State::Parking(actions)
+        | State::Processing(actions)
+        | State::Unloading(actions)) = &mut *state
+        else {
+            return Ok(false);
+        };
+        let route: Vec<Arc<track::Node>> = actions
+            .route(self.node.clone())
+            .into_iter()
+            .map(|(node, _)| node)
+            .collect();
+        if !self.track_graph.crosses_disabled(&route).await {
+            return Ok(false);
+        }
+
+        let mut from = self.node()?;
+        let mut builder = ActionSequenceBuilder::new();
+        for (end, work) in actions.legs() {
+            if let Some(end) = end {
+                let path = self
+                    .track_graph
+                    .find_timed_path(self.id, &from.name, &end.name, Utc::now())
+                    .await
+                    .map_err(Error::Track)?;
+                builder = builder.move_path(&path);
+                from = end;
+            }
+            for action in work {
+                builder = builder.action(action);
+            }
+        }
+        *actions = builder.build();
+        Self::reserve(&self.track_graph, self.id, &self.node, actions).await;
+        info!("vehicle({}): rerouted around disabled track.", self.id);
+        Ok(true)
+    }
+
+    pub fn set_tool_level(&mut self, tool_level: Option<f32>) {
+        self.tool_level = tool_level;
+    }
+
+    pub fn set_outcome(&mut self, outcome: Option<Outcome>) {
+        self.outcome = outcome;
+    }
+
+    pub async fn status(&self) -> Status {
+        Status {
+            id: self.id,
+            skill: self.skill.clone(),
+            state: self.state.read().await.name(),
+            node: self.node.as_ref().map(|node| node.name.clone()),
+            battery_level: self.battery_level,
+            tool_level: self.tool_level,
+            task_id: self.current_task_id,
+            waiting_for: self.waiting_for,
+        }
+    }
+
+    pub async fn overlay(&self) -> track::VehicleOverlay {
+        let route = match &*self.state.read().await {
+            State::Initing(actions)
+            | State::Charging(actions)
+            | State::Parking(actions)
+            | State::Processing(actions)
+            | State::Unloading(actions) => actions
+                .route(self.node.clone())
+                .into_iter()
+                .map(|(node, _)| node)
+                .collect(),
+            _ => Vec::new(),
+        };
+        track::VehicleOverlay {
+            vehicle_id: self.id,
+            node: self.node.clone(),
+            route,
+        }
+    }
+
+    /// Free to take a task, a charging vehicle once it is charged enough to be pulled away.
     pub async fn idle(&self) -> bool {
         match *self.state.read().await {
             State::InitDone
@@ -154,10 +561,23 @@ impl Vehicle {
             | State::ProcessDone
             | State::ParkDone
             | State::Parking(_) => true,
-            State::Initing(_) | State::Charging(_) | State::Processing(_) | State::Offline => false,
+            State::Charging(_) => self
+                .battery_level
+                .is_some_and(|level| level >= self.battery.interruptible),
+            State::Initing(_) | State::Processing(_) | State::Unloading(_) | State::Offline => {
+                false
+            }
         }
     }
 
+    pub async fn is_charging(&self) -> bool {
+        matches!(*self.state.read().await, State::Charging(_))
+    }
+
+    pub fn set_pending_work(&mut self, pending_work: bool) {
+        self.pending_work = pending_work;
+    }
+
     fn next_action(
         current_position: &Position,
         landmark: &mut Option<Arc<track::Node>>,
@@ -173,6 +593,8 @@ impl Vehicle {
                     Some(actions.next_action()?.clone())
                 }
             }
+            // Stays ahead until the lift is granted in `step`.
+            Action::WaitForLift(node) => Some(Action::WaitForLift(node.clone())),
             _ => {
                 actions.pop_next_action();
                 actions.next_action().cloned()
@@ -180,38 +602,234 @@ impl Vehicle {
         }
     }
 
+    /// `next_action`, holding the vehicle while the way ahead is reserved by someone else.
+    async fn step(
+        track_graph: &Graph,
+        vehicle_id: i32,
+        current_position: &Position,
+        landmark: &mut Option<Arc<track::Node>>,
+        waiting_for: &mut Option<i32>,
+        actions: &mut ActionSequence,
+    ) -> Option<Action> {
+        *waiting_for = None;
+        let previous = landmark.as_ref().map(|node| node.id);
+        let Some(mut action) = Self::next_action(current_position, landmark, actions) else {
+            track_graph.release_reservations(vehicle_id).await;
+            track_graph.release_zones(vehicle_id, None).await;
+            return None;
+        };
+        if landmark.as_ref().map(|node| node.id) != previous {
+            // Left the zone behind once the next node is reached.
+            track_graph
+                .release_zones(vehicle_id, landmark.as_ref().map(|node| node.id))
+                .await;
+            Self::reserve(track_graph, vehicle_id, landmark, actions).await;
+        }
+        if let Action::WaitForLift(lift) = &action {
+            if let Some(rider) = track_graph.acquire_zone(vehicle_id, lift.id).await {
+                Self::reserve(track_graph, vehicle_id, landmark, actions).await;
+                *waiting_for = Some(rider);
+                return Some(action);
+            }
+            actions.pop_next_action();
+            action = actions.next_action()?.clone();
+        }
+        if let Action::Move(to) = &action
+            && landmark
+                .as_ref()
+                .is_none_or(|from| current_position == &from.position)
+        {
+            let blocker = match landmark {
+                Some(from) => track_graph.blocker(vehicle_id, from.id, to.id).await,
+                None => None,
+            };
+            // A fork or lift is entered only once it is held.
+            let blocker = match blocker {
+                Some(blocker) => Some(blocker),
+                None => track_graph.acquire_zone(vehicle_id, to.id).await,
+            };
+            if let Some(blocker) = blocker {
+                // Running late, push the remaining windows back.
+                Self::reserve(track_graph, vehicle_id, landmark, actions).await;
+                *waiting_for = Some(blocker);
+                return Some(Action::Wait);
+            }
+        }
+        Some(action)
+    }
+
+    /// Weighs what the vehicle reported for the station action ahead, a vehicle that reports
+    /// nothing is taken at its word. A failed action stays ahead until its retries are used up.
+    fn settle(
+        vehicle_id: i32,
+        outcome: Option<Outcome>,
+        attempt: &mut Attempt,
+        actions: &mut ActionSequence,
+    ) -> Settled {
+        let Some(action) = actions.next_action() else {
+            return Settled::Done;
+        };
+        let (name, station) = match action {
+            Action::Drop(station) => ("drop", station),
+            Action::Suck(station) => ("suck", station),
+            Action::Fill(station) => ("fill", station),
+            Action::Use(station) => ("use", station),
+            _ => return Settled::Done,
+        };
+        let pickup = matches!(action, Action::Suck(_));
+        let node = station
+            .node
+            .as_ref()
+            .map_or("-".to_string(), |node| node.name.clone());
+        let outcome = match outcome {
+            None | Some(Outcome::Success) => {
+                *attempt = Attempt::default();
+                return Settled::Done;
+            }
+            Some(outcome) => outcome,
+        };
+        if let Outcome::Partial { count, .. } = outcome {
+            attempt.moved += count;
+            // Only what is still missing is asked for again.
+            if let Some(station) = actions.next_station_mut() {
+                station.cargo.shorten(count);
+                if station.cargo.quantity == Some(0) {
+                    *attempt = Attempt::default();
+                    return Settled::Done;
+                }
+            }
+        }
+        if attempt.retries < constant::VEHICLE_ACTION_RETRIES {
+            attempt.retries += 1;
+            warn!(
+                "vehicle({}): {} at {} {}, retry {}.",
+                vehicle_id, name, node, outcome, attempt.retries
+            );
+            return match actions.next_action() {
+                Some(action) => Settled::Retry(action.clone()),
+                None => Settled::Done,
+            };
+        }
+        let moved = std::mem::take(attempt).moved;
+        // Whatever was picked up is still worth delivering.
+        if pickup && moved > 0 {
+            warn!(
+                "vehicle({}): suck at {} {}, delivering the {} aboard.",
+                vehicle_id, node, outcome, moved
+            );
+            let missing = actions
+                .next_station_mut()
+                .and_then(|station| station.cargo.quantity)
+                .map_or(0, |missing| missing as u32);
+            let shortfall = match missing {
+                0 => format!("{} picked up, suck at {} {}", moved, node, outcome),
+                missing => format!(
+                    "{} of {} picked up, suck at {} {}",
+                    moved,
+                    moved + missing,
+                    node,
+                    outcome
+                ),
+            };
+            actions.shorten_delivery(missing, shortfall);
+            return Settled::Done;
+        }
+        Settled::GaveUp(format!("{} at {} {}", name, node, outcome))
+    }
+
+    async fn reserve(
+        track_graph: &Graph,
+        vehicle_id: i32,
+        landmark: &Option<Arc<track::Node>>,
+        actions: &ActionSequence,
+    ) {
+        track_graph
+            .reserve(vehicle_id, &actions.route(landmark.clone()), Utc::now())
+            .await;
+    }
+
+    /// A new sequence queues up behind everyone already on the track.
+    async fn reserve_new(
+        track_graph: &Graph,
+        vehicle_id: i32,
+        landmark: &Option<Arc<track::Node>>,
+        actions: &ActionSequence,
+    ) {
+        track_graph.release_reservations(vehicle_id).await;
+        Self::reserve(track_graph, vehicle_id, landmark, actions).await;
+    }
+
+    async fn lock_node(&self, node_id: i32) -> Result<()> {
+        if self
+            .track_graph
+            .lock_node(node_id, self.id)
+            .await
+            .map_err(Error::Track)?
+        {
+            Ok(())
+        } else {
+            error!(
+                "vehicle({}): node {} is locked by another vehicle.",
+                self.id, node_id
+            );
+            Err(Error::NodeLocked(node_id))
+        }
+    }
+
+    async fn unlock_node(&self, node_id: i32) -> Result<()> {
+        if !self
+            .track_graph
+            .unlock_node(node_id, self.id)
+            .await
+            .map_err(Error::Track)?
+        {
+            warn!(
+                "vehicle({}): node {} is not locked by this vehicle.",
+                self.id, node_id
+            );
+        }
+        Ok(())
+    }
+
+    async fn home_path(&self, from: &str) -> Option<track::Path> {
+        let home = self.home.as_deref()?;
+        match self.track_graph.find_home_path(from, home).await {
+            Ok(path) => Some(path),
+            Err(e) => {
+                info!("vehicle({}): home {} not available, {}.", self.id, home, e);
+                None
+            }
+        }
+    }
+
     async fn parking(&self, state: &mut State) -> Result<()> {
         if let State::ChargeDone = *state {
-            self.track_graph
-                .unlock_node(self.node()?.id)
-                .await
-                .map_err(Error::Db)?;
+            self.unlock_node(self.node()?.id).await?;
         }
         match *state {
             State::ChargeDone | State::ProcessDone | State::InitDone => {
-                let path = self
-                    .track_graph
-                    .find_parking_path(
-                        &self
-                            .node()
-                            .map_err(|e| {
-                                error!(
-                                    "vehicle({}): find current node in parking. error type: {:?}.",
-                                    self.id, e
-                                );
-                                Error::NotInTrackGraph
-                            })?
-                            .name,
-                    )
-                    .await
-                    .map_err(Error::Db)?;
-                self.track_graph
-                    .lock_node(path.last().ok_or_else(|| {
-                        error!("vehicle({}): get parking node from path error. current state is {:?}.",self.id, self.state);
-                        Error::TrackGraph
-                    })?.id)
-                    .await.map_err(Error::Db)?;
+                let from = self.node().map_err(|e| {
+                    error!(
+                        "vehicle({}): find current node in parking. error type: {:?}.",
+                        self.id, e
+                    );
+                    Error::NotInTrackGraph
+                })?;
+                let path = match self.home_path(&from.name).await {
+                    Some(path) => path,
+                    None => self
+                        .track_graph
+                        .find_parking_path(&from.name)
+                        .await
+                        .map_err(Error::Track)?,
+                };
+                self.lock_node(path.last().ok_or_else(|| {
+                    error!("vehicle({}): get parking node from path error. current state is {:?}.",self.id, self.state);
+                    Error::TrackGraph
+                })?.id)
+                .await?;
                 let actions = ActionSequenceBuilder::new().move_path(&path).build();
+                Self::reserve_new(&self.track_graph, self.id, &self.node, &actions).await;
                 *state = State::Parking(actions);
                 Ok(())
             }
@@ -226,14 +844,42 @@ impl Vehicle {
         }
     }
 
+    /// Back to the shipping dock to hand over what is aboard.
+    async fn unloading(&self, state: &mut State) -> Result<()> {
+        let path = self
+            .track_graph
+            .find_shipping_dock_path(&self.node()?.name)
+            .await
+            .map_err(Error::Track)?;
+        let actions = ActionSequenceBuilder::new().move_path(&path);
+        let actions = match self.skill {
+            Skill::Fluid => actions.fill(),
+            _ => actions.drop(),
+        }
+        .build();
+        Self::reserve_new(&self.track_graph, self.id, &self.node, &actions).await;
+        *state = State::Unloading(actions);
+        Ok(())
+    }
+
+    /// Leaves the task, a vehicle still holding its cargo takes it to the shipping dock first.
+    async fn end_task(&self, state: &mut State, loaded: bool) -> Result<()> {
+        *state = State::ProcessDone;
+        let at_dock = self
+            .node
+            .as_ref()
+            .is_some_and(|node| node.node_type == track::NodeType::ShippingDock);
+        if loaded && !at_dock {
+            self.unloading(state).await?;
+        }
+        Ok(())
+    }
+
     async fn charging(&self, state: &mut State) -> Result<()> {
         if let State::Parking(actions) = state
             && let Some(node) = actions.last_move_node()
         {
-            self.track_graph
-                .unlock_node(node.id)
-                .await
-                .map_err(Error::Db)?;
+            self.unlock_node(node.id).await?;
         }
         match *state {
             State::ParkDone | State::Parking(_) | State::ProcessDone | State::InitDone => {
@@ -252,15 +898,18 @@ impl Vehicle {
                             .name,
                     )
                     .await
-                    .map_err(Error::Db)?;
-                self.track_graph
-                    .lock_node(path.last().ok_or_else(|| {
-                        error!("vehicle({}): get parking node from path error. current state is {:?}.",self.id, self.state);
-                        Error::TrackGraph
-                    })?.id)
-                    .await
-                    .map_err(Error::Db)?;
+                    .map_err(Error::Track)?;
+                self.lock_node(path.last().ok_or_else(|| {
+                    error!("vehicle({}): get parking node from path error. current state is {:?}.",self.id, self.state);
+                    Error::TrackGraph
+                })?.id)
+                .await?;
+                // The parking spot is only given up once a charger is taken.
+                if let State::ParkDone = *state {
+                    self.unlock_node(self.node()?.id).await?;
+                }
                 let actions = ActionSequenceBuilder::new().move_path(&path).build();
+                Self::reserve_new(&self.track_graph, self.id, &self.node, &actions).await;
                 *state = State::Charging(actions);
                 Ok(())
             }
@@ -279,24 +928,172 @@ impl Vehicle {
         &mut self,
         current_position: &Position,
         current_battery_level: f32,
+    ) -> Option<Action> {
+        let from = self.state.read().await.name();
+        let action = self.advance(current_position, current_battery_level).await;
+        let to = self.state.read().await.name();
+
+        if self.position.as_ref() != Some(current_position) {
+            self.position = Some(*current_position);
+            let event = Event::Moved {
+                vehicle_id: self.id,
+                position: current_position.into(),
+                node: self.node.as_ref().map(|node| node.name.clone()),
+            };
+            Self::send_event(&mut self.sender, event).await;
+        }
+        if from != to {
+            let event = Event::StateChanged {
+                vehicle_id: self.id,
+                from,
+                to,
+            };
+            Self::send_event(&mut self.sender, event).await;
+        }
+        action
+    }
+
+    async fn advance(
+        &mut self,
+        current_position: &Position,
+        current_battery_level: f32,
     ) -> Option<Action> {
         self.overtime.update().await;
-        let require_charge = current_battery_level <= 0.3;
+        self.battery_level = Some(current_battery_level);
+        if let Err(e) = self.track_graph.renew_locks(self.id).await {
+            error!("vehicle({}): renew node locks error: {:?}.", self.id, e);
+        }
+        let require_charge = current_battery_level <= self.battery.charge;
+        let mut outcome = self.outcome.take();
         let mut state = self.state.write().await;
         loop {
             match &mut *state {
                 State::Initing(actions) => {
-                    let action = Self::next_action(current_position, &mut self.node, actions);
+                    match Self::settle(self.id, outcome.take(), &mut self.attempt, actions) {
+                        Settled::Done => {}
+                        Settled::Retry(action) => return Some(action),
+                        Settled::GaveUp(reason) => {
+                            error!("vehicle({}): {} in initing, moving on.", self.id, reason)
+                        }
+                    }
+                    let action = Self::step(
+                        &self.track_graph,
+                        self.id,
+                        current_position,
+                        &mut self.node,
+                        &mut self.waiting_for,
+                        actions,
+                    )
+                    .await;
                     if action.is_some() {
                         return action;
                     }
                     *state = State::InitDone;
                 }
+                State::Unloading(actions) => {
+                    match Self::settle(self.id, outcome.take(), &mut self.attempt, actions) {
+                        Settled::Done => {}
+                        Settled::Retry(action) => return Some(action),
+                        Settled::GaveUp(reason) => {
+                            error!("vehicle({}): {} in unloading, moving on.", self.id, reason)
+                        }
+                    }
+                    let action = Self::step(
+                        &self.track_graph,
+                        self.id,
+                        current_position,
+                        &mut self.node,
+                        &mut self.waiting_for,
+                        actions,
+                    )
+                    .await;
+                    if action.is_some() {
+                        return action;
+                    }
+                    *state = State::ProcessDone;
+                }
                 State::Processing(actions) => {
-                    let action = Self::next_action(current_position, &mut self.node, actions);
+                    let settled = Self::settle(self.id, outcome.take(), &mut self.attempt, actions);
+                    if current_battery_level <= self.battery.critical {
+                        if let Settled::Done = settled
+                            && actions.next_station_mut().is_some()
+                        {
+                            // The station action just reported is behind the vehicle.
+                            actions.pop_next_action();
+                        }
+                        self.attempt = Attempt::default();
+                        let (loaded, delivered) = (actions.loaded(), actions.delivered());
+                        let task_id = self.current_task_id.take().unwrap();
+                        warn!(
+                            "vehicle({}): battery critical at {}, task {} dropped to charge.",
+                            self.id, current_battery_level, task_id
+                        );
+                        let event = if delivered {
+                            Event::ProcessDone {
+                                vehicle_id: self.id,
+                                vehicle_skill: self.skill.clone(),
+                                task_id,
+                                shortfall: actions.shortfall().map(str::to_string),
+                            }
+                        } else {
+                            // Nothing picked up yet, another vehicle can take it over.
+                            Event::ProcessAborted {
+                                vehicle_id: self.id,
+                                vehicle_skill: self.skill.clone(),
+                                task_id,
+                                requeue: !loaded,
+                                reason: "battery critical".to_string(),
+                            }
+                        };
+                        Self::send_event(&mut self.sender, event).await;
+                        self.end_task(&mut state, loaded).await.ok()?;
+                        continue;
+                    }
+                    match settled {
+                        Settled::Done => {}
+                        Settled::Retry(action) => return Some(action),
+                        Settled::GaveUp(reason) => {
+                            let (loaded, delivered) = (actions.loaded(), actions.delivered());
+                            let task_id = self.current_task_id.take().unwrap();
+                            let event = if delivered {
+                                warn!(
+                                    "vehicle({}): {} after task {} was delivered.",
+                                    self.id, reason, task_id
+                                );
+                                Event::ProcessDone {
+                                    vehicle_id: self.id,
+                                    vehicle_skill: self.skill.clone(),
+                                    task_id,
+                                    shortfall: actions.shortfall().map(str::to_string),
+                                }
+                            } else {
+                                warn!("vehicle({}): task {} failed, {}.", self.id, task_id, reason);
+                                Event::ProcessAborted {
+                                    vehicle_id: self.id,
+                                    vehicle_skill: self.skill.clone(),
+                                    task_id,
+                                    requeue: false,
+                                    reason,
+                                }
+                            };
+                            Self::send_event(&mut self.sender, event).await;
+                            self.end_task(&mut state, loaded).await.ok()?;
+                            continue;
+                        }
+                    }
+                    let action = Self::step(
+                        &self.track_graph,
+                        self.id,
+                        current_position,
+                        &mut self.node,
+                        &mut self.waiting_for,
+                        actions,
+                    )
+                    .await;
                     if action.is_some() {
                         return action;
                     }
+                    let shortfall = actions.shortfall().map(str::to_string);
                     *state = State::ProcessDone;
                     Self::send_event(
                         &mut self.sender,
@@ -304,6 +1101,7 @@ impl Vehicle {
                             vehicle_id: self.id,
                             vehicle_skill: self.skill.clone(),
                             task_id: self.current_task_id.unwrap(),
+                            shortfall,
                         },
                     )
                     .await;
@@ -311,9 +1109,23 @@ impl Vehicle {
                 State::Parking(actions) => {
                     if require_charge {
                         self.charging(&mut state).await.ok()?;
-                        Self::send_event(&mut self.sender, Event::ChargeStart).await;
+                        Self::send_event(
+                            &mut self.sender,
+                            Event::ChargeStart {
+                                vehicle_id: self.id,
+                            },
+                        )
+                        .await;
                     } else {
-                        let action = Self::next_action(current_position, &mut self.node, actions);
+                        let action = Self::step(
+                            &self.track_graph,
+                            self.id,
+                            current_position,
+                            &mut self.node,
+                            &mut self.waiting_for,
+                            actions,
+                        )
+                        .await;
                         if action.is_some() {
                             return action;
                         }
@@ -321,14 +1133,28 @@ impl Vehicle {
                     }
                 }
                 State::Charging(actions) => {
-                    let action = Self::next_action(current_position, &mut self.node, actions);
+                    let action = Self::step(
+                        &self.track_graph,
+                        self.id,
+                        current_position,
+                        &mut self.node,
+                        &mut self.waiting_for,
+                        actions,
+                    )
+                    .await;
                     if action.is_some() {
                         return action;
                     }
 
-                    if current_battery_level >= 0.95 {
+                    if current_battery_level >= self.battery.resume {
                         *state = State::ChargeDone;
-                        Self::send_event(&mut self.sender, Event::ChargeDone).await;
+                        Self::send_event(
+                            &mut self.sender,
+                            Event::ChargeDone {
+                                vehicle_id: self.id,
+                            },
+                        )
+                        .await;
                     } else {
                         return None;
                     }
@@ -336,7 +1162,13 @@ impl Vehicle {
                 State::InitDone => {
                     if require_charge {
                         self.charging(&mut state).await.ok()?;
-                        Self::send_event(&mut self.sender, Event::ChargeStart).await;
+                        Self::send_event(
+                            &mut self.sender,
+                            Event::ChargeStart {
+                                vehicle_id: self.id,
+                            },
+                        )
+                        .await;
                     } else {
                         self.parking(&mut state).await.ok()?;
                     }
@@ -348,31 +1180,47 @@ impl Vehicle {
                     self.current_task_id = None;
                     if require_charge {
                         self.charging(&mut state).await.ok()?;
-                        Self::send_event(&mut self.sender, Event::ChargeStart).await;
+                        Self::send_event(
+                            &mut self.sender,
+                            Event::ChargeStart {
+                                vehicle_id: self.id,
+                            },
+                        )
+                        .await;
                     } else {
                         self.parking(&mut state).await.ok()?;
                     }
                 }
                 State::ParkDone => {
-                    if require_charge {
+                    let top_up =
+                        !self.pending_work && current_battery_level <= self.battery.opportunistic;
+                    if require_charge || top_up {
+                        // Stays parked while every charger is taken.
                         self.charging(&mut state).await.ok()?;
-                        Self::send_event(&mut self.sender, Event::ChargeStart).await;
+                        Self::send_event(
+                            &mut self.sender,
+                            Event::ChargeStart {
+                                vehicle_id: self.id,
+                            },
+                        )
+                        .await;
                     } else {
                         return None;
                     }
                 }
                 State::Offline => {
+                    self.waiting_for = None;
                     self.initing(current_position, &mut state).await.ok()?;
                 }
             }
         }
     }
 
-    async fn send_event(sender: &mut Option<mpsc::Sender<Event>>, event: Event) {
-        if let Some(event_sender) = sender {
-            if let Err(_) = event_sender.send(event).await {
-                *sender = None;
-            }
+    async fn send_event(sender: &mut Option<EventSender>, event: Event) {
+        if let Some(event_sender) = sender
+            && event_sender.send(event).await.is_err()
+        {
+            *sender = None;
         }
     }
 
@@ -380,10 +1228,8 @@ impl Vehicle {
         let mut state = self.state.write().await;
         match &*state {
             State::ParkDone | State::ChargeDone | State::ProcessDone | State::InitDone => {
-                self.track_graph
-                    .unlock_node(self.node()?.id)
-                    .await
-                    .map_err(Error::Db)?;
+                self.unlock_node(self.node()?.id).await?;
+                Self::reserve_new(&self.track_graph, self.id, &self.node, &actions).await;
                 *state = State::Processing(actions);
                 Self::send_event(
                     &mut self.sender,
@@ -397,13 +1243,24 @@ impl Vehicle {
                 self.current_task_id = Some(task_id);
                 Ok(())
             }
-            State::Parking(parking_actions) => {
-                if let Some(node) = parking_actions.last_move_node() {
-                    self.track_graph
-                        .unlock_node(node.id)
-                        .await
-                        .map_err(Error::Db)?;
+            State::Parking(moving) | State::Charging(moving) => {
+                if let State::Charging(_) = &*state {
+                    info!(
+                        "vehicle({}): pulled off charging for task {}.",
+                        self.id, task_id
+                    );
+                    Self::send_event(
+                        &mut self.sender,
+                        Event::ChargeDone {
+                            vehicle_id: self.id,
+                        },
+                    )
+                    .await;
+                }
+                if let Some(node) = moving.last_move_node().or_else(|| self.node.clone()) {
+                    self.unlock_node(node.id).await?;
                 }
+                Self::reserve_new(&self.track_graph, self.id, &self.node, &actions).await;
                 *state = State::Processing(actions);
                 Self::send_event(
                     &mut self.sender,
@@ -417,9 +1274,9 @@ impl Vehicle {
                 self.current_task_id = Some(task_id);
                 Ok(())
             }
-            State::Initing(_) | State::Processing(_) | State::Charging(_) | State::Offline => {
+            State::Initing(_) | State::Processing(_) | State::Unloading(_) | State::Offline => {
                 error!(
-                    "vehicle({}): state error before processing. current status is {:?}, expect ParkDone|Parking|ProcessDone|ChargeDone|InitDone",
+                    "vehicle({}): state error before processing. current status is {:?}, expect ParkDone|Parking|Charging|ProcessDone|ChargeDone|InitDone",
                     self.id,
                     self.state.read().await
                 );
@@ -437,24 +1294,630 @@ impl fmt::Debug for Vehicle {
 
 #[cfg(test)]
 mod tests {
-    use dotenvy::dotenv;
-    use sqlx::postgres::PgPoolOptions;
-
-    use crate::db_manager::DbManager;
+    use crate::transport::track::{Layout, MemoryStore, sample_layout};
 
     use super::*;
 
     async fn get_track_graph() -> Graph {
-        dotenv().ok();
-        let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
-        println!("Connecting to database: {}", database_url);
-        let pool = PgPoolOptions::new()
-            .max_connections(5)
-            .connect(&database_url)
-            .await
-            .expect("Failed to create pool");
-        let db_manaer = DbManager::new(pool);
-        Graph::new(db_manaer).await
+        let (nodes, edges) = sample_layout();
+        Graph::new(MemoryStore::new(nodes, edges)).await.unwrap()
+    }
+
+    /// Every event on the feed, task events also stay queued on the second receiver.
+    fn event_sender() -> (
+        EventSender,
+        broadcast::Receiver<Event>,
+        mpsc::Receiver<Event>,
+    ) {
+        let (feed, receiver) = broadcast::channel(64);
+        let (persist, persisted) = mpsc::channel(64);
+        (EventSender::new(feed, persist), receiver, persisted)
+    }
+
+    #[tokio::test]
+    async fn init_on_edge() {
+        let track_graph = Arc::new(get_track_graph().await);
+        let mut vehicle = Vehicle::new(2000, Skill::Item, track_graph.clone()).await;
+
+        // A3 is the nearest node but A3 -> A2 is one-way, so drive on to A2.
+        assert!(
+            matches!(vehicle.get_action(&(1.0, 1.7, 0.0).into(), 1.0).await.unwrap(), Action::Move(node) if node.name == "A2")
+        );
+        assert!(
+            matches!(vehicle.get_action(&(1.0, 1.0, 0.0).into(), 1.0).await.unwrap(), Action::Move(node) if node.name == "A1")
+        );
+    }
+
+    #[tokio::test]
+    async fn status() {
+        let track_graph = Arc::new(get_track_graph().await);
+        let mut vehicle = Vehicle::new(4000, Skill::Fluid, track_graph).await;
+        let status = vehicle.status().await;
+        assert_eq!((status.state, status.battery_level), ("offline", None));
+
+        vehicle.set_tool_level(Some(0.5));
+        vehicle.get_action(&(-1.0, 2.0, 0.0).into(), 0.8).await;
+        let status = vehicle.status().await;
+        assert_eq!(status.skill, Skill::Fluid);
+        assert_eq!(status.node.as_deref(), Some("S3"));
+        assert_eq!(status.battery_level, Some(0.8));
+        assert_eq!(status.tool_level, Some(0.5));
+        assert_eq!(status.task_id, None);
+    }
+
+    #[tokio::test]
+    async fn events() {
+        let track_graph = Arc::new(get_track_graph().await);
+        let mut vehicle = Vehicle::new(2000, Skill::Item, track_graph).await;
+        let (sender, mut receiver, _persisted) = event_sender();
+        vehicle.set_event_sender(sender).await;
+
+        vehicle.get_action(&(2.0, 4.0, 0.0).into(), 1.0).await;
+        assert!(matches!(
+            receiver.try_recv().unwrap(),
+            Event::Moved {
+                vehicle_id: 2000,
+                position: (2.0, 4.0, 0.0),
+                node: None
+            }
+        ));
+        assert!(matches!(
+            receiver.try_recv().unwrap(),
+            Event::StateChanged {
+                vehicle_id: 2000,
+                from: "offline",
+                to: "initing"
+            }
+        ));
+
+        // Same place and state, nothing to tell.
+        vehicle.get_action(&(2.0, 4.0, 0.0).into(), 1.0).await;
+        assert!(receiver.try_recv().is_err());
+
+        let event = Event::ChargeStart { vehicle_id: 2000 };
+        assert_eq!(
+            serde_json::to_value(&event).unwrap(),
+            serde_json::json!({"type": "charge_start", "vehicle_id": 2000})
+        );
+    }
+
+    #[tokio::test]
+    async fn critical_battery() {
+        let track_graph = Arc::new(get_track_graph().await);
+        let mut vehicle = Vehicle::new(2000, Skill::Item, track_graph.clone()).await;
+        let (sender, mut receiver, _persisted) = event_sender();
+        vehicle.set_event_sender(sender).await;
+        for _ in 0..3 {
+            vehicle.get_action(&(-1.0, 2.0, 0.0).into(), 1.0).await;
+        }
+        assert!(vehicle.idle().await);
+
+        let path = track_graph.find_path("S3", "S2").await.unwrap();
+        let actions = ActionSequenceBuilder::new().move_path(&path).suck().build();
+        vehicle.processing(7, actions).await.unwrap();
+        vehicle.get_action(&(-1.0, 2.0, 0.0).into(), 0.05).await;
+        let status = vehicle.status().await;
+        assert_eq!((status.state, status.task_id), ("charging", None));
+
+        let mut aborted = None;
+        while let Ok(event) = receiver.try_recv() {
+            if let Event::ProcessAborted {
+                task_id, requeue, ..
+            } = event
+            {
+                aborted = Some((task_id, requeue));
+            }
+        }
+        assert_eq!(aborted, Some((7, true)));
+    }
+
+    /// A fluid task picks up at S2, fills at S1 and drops the empties at the S3 dock.
+    async fn fluid_task(track_graph: &Graph, vehicle: &mut Vehicle) {
+        let to_begin = track_graph.find_path("S3", "S2").await.unwrap();
+        let to_end = track_graph.find_path("S2", "S1").await.unwrap();
+        let to_dock = track_graph.find_path("S1", "S3").await.unwrap();
+        let actions = ActionSequenceBuilder::new()
+            .move_path(&to_begin)
+            .suck()
+            .move_path(&to_end)
+            .fill()
+            .move_path(&to_dock)
+            .drop()
+            .build();
+        vehicle.processing(7, actions).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn critical_battery_loaded() {
+        // Picked up, the cargo goes back to the dock before charging.
+        let track_graph = Arc::new(get_track_graph().await);
+        let mut vehicle = Vehicle::new(4000, Skill::Fluid, track_graph.clone()).await;
+        let (sender, mut receiver, _persisted) = event_sender();
+        vehicle.set_event_sender(sender).await;
+        let mut position: Position = (-1.0, 2.0, 0.0).into();
+        for _ in 0..3 {
+            vehicle.get_action(&position, 1.0).await;
+        }
+        fluid_task(&track_graph, &mut vehicle).await;
+        assert!(matches!(
+            drive(&mut vehicle, &mut position).await,
+            Some(Action::Suck(_))
+        ));
+        vehicle.set_outcome(Some(Outcome::Success));
+        vehicle.get_action(&position, 0.05).await;
+        let status = vehicle.status().await;
+        assert_eq!((status.state, status.task_id), ("unloading", None));
+        let mut aborted = None;
+        while let Ok(event) = receiver.try_recv() {
+            if let Event::ProcessAborted {
+                task_id, requeue, ..
+            } = event
+            {
+                aborted = Some((task_id, requeue));
+            }
+        }
+        assert_eq!(aborted, Some((7, false)));
+
+        // Filled, only the way back is left and the task counts as done.
+        let track_graph = Arc::new(get_track_graph().await);
+        let mut vehicle = Vehicle::new(4000, Skill::Fluid, track_graph.clone()).await;
+        let (sender, mut receiver, _persisted) = event_sender();
+        vehicle.set_event_sender(sender).await;
+        let mut position: Position = (-1.0, 2.0, 0.0).into();
+        for _ in 0..3 {
+            vehicle.get_action(&position, 1.0).await;
+        }
+        fluid_task(&track_graph, &mut vehicle).await;
+        drive(&mut vehicle, &mut position).await;
+        vehicle.set_outcome(Some(Outcome::Success));
+        assert!(matches!(
+            drive(&mut vehicle, &mut position).await,
+            Some(Action::Fill(_))
+        ));
+        vehicle.set_outcome(Some(Outcome::Success));
+        vehicle.get_action(&position, 0.05).await;
+        let status = vehicle.status().await;
+        assert_eq!((status.state, status.task_id), ("charging", None));
+        let mut ended = Vec::new();
+        while let Ok(event) = receiver.try_recv() {
+            match event {
+                Event::ProcessDone { task_id, .. } => ended.push((task_id, "done")),
+                Event::ProcessAborted { task_id, .. } => ended.push((task_id, "aborted")),
+                _ => {}
+            }
+        }
+        assert_eq!(ended, [(7, "done")]);
+    }
+
+    /// Follows the moves handed out until something else comes back.
+    async fn drive(vehicle: &mut Vehicle, position: &mut Position) -> Option<Action> {
+        for _ in 0..20 {
+            match vehicle.get_action(position, 1.0).await {
+                Some(Action::Move(node)) => *position = node.position,
+                action => return action,
+            }
+        }
+        None
+    }
+
+    #[tokio::test]
+    async fn action_outcome() {
+        let track_graph = Arc::new(get_track_graph().await);
+        let mut vehicle = Vehicle::new(2000, Skill::Item, track_graph.clone()).await;
+        let (sender, mut receiver, _persisted) = event_sender();
+        vehicle.set_event_sender(sender).await;
+        let mut position: Position = (-1.0, 2.0, 0.0).into();
+        for _ in 0..3 {
+            vehicle.get_action(&position, 1.0).await;
+        }
+
+        let path = track_graph.find_path("S3", "S2").await.unwrap();
+        let actions = ActionSequenceBuilder::new().move_path(&path).suck().build();
+        vehicle.processing(7, actions).await.unwrap();
+        assert!(matches!(
+            drive(&mut vehicle, &mut position).await,
+            Some(Action::Suck(_))
+        ));
+        let empty = Outcome::Failure {
+            code: "source_empty".to_string(),
+        };
+        for _ in 0..constant::VEHICLE_ACTION_RETRIES {
+            vehicle.set_outcome(Some(empty.clone()));
+            assert!(matches!(
+                vehicle.get_action(&position, 1.0).await,
+                Some(Action::Suck(_))
+            ));
+        }
+        // Nothing aboard, the task fails and the vehicle parks.
+        vehicle.set_outcome(Some(empty));
+        assert!(matches!(
+            vehicle.get_action(&position, 1.0).await,
+            Some(Action::Move(_))
+        ));
+        let status = vehicle.status().await;
+        assert_eq!((status.state, status.task_id), ("parking", None));
+
+        let path = track_graph.find_path("S2", "S1").await.unwrap();
+        let actions = ActionSequenceBuilder::new()
+            .action(Action::Move(track_graph.node("S2").await.unwrap()))
+            .suck()
+            .move_path(&path)
+            .drop()
+            .build();
+        vehicle.processing(8, actions).await.unwrap();
+        assert!(matches!(
+            drive(&mut vehicle, &mut position).await,
+            Some(Action::Suck(_))
+        ));
+        vehicle.set_outcome(Some(Outcome::Success));
+        assert!(matches!(
+            drive(&mut vehicle, &mut position).await,
+            Some(Action::Drop(station)) if station.node.as_ref().unwrap().name == "S1"
+        ));
+        let full = Outcome::Partial {
+            count: 0,
+            expected: 16,
+        };
+        for _ in 0..=constant::VEHICLE_ACTION_RETRIES {
+            vehicle.set_outcome(Some(full.clone()));
+            vehicle.get_action(&position, 1.0).await;
+        }
+        // Still loaded, so off to the shipping dock.
+        assert_eq!(vehicle.status().await.state, "unloading");
+        assert!(matches!(
+            drive(&mut vehicle, &mut position).await,
+            Some(Action::Drop(station)) if station.node.as_ref().unwrap().name == "S3"
+        ));
+        vehicle.set_outcome(Some(Outcome::Success));
+        assert!(matches!(
+            vehicle.get_action(&position, 1.0).await,
+            Some(Action::Move(_))
+        ));
+        assert_eq!(vehicle.status().await.state, "parking");
+
+        let mut aborted = Vec::new();
+        while let Ok(event) = receiver.try_recv() {
+            if let Event::ProcessAborted {
+                task_id,
+                requeue,
+                reason,
+                ..
+            } = event
+            {
+                aborted.push((task_id, requeue, reason));
+            }
+        }
+        assert_eq!(
+            aborted,
+            [
+                (7, false, "suck at S2 failure source_empty".to_string()),
+                (8, false, "drop at S1 partial 0/16".to_string())
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn partial_pickup() {
+        let track_graph = Arc::new(get_track_graph().await);
+        let mut vehicle = Vehicle::new(2000, Skill::Item, track_graph.clone()).await;
+        let mut position: Position = (-1.0, 2.0, 0.0).into();
+        for _ in 0..3 {
+            vehicle.get_action(&position, 1.0).await;
+        }
+
+        let cargo = Cargo {
+            name: Some("copper_plate".to_string()),
+            quantity: Some(32),
+        };
+        let to_begin = track_graph.find_path("S3", "S2").await.unwrap();
+        let to_end = track_graph.find_path("S2", "S1").await.unwrap();
+        let actions = ActionSequenceBuilder::new()
+            .move_path(&to_begin)
+            .suck()
+            .cargo(&cargo, None)
+            .move_path(&to_end)
+            .drop()
+            .cargo(&cargo, None)
+            .build();
+        vehicle.processing(7, actions).await.unwrap();
+        let quantity = |action: Option<Action>| match action {
+            Some(Action::Suck(station) | Action::Drop(station)) => station.cargo.quantity,
+            _ => None,
+        };
+        assert_eq!(quantity(drive(&mut vehicle, &mut position).await), Some(32));
+
+        // Each retry asks for the rest only.
+        vehicle.set_outcome(Some(Outcome::Partial {
+            count: 20,
+            expected: 32,
+        }));
+        assert_eq!(quantity(vehicle.get_action(&position, 1.0).await), Some(12));
+        vehicle.set_outcome(Some(Outcome::Partial {
+            count: 4,
+            expected: 12,
+        }));
+        assert_eq!(quantity(vehicle.get_action(&position, 1.0).await), Some(8));
+
+        // The source ran dry, the 24 aboard are delivered.
+        vehicle.set_outcome(Some(Outcome::Failure {
+            code: "source_empty".to_string(),
+        }));
+        assert!(matches!(
+            vehicle.get_action(&position, 1.0).await,
+            Some(Action::Move(_))
+        ));
+        assert_eq!(quantity(drive(&mut vehicle, &mut position).await), Some(24));
+        let (sender, mut receiver, _persisted) = event_sender();
+        vehicle.set_event_sender(sender).await;
+        vehicle.set_outcome(Some(Outcome::Success));
+        vehicle.get_action(&position, 1.0).await;
+        let status = vehicle.status().await;
+        assert_eq!((status.state, status.task_id), ("parking", None));
+        // Done, but not as if all 32 had arrived.
+        let shortfall =
+            std::iter::from_fn(|| receiver.try_recv().ok()).find_map(|event| match event {
+                Event::ProcessDone {
+                    task_id: 7,
+                    shortfall,
+                    ..
+                } => Some(shortfall),
+                _ => None,
+            });
+        assert_eq!(
+            shortfall,
+            Some(Some(
+                "24 of 32 picked up, suck at S2 failure source_empty".to_string()
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn partial_fill() {
+        let track_graph = Arc::new(get_track_graph().await);
+        let mut vehicle = Vehicle::new(4000, Skill::Fluid, track_graph.clone()).await;
+        let mut position: Position = (-1.0, 2.0, 0.0).into();
+        for _ in 0..3 {
+            vehicle.get_action(&position, 1.0).await;
+        }
+
+        let cargo = Cargo {
+            name: Some("water".to_string()),
+            quantity: Some(1000),
+        };
+        let to_begin = track_graph.find_path("S3", "S2").await.unwrap();
+        let to_end = track_graph.find_path("S2", "S1").await.unwrap();
+        let actions = ActionSequenceBuilder::new()
+            .move_path(&to_begin)
+            .suck()
+            .cargo(&cargo, None)
+            .move_path(&to_end)
+            .fill()
+            .cargo(&cargo, None)
+            .build();
+        vehicle.processing(7, actions).await.unwrap();
+        let quantity = |action: Option<Action>| match action {
+            Some(Action::Suck(station) | Action::Fill(station)) => station.cargo.quantity,
+            _ => None,
+        };
+        assert_eq!(
+            quantity(drive(&mut vehicle, &mut position).await),
+            Some(1000)
+        );
+        vehicle.set_outcome(Some(Outcome::Success));
+        vehicle.get_action(&position, 1.0).await;
+        assert_eq!(
+            quantity(drive(&mut vehicle, &mut position).await),
+            Some(1000)
+        );
+
+        // Millibuckets, the retry fills the rest.
+        vehicle.set_outcome(Some(Outcome::Partial {
+            count: 400,
+            expected: 1000,
+        }));
+        assert_eq!(
+            quantity(vehicle.get_action(&position, 1.0).await),
+            Some(600)
+        );
+
+        // Reporting more than was left counts as filled.
+        vehicle.set_outcome(Some(Outcome::Partial {
+            count: 900,
+            expected: 600,
+        }));
+        vehicle.get_action(&position, 1.0).await;
+        let status = vehicle.status().await;
+        assert_eq!((status.state, status.task_id), ("parking", None));
+    }
+
+    #[tokio::test]
+    async fn opportunistic_charging() {
+        let track_graph = Arc::new(get_track_graph().await);
+        let mut vehicle = Vehicle::new(2000, Skill::Item, track_graph.clone()).await;
+        let mut position: Position = (-1.0, 2.0, 0.0).into();
+        for _ in 0..5 {
+            if let Some(Action::Move(node)) = vehicle.get_action(&position, 1.0).await {
+                position = node.position;
+            }
+        }
+        let at_p2: Position = (0.0, 0.0, 0.0).into();
+        assert_eq!(position, at_p2);
+        assert_eq!(vehicle.status().await.state, "park_done");
+        let locked = async || {
+            let mut nodes: Vec<i32> = track_graph
+                .locks()
+                .await
+                .iter()
+                .map(|lock| lock.node_id)
+                .collect();
+            nodes.sort();
+            nodes
+        };
+        assert_eq!(locked().await, [2]);
+
+        // Work is waiting, so no topping up.
+        vehicle.set_pending_work(true);
+        assert!(vehicle.get_action(&at_p2, 0.55).await.is_none());
+        assert_eq!(vehicle.status().await.state, "park_done");
+
+        vehicle.set_pending_work(false);
+        assert!(matches!(
+            vehicle.get_action(&at_p2, 0.55).await,
+            Some(Action::Move(node)) if node.name == "A6"
+        ));
+        assert_eq!(vehicle.status().await.state, "charging");
+        assert_eq!(locked().await, [3]);
+        assert!(vehicle.idle().await);
+
+        vehicle.get_action(&at_p2, 0.4).await;
+        assert!(!vehicle.idle().await);
+
+        // Charged enough again, pulled off for a task.
+        vehicle.get_action(&at_p2, 0.6).await;
+        assert!(vehicle.idle().await);
+        let (sender, mut receiver, mut persisted) = event_sender();
+        vehicle.set_event_sender(sender).await;
+        let path = track_graph.find_path("P2", "S2").await.unwrap();
+        let actions = ActionSequenceBuilder::new().move_path(&path).suck().build();
+        vehicle.processing(7, actions).await.unwrap();
+        assert_eq!(vehicle.status().await.state, "processing");
+        assert!(matches!(
+            receiver.try_recv().unwrap(),
+            Event::ChargeDone { vehicle_id: 2000 }
+        ));
+        assert!(matches!(
+            receiver.try_recv().unwrap(),
+            Event::ProcessStart { task_id: 7, .. }
+        ));
+        // Only the task event waits for the database.
+        assert!(matches!(
+            persisted.try_recv().unwrap(),
+            Event::ProcessStart { task_id: 7, .. }
+        ));
+        assert!(persisted.try_recv().is_err());
+        assert!(locked().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn fork_exclusion() {
+        let track_graph = Arc::new(get_track_graph().await);
+        let mut vehicle = Vehicle::new(2000, Skill::Item, track_graph.clone()).await;
+        assert!(
+            matches!(vehicle.get_action(&(2.0, 4.0, 0.0).into(), 1.0).await.unwrap(), Action::Move(node) if node.name == "S1")
+        );
+
+        // 3000 is on the switch at A3, the move into it is held.
+        assert_eq!(track_graph.acquire_zone(3000, 9).await, None);
+        assert!(matches!(
+            vehicle
+                .get_action(&(1.0, 3.0, 0.0).into(), 1.0)
+                .await
+                .unwrap(),
+            Action::Wait
+        ));
+        assert_eq!(vehicle.waiting_for(), Some(3000));
+
+        track_graph.release_zones(3000, None).await;
+        assert!(
+            matches!(vehicle.get_action(&(1.0, 3.0, 0.0).into(), 1.0).await.unwrap(), Action::Move(node) if node.name == "A3")
+        );
+        assert_eq!(track_graph.zones().await, [(9, 2000)]);
+        assert_eq!(track_graph.blocker(3000, 8, 9).await, Some(2000));
+
+        // A3 is let go once A5 is reached, A5 once the vehicle is off the forks.
+        assert!(
+            matches!(vehicle.get_action(&(1.0, 2.0, 0.0).into(), 1.0).await.unwrap(), Action::Move(node) if node.name == "A5")
+        );
+        assert!(
+            matches!(vehicle.get_action(&(0.0, 2.0, 0.0).into(), 1.0).await.unwrap(), Action::Move(node) if node.name == "S3")
+        );
+        assert_eq!(track_graph.zones().await, [(11, 2000)]);
+        vehicle.get_action(&(-1.0, 2.0, 0.0).into(), 1.0).await;
+        assert!(track_graph.zones().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn zone_deadlock() {
+        let track_graph = Arc::new(get_track_graph().await);
+        let mut vehicle = Vehicle::new(2000, Skill::Item, track_graph.clone()).await;
+        vehicle.get_action(&(2.0, 4.0, 0.0).into(), 1.0).await;
+
+        // 2000 holds the switch at A5 and waits for A3, 3000 holds A3 and waits for A5.
+        assert_eq!(track_graph.acquire_zone(3000, 9).await, None);
+        assert!(matches!(
+            vehicle
+                .get_action(&(1.0, 3.0, 0.0).into(), 1.0)
+                .await
+                .unwrap(),
+            Action::Wait
+        ));
+        assert_eq!(vehicle.waiting_for(), Some(3000));
+        assert_eq!(track_graph.acquire_zone(2000, 11).await, None);
+        assert_eq!(track_graph.acquire_zone(3000, 11).await, Some(2000));
+
+        assert_eq!(vehicle.give_way().await, GiveWay::BackedOff);
+        assert_eq!(track_graph.zones().await, [(9, 3000)]);
+        assert_eq!(track_graph.acquire_zone(3000, 11).await, None);
+    }
+
+    #[tokio::test]
+    async fn wait_for_lift() {
+        let layout = serde_json::from_str::<Layout>(
+            r#"{
+                "version": 1,
+                "nodes": [
+                    {"name": "P1", "type": "parking_station", "x": 0, "y": 0, "z": 0},
+                    {"name": "A1", "type": "fork", "x": 1, "y": 0, "z": 0},
+                    {"name": "L1", "type": "lift", "x": 2, "y": 0, "z": 0},
+                    {"name": "L2", "type": "lift", "x": 2, "y": 0, "z": 1},
+                    {"name": "S3", "type": "shipping_dock", "x": 3, "y": 0, "z": 1}
+                ],
+                "edges": [
+                    {"begin": "P1", "end": "A1", "cost": 1, "reverse_cost": 1},
+                    {"begin": "A1", "end": "L1", "cost": 1, "reverse_cost": 1},
+                    {"begin": "L1", "end": "L2", "cost": 1, "reverse_cost": 1},
+                    {"begin": "L2", "end": "S3", "cost": 1, "reverse_cost": 1}
+                ]
+            }"#,
+        )
+        .unwrap();
+        let (nodes, edges) = layout.to_graph().unwrap();
+        let track_graph = Arc::new(Graph::new(MemoryStore::new(nodes, edges)).await.unwrap());
+        let mut vehicle = Vehicle::new(2000, Skill::Item, track_graph.clone()).await;
+
+        assert!(
+            matches!(vehicle.get_action(&(0.5, 0.0, 0.0).into(), 1.0).await.unwrap(), Action::Move(node) if node.name == "A1")
+        );
+        // 3000 rides the lift, 2000 waits at the entrance.
+        assert_eq!(track_graph.acquire_zone(3000, 4).await, None);
+        for _ in 0..2 {
+            assert!(
+                matches!(vehicle.get_action(&(1.0, 0.0, 0.0).into(), 1.0).await.unwrap(), Action::WaitForLift(node) if node.name == "L1")
+            );
+            assert_eq!(vehicle.waiting_for(), Some(3000));
+        }
+
+        track_graph.release_zones(3000, None).await;
+        assert!(
+            matches!(vehicle.get_action(&(1.0, 0.0, 0.0).into(), 1.0).await.unwrap(), Action::Move(node) if node.name == "L1")
+        );
+        assert_eq!(track_graph.acquire_zone(3000, 3).await, Some(2000));
+        assert_eq!(track_graph.blocker(3000, 2, 3).await, Some(2000));
+        assert!(
+            matches!(vehicle.get_action(&(2.0, 0.0, 0.0).into(), 1.0).await.unwrap(), Action::Move(node) if node.name == "L2")
+        );
+        assert!(
+            matches!(vehicle.get_action(&(2.0, 0.0, 1.0).into(), 1.0).await.unwrap(), Action::Move(node) if node.name == "S3")
+        );
+        assert!(matches!(
+            vehicle
+                .get_action(&(3.0, 0.0, 1.0).into(), 1.0)
+                .await
+                .unwrap(),
+            Action::Drop(_)
+        ));
+        // Off the lift at S3, it is free again.
+        assert_eq!(track_graph.acquire_zone(3000, 3).await, None);
     }
 
     #[tokio::test]
@@ -462,7 +1925,7 @@ mod tests {
         let track_graph = get_track_graph().await;
         let track_graph = Arc::new(track_graph);
 
-        let mut vehicle = Vehicle::new(2000, track_graph.clone()).await;
+        let mut vehicle = Vehicle::new(2000, Skill::Item, track_graph.clone()).await;
 
         // move to shortest node
         assert!(
@@ -488,7 +1951,7 @@ mod tests {
                 .get_action(&(-1.0, 2.0, 0.0).into(), 1.0)
                 .await
                 .unwrap(),
-            Action::Drop
+            Action::Drop(station) if station.interaction.port.as_deref() == Some("dock")
         ));
 
         assert!(
@@ -504,11 +1967,137 @@ mod tests {
         assert!(
             matches!(vehicle.get_action(&(0.0, 1.0, 0.0).into(), 1.0).await.unwrap(), Action::Move(node) if node.name == "P2")
         );
+        assert!(
+            vehicle
+                .get_action(&(0.0, 0.0, 0.0).into(), 1.0)
+                .await
+                .is_none()
+        );
+        assert!(matches!(*vehicle.state.read().await, State::ParkDone));
+    }
+
+    #[tokio::test]
+    async fn hold_on_reserved_edge() {
+        let track_graph = Arc::new(get_track_graph().await);
+        let mut vehicle = Vehicle::new(2000, Skill::Item, track_graph.clone()).await;
+
+        vehicle.get_action(&(-2.0, 2.0, 0.0).into(), 1.0).await;
         assert!(matches!(
-            vehicle.get_action(&(0.0, 0.0, 0.0).into(), 1.0).await,
-            None
+            vehicle
+                .get_action(&(-1.0, 2.0, 0.0).into(), 1.0)
+                .await
+                .unwrap(),
+            Action::Drop(_)
         ));
-        assert!(matches!(*vehicle.state.read().await, State::ParkDone));
+
+        // Another vehicle got on S3 - A5 first.
+        let s3 = track_graph.node("S3").await.unwrap();
+        let a5 = track_graph.node("A5").await.unwrap();
+        track_graph
+            .reserve(
+                2500,
+                &[
+                    (a5, chrono::TimeDelta::seconds(10)),
+                    (s3, chrono::TimeDelta::zero()),
+                ],
+                Utc::now() - chrono::TimeDelta::seconds(1),
+            )
+            .await;
+        assert!(matches!(
+            vehicle
+                .get_action(&(-1.0, 2.0, 0.0).into(), 1.0)
+                .await
+                .unwrap(),
+            Action::Wait
+        ));
+        assert!(matches!(*vehicle.state.read().await, State::Parking(_)));
+        assert_eq!(vehicle.waiting_for(), Some(2500));
+
+        // The sample track has no way around S3 - A5.
+        assert_eq!(vehicle.give_way().await, GiveWay::BackedOff);
+        assert_eq!(vehicle.waiting_for(), None);
+
+        track_graph.release_reservations(2500).await;
+        assert!(
+            matches!(vehicle.get_action(&(-1.0, 2.0, 0.0).into(), 1.0).await.unwrap(), Action::Move(node) if node.name == "A5")
+        );
+        assert!(
+            track_graph
+                .reservations()
+                .await
+                .iter()
+                .all(|reservation| reservation.vehicle_id == 2000)
+        );
+    }
+
+    #[tokio::test]
+    async fn reroute_around_disabled() {
+        let layout = serde_json::from_str::<Layout>(
+            r#"{
+                "version": 1,
+                "nodes": [
+                    {"name": "S3", "type": "shipping_dock", "x": 0, "y": 0, "z": 0},
+                    {"name": "A", "type": "fork", "x": 1, "y": 0, "z": 0},
+                    {"name": "B", "type": "fork", "x": 2, "y": 1, "z": 0},
+                    {"name": "C", "type": "fork", "x": 2, "y": -1, "z": 0},
+                    {"name": "D", "type": "fork", "x": 3, "y": 0, "z": 0},
+                    {"name": "P1", "type": "parking_station", "x": 4, "y": 0, "z": 0},
+                    {"name": "C1", "type": "charging_station", "x": 3, "y": 1, "z": 0}
+                ],
+                "edges": [
+                    {"begin": "S3", "end": "A", "cost": 1, "reverse_cost": 1},
+                    {"begin": "A", "end": "B", "cost": 1, "reverse_cost": 1},
+                    {"begin": "B", "end": "D", "cost": 1, "reverse_cost": 1},
+                    {"begin": "A", "end": "C", "cost": 1.5, "reverse_cost": 1.5},
+                    {"begin": "C", "end": "D", "cost": 1.5, "reverse_cost": 1.5},
+                    {"begin": "D", "end": "P1", "cost": 1, "reverse_cost": 1},
+                    {"begin": "D", "end": "C1", "cost": 1, "reverse_cost": 1}
+                ]
+            }"#,
+        )
+        .unwrap();
+        let (nodes, edges) = layout.to_graph().unwrap();
+        let track_graph = Arc::new(Graph::new(MemoryStore::new(nodes, edges)).await.unwrap());
+        let mut vehicle = Vehicle::new(2000, Skill::Item, track_graph.clone()).await;
+
+        vehicle.get_action(&(-1.0, 0.0, 0.0).into(), 1.0).await;
+        assert!(matches!(
+            vehicle
+                .get_action(&(0.0, 0.0, 0.0).into(), 1.0)
+                .await
+                .unwrap(),
+            Action::Drop(_)
+        ));
+        assert!(
+            matches!(vehicle.get_action(&(0.0, 0.0, 0.0).into(), 1.0).await.unwrap(), Action::Move(node) if node.name == "A")
+        );
+        assert!(matches!(*vehicle.state.read().await, State::Parking(_)));
+        assert!(!vehicle.reroute_disabled().await.unwrap());
+
+        track_graph
+            .disable(track::Resource::Node(3), "rail rebuild", "ops")
+            .await
+            .unwrap();
+        assert!(vehicle.reroute_disabled().await.unwrap());
+        assert!(
+            matches!(vehicle.get_action(&(0.0, 0.0, 0.0).into(), 1.0).await.unwrap(), Action::Move(node) if node.name == "A")
+        );
+        assert!(
+            matches!(vehicle.get_action(&(1.0, 0.0, 0.0).into(), 1.0).await.unwrap(), Action::Move(node) if node.name == "C")
+        );
+
+        // With the way around closed as well the vehicle keeps its route.
+        track_graph
+            .disable(track::Resource::Node(4), "rail rebuild", "ops")
+            .await
+            .unwrap();
+        assert!(matches!(
+            vehicle.reroute_disabled().await,
+            Err(Error::Track(track::Error::NoRoute { .. }))
+        ));
+        assert!(track_graph.enable(track::Resource::Node(3)).await.unwrap());
+        assert!(!track_graph.enable(track::Resource::Node(3)).await.unwrap());
+        assert!(vehicle.reroute_disabled().await.unwrap());
     }
 
     #[tokio::test]
@@ -516,7 +2105,7 @@ mod tests {
         let track_graph = get_track_graph().await;
         let track_graph = Arc::new(track_graph);
 
-        let mut vehicle = Vehicle::new(2000, track_graph.clone()).await;
+        let mut vehicle = Vehicle::new(2000, Skill::Item, track_graph.clone()).await;
 
         assert!(matches!(vehicle
             .get_action(&(-2.0, 2.0, 0.0).into(), 0.1)
@@ -528,7 +2117,7 @@ mod tests {
                 .get_action(&(-1.0, 2.0, 0.0).into(), 0.8)
                 .await
                 .unwrap(),
-            Action::Drop
+            Action::Drop(_)
         ));
 
         vehicle.get_action(&(-1.0, 2.0, 0.0).into(), 0.8).await;
@@ -553,10 +2142,12 @@ mod tests {
             matches!(vehicle.get_action(&(1.0, 1.0, 0.0).into(), 0.2).await.unwrap(), Action::Move(node) if node.name == "C1")
         );
 
-        assert!(matches!(
-            vehicle.get_action(&(1.0, 0.0, 0.0).into(), 0.2).await,
-            None
-        ));
+        assert!(
+            vehicle
+                .get_action(&(1.0, 0.0, 0.0).into(), 0.2)
+                .await
+                .is_none()
+        );
 
         // charge over
         assert!(
@@ -1,7 +1,8 @@
-use crate::constant;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, PartialEq, Clone, sqlx::Type)]
+#[derive(Debug, PartialEq, Clone, sqlx::Type, Serialize, Deserialize)]
 #[sqlx(type_name = "tooltype")]
+#[serde(rename_all = "snake_case")]
 pub enum ToolType {
     #[sqlx(rename = "wrench")]
     Wrench, // 扳手
@@ -17,39 +18,10 @@ pub enum ToolType {
     SoftHammer, // 软锤
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Skill {
     Item,
     Fluid,
     UseTool(ToolType),
 }
-
-impl Skill {
-    pub fn from_id(id: &i32) -> Self {
-        if constant::ITEM_VEHICLE_ID_RANGE.contains(id) {
-            return Self::Item;
-        }
-        if constant::FLUID_VEHICLE_ID_RANGE.contains(id) {
-            return Self::Fluid;
-        }
-        if constant::USE_TOOL_WRENCH_VEHICLE_ID_RANGE.contains(id) {
-            return Self::UseTool(ToolType::Wrench);
-        }
-        if constant::USE_TOOL_SOLDER_VEHICLE_ID_RANGE.contains(id) {
-            return Self::UseTool(ToolType::Solder);
-        }
-        if constant::USE_TOOL_CROWBAR_VEHICLE_ID_RANGE.contains(id) {
-            return Self::UseTool(ToolType::Crowbar);
-        }
-        if constant::USE_TOOL_SCREWDRIVER_VEHICLE_ID_RANGE.contains(id) {
-            return Self::UseTool(ToolType::Screwdriver);
-        }
-        if constant::USE_TOOL_WIRENIPPER_VEHICLE_ID_RANGE.contains(id) {
-            return Self::UseTool(ToolType::WireNipper);
-        }
-        if constant::USE_TOOL_SOFT_HAMMER_VEHICLE_ID_RANGE.contains(id) {
-            return Self::UseTool(ToolType::SoftHammer);
-        }
-        panic!("id: {}, can not have any skill.", id);
-    }
-}
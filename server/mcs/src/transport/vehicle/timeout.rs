@@ -1,37 +1,61 @@
 use std::sync::Arc;
 
-use tokio::{sync::RwLock, time};
+use tokio::{sync::RwLock, task::JoinHandle, time};
 
 use chrono::Local;
 
-use crate::{constant, transport::vehicle::State};
+use crate::transport::vehicle::{Event, EventSender, State};
 
 #[derive(Debug)]
 pub struct Timeout {
     time_stamp: Arc<RwLock<chrono::DateTime<Local>>>,
+    sender: Arc<RwLock<Option<EventSender>>>,
+    task: JoinHandle<()>,
 }
 
 impl Timeout {
-    pub fn new(state: Arc<RwLock<State>>) -> Self {
+    pub fn new(id: i32, state: Arc<RwLock<State>>, timeout: i64) -> Self {
         let time_stamp = Arc::new(RwLock::new(Local::now()));
+        let sender: Arc<RwLock<Option<EventSender>>> = Arc::new(RwLock::new(None));
 
         let inner_time_stamp = time_stamp.clone();
-        tokio::spawn(async move {
-            let mut interval = time::interval(time::Duration::from_secs(
-                constant::VEHICLE_ONLINE_UPDATE_TIMEOUT as u64,
-            ));
+        let inner_sender = sender.clone();
+        let task = tokio::spawn(async move {
+            let mut interval = time::interval(time::Duration::from_secs(timeout as u64));
             loop {
                 interval.tick().await;
                 let now = chrono::Local::now();
                 let dt = (now - *inner_time_stamp.read().await).num_seconds();
                 // println!("detect! dt={}", dt);
-                if dt > constant::VEHICLE_ONLINE_UPDATE_TIMEOUT {
-                    *state.write().await = State::Offline;
+                if dt > timeout {
+                    let mut state = state.write().await;
+                    if let State::Offline = *state {
+                        continue;
+                    }
+                    let from = state.name();
+                    *state = State::Offline;
+                    drop(state);
+                    if let Some(sender) = &*inner_sender.read().await {
+                        let event = Event::StateChanged {
+                            vehicle_id: id,
+                            from,
+                            to: State::Offline.name(),
+                        };
+                        let _ = sender.send(event).await;
+                    }
                 }
             }
         });
 
-        Self { time_stamp }
+        Self {
+            time_stamp,
+            sender,
+            task,
+        }
+    }
+
+    pub async fn set_event_sender(&self, sender: EventSender) {
+        *self.sender.write().await = Some(sender);
     }
 
     pub async fn update(&mut self) {
@@ -39,39 +63,34 @@ impl Timeout {
     }
 }
 
+impl Drop for Timeout {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use dotenvy::dotenv;
-    use sqlx::postgres::PgPoolOptions;
     use tokio::time;
 
-    use crate::{
-        db_manager::DbManager,
-        transport::{
-            track::Graph,
-            vehicle::{State, Vehicle},
-        },
+    use crate::transport::{
+        track::{Graph, MemoryStore, sample_layout},
+        vehicle::{Skill, State, Vehicle},
     };
 
     use super::*;
 
     async fn get_track_graph() -> Graph {
-        dotenv().ok();
-        let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
-        println!("Connecting to database: {}", database_url);
-        let pool = PgPoolOptions::new()
-            .max_connections(5)
-            .connect(&database_url)
-            .await
-            .expect("Failed to create pool");
-        let db_manaer = DbManager::new(pool);
-        Graph::new(db_manaer).await
+        let (nodes, edges) = sample_layout();
+        Graph::new(MemoryStore::new(nodes, edges)).await.unwrap()
     }
 
     #[tokio::test]
     async fn vehicle_timeout() {
         let track_graph = Arc::new(get_track_graph().await);
-        let vehicle = Arc::new(RwLock::new(Vehicle::new(2000, track_graph).await));
+        let vehicle = Arc::new(RwLock::new(
+            Vehicle::new(2000, Skill::Item, track_graph).await,
+        ));
 
         assert!(matches!(
             *vehicle.read().await.state.read().await,
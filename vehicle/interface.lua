@@ -74,6 +74,67 @@ function Robot:drain(side, count)
     print("drain", sides_to_string(side), count)
 end
 
+---@class Stack
+---@field name string
+---@field size number
+
+---comment
+---@param side Sides
+---@param slot number
+---@return Stack | nil
+function Robot:stack_in_slot(side, slot)
+    print("stack in " .. sides_to_string(side) .. " " .. slot)
+    return nil
+end
+
+---comment
+---@param slot number
+---@return Stack | nil
+function Robot:stack_in_internal_slot(slot)
+    print("stack in internal " .. slot)
+    return nil
+end
+
+---comment
+---@param side Sides
+---@param slot number
+---@param count number
+---@return number moved
+function Robot:suck_from_slot(side, slot, count)
+    print("suck from " .. sides_to_string(side) .. " " .. slot .. " " .. count)
+    return 0
+end
+
+---comment
+---@param slot number internal slot to take from
+---@param side Sides
+---@param target number slot of the inventory on `side`
+---@param count number
+---@return number moved
+function Robot:drop_into_slot(slot, side, target, count)
+    print("select " .. slot, " drop into " .. sides_to_string(side) .. " " .. target .. " " .. count)
+    return 0
+end
+
+---comment
+---@param tank number
+---@return Stack | nil size in millibuckets
+function Robot:fluid_in_tank(tank)
+    print("fluid in tank " .. tank)
+    return nil
+end
+
+---comment
+---@param tank number internal tank to take from
+---@param side Sides
+---@param target number tank of the station on `side`
+---@param count number millibuckets
+---@return number moved
+function Robot:fill_into_tank(tank, side, target, count)
+    print("select tank " .. tank, " fill into " .. sides_to_string(side) .. " " .. target .. " " .. count)
+    return 0
+end
+
 --------------------------------------------------------------------
 
 function os.sleep(secounds)
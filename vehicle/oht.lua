@@ -72,28 +72,117 @@ function OHT:move(pos)
     self.pos = pos
 end
 
----comment
----@param side Sides
-function OHT:drop(side)
-    for i = 1, 16 do
-        self.robot:drop(i, side)
+---@class Work
+---@field side Sides
+---@field slots {first: number, last: number} | nil slots, or tanks for fill
+---@field name string | nil
+---@field quantity number | nil items, or millibuckets of fluid
+
+---@class Outcome
+---@field status "success" | "partial" | "failure"
+---@field count number | nil items, or millibuckets of fluid
+---@field expected number | nil items, or millibuckets of fluid
+---@field code string | nil
+
+local INVENTORY_SIZE = 16
+local TANK_COUNT = 16
+
+---@param stack Stack | nil
+---@param name string | nil
+---@return boolean
+local function matches(stack, name)
+    return stack ~= nil and (name == nil or stack.name == name)
+end
+
+---@param moved number
+---@param quantity number | nil
+---@param code string reported when nothing moved
+---@return Outcome
+local function outcome(moved, quantity, code)
+    if moved == 0 then
+        return { status = "failure", code = code }
     end
+    if quantity == nil or moved >= quantity then
+        return { status = "success" }
+    end
+    return { status = "partial", count = moved, expected = quantity }
 end
 
----comment
----@param side Sides
-function OHT:suck(side)
-    for _ = 1, 16 do
-        self.robot:suck(side)
+---Takes `work.quantity` of `work.name` out of the station's slots, everything there when unset.
+---@param work Work
+---@return Outcome
+function OHT:suck(work)
+    local first, last = 1, INVENTORY_SIZE
+    if work.slots then
+        first, last = work.slots.first, work.slots.last
+    end
+    local moved = 0
+    for slot = first, last do
+        local left = work.quantity and work.quantity - moved or math.huge
+        if left <= 0 then
+            break
+        end
+        local stack = self.robot:stack_in_slot(work.side, slot)
+        if matches(stack, work.name) then
+            moved = moved + self.robot:suck_from_slot(work.side, slot, math.min(left, stack.size))
+        end
     end
+    return outcome(moved, work.quantity, "source_empty")
 end
 
----comment
----@param side Sides
-function OHT:fill(side)
-    for i = 1, 16 do
-        self.robot:drop(i, side)
+---Puts `work.quantity` of `work.name` into the station's slots, everything aboard when unset.
+---@param work Work
+---@return Outcome
+function OHT:drop(work)
+    local first, last = 1, INVENTORY_SIZE
+    if work.slots then
+        first, last = work.slots.first, work.slots.last
+    end
+    local moved = 0
+    for slot = 1, INVENTORY_SIZE do
+        local stack = self.robot:stack_in_internal_slot(slot)
+        if matches(stack, work.name) then
+            local size = stack.size
+            for target = first, last do
+                local left = work.quantity and work.quantity - moved or math.huge
+                if left <= 0 or size <= 0 then
+                    break
+                end
+                local dropped = self.robot:drop_into_slot(slot, work.side, target, math.min(left, size))
+                moved = moved + dropped
+                size = size - dropped
+            end
+        end
+    end
+    return outcome(moved, work.quantity, "target_full")
+end
+
+---Pours `work.quantity` millibuckets of `work.name` into the station's tanks, everything aboard
+---when unset.
+---@param work Work
+---@return Outcome
+function OHT:fill(work)
+    local first, last = 1, TANK_COUNT
+    if work.slots then
+        first, last = work.slots.first, work.slots.last
+    end
+    local moved = 0
+    for tank = 1, TANK_COUNT do
+        local fluid = self.robot:fluid_in_tank(tank)
+        if matches(fluid, work.name) then
+            local size = fluid.size
+            for target = first, last do
+                local left = work.quantity and work.quantity - moved or math.huge
+                if left <= 0 or size <= 0 then
+                    break
+                end
+                local filled = self.robot:fill_into_tank(tank, work.side, target, math.min(left, size))
+                moved = moved + filled
+                size = size - filled
+            end
+        end
     end
+    return outcome(moved, work.quantity, "target_full")
 end
 
 ---comment